@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, Once};
+
+use crate::{util, WalkDirOptions};
+
+/// What a file system supports, as found by [`fs_capabilities`].
+///
+/// [`fs_capabilities`]: fn.fs_capabilities.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Capabilities {
+    symlinks: bool,
+    case_sensitive: bool,
+    hard_links: bool,
+}
+
+impl Capabilities {
+    /// Returns true if symbolic links to files can be created.
+    ///
+    /// On Windows, this also depends on whether the current process has the
+    /// privilege to create symbolic links.
+    pub fn symlinks(&self) -> bool {
+        self.symlinks
+    }
+
+    /// Returns true if file names that only differ in case name different
+    /// files.
+    pub fn case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    /// Returns true if hard links to files can be created.
+    pub fn supports_hardlinks(&self) -> bool {
+        self.hard_links
+    }
+}
+
+/// How well an option is honored on this platform.
+///
+/// See [`WalkDir::capabilities`].
+///
+/// [`WalkDir::capabilities`]: struct.WalkDir.html#method.capabilities
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Support {
+    /// The option is honored as documented.
+    Supported,
+    /// The option is only partly honored, for the given reason.
+    Degraded(&'static str),
+    /// The option isn't honored at all. Walking with it enabled yields
+    /// errors.
+    Unsupported,
+}
+
+/// What a configured [`WalkDir`] will honor, as reported by
+/// [`WalkDir::capabilities`].
+///
+/// [`WalkDir`]: struct.WalkDir.html
+/// [`WalkDir::capabilities`]: struct.WalkDir.html#method.capabilities
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WalkCapabilities {
+    options: Vec<(&'static str, Support)>,
+}
+
+impl WalkCapabilities {
+    /// Returns the support for the option with the given name, e.g.,
+    /// `"same_file_system"`.
+    ///
+    /// This returns `None` if the option isn't enabled or if it works the
+    /// same everywhere.
+    pub fn get(&self, option: &str) -> Option<Support> {
+        self.options
+            .iter()
+            .find(|&&(name, _)| name == option)
+            .map(|&(_, support)| support)
+    }
+
+    /// Returns the name of each reported option with its support.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, Support)> + '_ {
+        self.options.iter().copied()
+    }
+
+    /// Returns true if every reported option is supported.
+    pub fn all_supported(&self) -> bool {
+        self.iter().all(|(_, support)| support == Support::Supported)
+    }
+}
+
+/// The platforms whose options differ.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Platform {
+    Unix,
+    Windows,
+    Other,
+}
+
+impl Platform {
+    pub(crate) const CURRENT: Platform = if cfg!(unix) {
+        Platform::Unix
+    } else if cfg!(windows) {
+        Platform::Windows
+    } else {
+        Platform::Other
+    };
+}
+
+/// Reports the support of the platform dependent options enabled in `opts`.
+pub(crate) fn walk_capabilities(
+    opts: &WalkDirOptions,
+    platform: Platform,
+) -> WalkCapabilities {
+    use self::Support::*;
+
+    let native = platform != Platform::Other;
+    let mut options = vec![];
+    if opts.same_file_system {
+        options.push((
+            "same_file_system",
+            if native { Supported } else { Unsupported },
+        ));
+    }
+    if opts.track_file_systems {
+        options.push((
+            "track_file_systems",
+            if native { Supported } else { Unsupported },
+        ));
+    }
+    if opts.cross_fs_via_symlink {
+        let support = if !native {
+            Unsupported
+        } else if !opts.same_file_system || !opts.follow_links {
+            Degraded(
+                "no effect unless same_file_system and follow_links are \
+                 enabled",
+            )
+        } else {
+            Supported
+        };
+        options.push(("cross_fs_via_symlink", support));
+    }
+    if opts.follow_links {
+        options.push((
+            "follow_links",
+            if native { Supported } else { Unsupported },
+        ));
+    }
+    if !opts.follow_mounts {
+        options.push((
+            "follow_mounts",
+            if native { Supported } else { Unsupported },
+        ));
+    }
+    if opts.dedup_hard_links {
+        options.push((
+            "dedup_hard_links",
+            if native { Supported } else { Unsupported },
+        ));
+    }
+    if platform == Platform::Windows
+        && (opts.follow_links || !opts.follow_mounts)
+    {
+        options.push((
+            "max_open",
+            Degraded(
+                "not respected on Windows when follow_links is enabled or \
+                 follow_mounts is disabled",
+            ),
+        ));
+    }
+    WalkCapabilities { options }
+}
+
+/// Returns the capabilities found so far in this process, by device.
+fn cache() -> MutexGuard<'static, HashMap<u64, Capabilities>> {
+    // `Mutex::new` can't be used to initialize a static before Rust 1.63.
+    static INIT: Once = Once::new();
+    static mut CACHE: Option<Mutex<HashMap<u64, Capabilities>>> = None;
+
+    // SAFETY: `CACHE` is only written once, by `INIT`, before any reads.
+    let cache = unsafe {
+        INIT.call_once(|| CACHE = Some(Mutex::new(HashMap::new())));
+        (*std::ptr::addr_of!(CACHE)).as_ref().unwrap()
+    };
+    // Nothing panics while holding the lock, but a poisoned cache is still
+    // just a cache.
+    cache.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Finds out what the file system containing the given directory supports.
+///
+/// This is useful to decide whether, e.g., [`follow_links`] is worth
+/// enabling before walking a directory. The file system is probed by
+/// creating a uniquely named temporary directory inside `dir`, creating a
+/// file, a symbolic link and a hard link in it, and removing it all again.
+/// So `dir` must be writable.
+///
+/// The result is cached by the device that `dir` is on, so that further
+/// calls for directories on the same device don't touch the file system
+/// beyond looking up that device. The cache is shared by all threads, and
+/// concurrent calls for the same device only probe it once. On platforms
+/// other than Unix and Windows, where devices can't be looked up, nothing is
+/// cached.
+///
+/// # Example
+///
+/// ```no_run
+/// use walkdir::{fs_capabilities, WalkDir};
+///
+/// # fn try_main() -> Result<(), std::io::Error> {
+/// let caps = fs_capabilities("foo")?;
+/// for entry in WalkDir::new("foo").follow_links(caps.symlinks()) {
+///     println!("{}", entry?.path().display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`follow_links`]: struct.WalkDir.html#method.follow_links
+pub fn fs_capabilities<P: AsRef<Path>>(dir: P) -> io::Result<Capabilities> {
+    let dir = dir.as_ref();
+    let device = match util::device_num(dir) {
+        Ok(device) => device,
+        Err(_) => return probe(dir),
+    };
+    // The lock is held while probing, so that other threads asking about
+    // the same device wait for the result instead of probing again.
+    let mut cache = cache();
+    if let Some(&caps) = cache.get(&device) {
+        return Ok(caps);
+    }
+    let caps = probe(dir)?;
+    cache.insert(device, caps);
+    Ok(caps)
+}
+
+/// Probes the file system containing `dir`, without consulting the cache.
+fn probe(dir: &Path) -> io::Result<Capabilities> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    #[cfg(test)]
+    PROBES.fetch_add(1, Ordering::SeqCst);
+
+    let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let name = format!(".walkdir-probe-{}-{}", process::id(), count);
+    let tmp = dir.join(name);
+    fs::create_dir(&tmp)?;
+    let result = probe_in(&tmp);
+    let removed = fs::remove_dir_all(&tmp);
+    let caps = result?;
+    removed?;
+    Ok(caps)
+}
+
+fn probe_in(tmp: &Path) -> io::Result<Capabilities> {
+    File::create(tmp.join("a"))?;
+    Ok(Capabilities {
+        symlinks: util::symlink(&tmp.join("a"), &tmp.join("link"), false)
+            .is_ok(),
+        case_sensitive: fs::symlink_metadata(tmp.join("A")).is_err(),
+        hard_links: fs::hard_link(tmp.join("a"), tmp.join("b")).is_ok(),
+    })
+}
+
+/// The number of times a file system was probed in this process.
+#[cfg(test)]
+pub(crate) static PROBES: AtomicUsize = AtomicUsize::new(0);
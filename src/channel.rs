@@ -0,0 +1,179 @@
+use std::panic;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::{DirEntry, Result, WalkDir};
+
+/// A handle to a directory traversal running on its own thread.
+///
+/// Values of this type are created by [`WalkDir::into_channel`].
+///
+/// Dropping this handle detaches the thread. It still stops as soon as the
+/// receiving end of the channel is dropped.
+///
+/// [`WalkDir::into_channel`]: struct.WalkDir.html#method.into_channel
+#[derive(Debug)]
+pub struct WalkHandle {
+    thread: thread::JoinHandle<WalkStats>,
+}
+
+impl WalkHandle {
+    /// Wait for the traversal to finish and return its statistics.
+    ///
+    /// The traversal finishes either when all entries have been sent, or
+    /// when the receiving end of the channel has been dropped.
+    ///
+    /// # Panics
+    ///
+    /// If the traversal thread panicked (for example, in a sorting function
+    /// given to [`sort_by`]), then the panic is propagated to the caller.
+    ///
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    pub fn join(self) -> WalkStats {
+        match self.thread.join() {
+            Ok(stats) => stats,
+            Err(err) => panic::resume_unwind(err),
+        }
+    }
+}
+
+/// Statistics about a directory traversal.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WalkStats {
+    entries: u64,
+    errors: u64,
+    dirs_opened: u64,
+    root_device: Option<u64>,
+    pending_dirs: Vec<(PathBuf, usize)>,
+}
+
+impl WalkStats {
+    /// Returns the number of entries that were yielded.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Returns the number of errors that were yielded.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Returns the number of directories that were opened for reading.
+    ///
+    /// This counts every attempt to open a directory, including ones that
+    /// failed.
+    pub fn dirs_opened(&self) -> u64 {
+        self.dirs_opened
+    }
+
+    /// Returns the device that the [`same_file_system`] option compared
+    /// directories against.
+    ///
+    /// This is the device of the root directory that was descended into. If
+    /// the root path is a symbolic link, then this is the device of the
+    /// link's target. This is `None` if [`same_file_system`] isn't enabled
+    /// or if the root hasn't been handled yet.
+    ///
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    pub fn root_device(&self) -> Option<u64> {
+        self.root_device
+    }
+
+    /// Returns the directories that hadn't been fully read yet, along with
+    /// their depths.
+    ///
+    /// Directories are listed from the root down. This is only ever
+    /// non-empty for statistics returned by [`IntoIter::into_stats`] (or by
+    /// [`WalkHandle::join`] when the receiver was dropped early), since a
+    /// traversal that runs to completion has no pending directories.
+    ///
+    /// [`IntoIter::into_stats`]: struct.IntoIter.html#method.into_stats
+    /// [`WalkHandle::join`]: struct.WalkHandle.html#method.join
+    pub fn pending_dirs(&self) -> &[(PathBuf, usize)] {
+        &self.pending_dirs
+    }
+
+    pub(crate) fn set_root_device(&mut self, device: u64) {
+        self.root_device = Some(device);
+    }
+
+    pub(crate) fn add_result(&mut self, result: &Result<DirEntry>) {
+        if result.is_ok() {
+            self.entries += 1;
+        } else {
+            self.errors += 1;
+        }
+    }
+
+    pub(crate) fn add_dir_opened(&mut self) {
+        self.dirs_opened += 1;
+    }
+
+    pub(crate) fn set_pending_dirs(&mut self, dirs: Vec<(PathBuf, usize)>) {
+        self.pending_dirs = dirs;
+    }
+}
+
+/// The progress of a directory traversal, as reported to the callback given
+/// to [`WalkDir::on_progress`].
+///
+/// [`WalkDir::on_progress`]: struct.WalkDir.html#method.on_progress
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Progress {
+    entries_yielded: u64,
+    dirs_entered: u64,
+    errors: u64,
+}
+
+impl Progress {
+    /// Returns the number of entries yielded so far.
+    pub fn entries_yielded(&self) -> u64 {
+        self.entries_yielded
+    }
+
+    /// Returns the number of directories opened for reading so far.
+    ///
+    /// Like [`WalkStats::dirs_opened`], this includes directories that
+    /// couldn't be opened.
+    ///
+    /// [`WalkStats::dirs_opened`]: struct.WalkStats.html#method.dirs_opened
+    pub fn dirs_entered(&self) -> u64 {
+        self.dirs_entered
+    }
+
+    /// Returns the number of errors yielded so far.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    pub(crate) fn add_result(&mut self, result: &Result<DirEntry>) {
+        if result.is_ok() {
+            self.entries_yielded += 1;
+        } else {
+            self.errors += 1;
+        }
+    }
+
+    pub(crate) fn set_dirs_entered(&mut self, dirs: u64) {
+        self.dirs_entered = dirs;
+    }
+}
+
+pub(crate) fn spawn(
+    walkdir: WalkDir,
+    bound: usize,
+) -> (WalkHandle, Receiver<Result<DirEntry>>) {
+    let (tx, rx) = mpsc::sync_channel(bound);
+    let thread = thread::spawn(move || {
+        let mut it = walkdir.into_iter();
+        for result in &mut it {
+            if tx.send(result).is_err() {
+                // The receiver is gone, so nobody cares about the rest.
+                break;
+            }
+        }
+        it.into_stats()
+    });
+    (WalkHandle { thread }, rx)
+}
@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+/// A saved position of a directory traversal.
+///
+/// Values of this type are created by [`IntoIter::checkpoint`] and are
+/// consumed by [`WalkDir::resume`]. A checkpoint records the path of every
+/// directory that is currently open in the traversal, along with how many
+/// of each directory's entries have already been read. It is independent of
+/// the iterator it came from, so it can be stored and used to resume the
+/// traversal from another process.
+///
+/// A checkpoint can be taken apart with [`dirs`] and put back together with
+/// [`Checkpoint::new`]. When the `serde` feature is enabled, it also
+/// implements `Serialize` and `Deserialize`.
+///
+/// [`IntoIter::checkpoint`]: struct.IntoIter.html#method.checkpoint
+/// [`WalkDir::resume`]: struct.WalkDir.html#method.resume
+/// [`dirs`]: struct.Checkpoint.html#method.dirs
+/// [`Checkpoint::new`]: struct.Checkpoint.html#method.new
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Checkpoint {
+    /// The open directories, from the root down, or `None` if the traversal
+    /// hadn't started yet.
+    dirs: Option<Vec<(PathBuf, u64)>>,
+}
+
+impl Checkpoint {
+    /// Create a checkpoint of a traversal that has started, from the paths of
+    /// its open directories and the number of entries read from each.
+    ///
+    /// The directories must be given from the root down, such that each
+    /// directory is a child of the one before it. An empty list corresponds
+    /// to a traversal that has finished.
+    ///
+    /// A checkpoint of a traversal that hasn't started yet is created with
+    /// `Checkpoint::default()`.
+    pub fn new(dirs: Vec<(PathBuf, u64)>) -> Checkpoint {
+        Checkpoint { dirs: Some(dirs) }
+    }
+
+    /// Returns true if the traversal had started when this checkpoint was
+    /// taken.
+    pub fn is_started(&self) -> bool {
+        self.dirs.is_some()
+    }
+
+    /// Returns the paths of the open directories, from the root down, along
+    /// with the number of entries that had been read from each.
+    ///
+    /// This is empty if the traversal hadn't started or had finished.
+    pub fn dirs(&self) -> impl Iterator<Item = (&Path, u64)> {
+        self.dirs.iter().flatten().map(|(p, n)| (p.as_path(), *n))
+    }
+
+    pub(crate) fn into_dirs(self) -> Option<Vec<(PathBuf, u64)>> {
+        self.dirs
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Checkpoint {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.dirs.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Checkpoint {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Checkpoint, D::Error> {
+        Ok(Checkpoint { dirs: serde::Deserialize::deserialize(deserializer)? })
+    }
+}
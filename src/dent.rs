@@ -1,10 +1,13 @@
-use std::ffi::OsStr;
+use std::convert::TryFrom;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::fs::{self, FileType};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::error::Error;
-use crate::Result;
+use crate::{util, Result};
 
 /// A directory entry.
 ///
@@ -20,12 +23,12 @@ use crate::Result;
 /// are some differences however:
 ///
 /// * All recursive directory iterators must inspect the entry's type.
-/// Therefore, the value is stored and its access is guaranteed to be cheap and
-/// successful.
+///   Therefore, the value is stored and its access is guaranteed to be cheap
+///   and successful.
 /// * [`path`] and [`file_name`] return borrowed variants.
 /// * If [`follow_links`] was enabled on the originating iterator, then all
-/// operations except for [`path`] operate on the link target. Otherwise, all
-/// operations operate on the symbolic link.
+///   operations except for [`path`] operate on the link target. Otherwise, all
+///   operations operate on the symbolic link.
 ///
 /// [`std::fs`]: https://doc.rust-lang.org/stable/std/fs/index.html
 /// [`path`]: #method.path
@@ -45,9 +48,26 @@ pub struct DirEntry {
     follow_link: bool,
     /// The depth at which this entry was generated relative to the root.
     depth: usize,
+    /// Is set when this entry marks the end of a directory's contents. See
+    /// `WalkDir::emit_dir_close`.
+    dir_close: bool,
+    /// The device (Unix) or volume serial number (Windows) of this entry, if
+    /// the iterator needed to find it out anyway.
+    device: Option<u64>,
+    /// The ancestor that this entry leads back to, if it was found to cause
+    /// a loop. See `WalkDir::on_loop`.
+    loop_target: Option<PathBuf>,
+    /// The number of hard links to this entry, once `nlink` has looked it
+    /// up, or `0` before that.
+    ///
+    /// This is atomic so that entries stay `Sync`.
+    nlink: AtomicUsize,
     /// The underlying inode number (Unix only).
     #[cfg(unix)]
     ino: u64,
+    /// Where `ino` came from (Unix only).
+    #[cfg(unix)]
+    ino_source: InoSource,
     /// The underlying metadata (Windows only). We store this on Windows
     /// because this comes for free while reading a directory.
     ///
@@ -82,11 +102,33 @@ impl DirEntry {
     ///
     /// Analogous to [`path`], but moves ownership of the path.
     ///
+    /// The returned path is the same buffer that [`path`] borrows, so this
+    /// never allocates or copies.
+    ///
     /// [`path`]: struct.DirEntry.html#method.path
     pub fn into_path(self) -> PathBuf {
         self.path
     }
 
+    /// Returns the capacity of the buffer holding this entry's path.
+    #[cfg(test)]
+    pub(crate) fn path_buf_capacity(&self) -> usize {
+        self.path.capacity()
+    }
+
+    /// Splits the full path that this entry represents into its components.
+    ///
+    /// This consumes the entry, and the path is only split once. The
+    /// components are the same as those yielded by [`Path::components`], so a
+    /// root directory (e.g., `/`) or a Windows prefix (e.g., `C:`) is its own
+    /// component, and `.` components are dropped except at the start of the
+    /// path.
+    ///
+    /// [`Path::components`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html#method.components
+    pub fn into_components(self) -> Vec<OsString> {
+        self.path.components().map(|c| c.as_os_str().to_os_string()).collect()
+    }
+
     /// Returns `true` if and only if this entry was created from a symbolic
     /// link. This is unaffected by the [`follow_links`] setting.
     ///
@@ -101,17 +143,46 @@ impl DirEntry {
         self.ty.is_symlink() || self.follow_link
     }
 
+    /// Returns `true` if and only if this is the root entry of a walk and
+    /// the root path given to [`WalkDir::new`] was a symbolic link.
+    ///
+    /// A symbolic link root is always followed to find the directory to
+    /// walk (unless [`follow_root_links`] is disabled), but its entry still
+    /// obeys [`follow_links`]: when that's disabled, the entry's
+    /// [`file_type`] is that of the link, and when it's enabled, it's that of
+    /// the target. So the file type alone can't tell whether the root was a
+    /// link, while this method can in both cases.
+    ///
+    /// This always returns `false` for entries that aren't at depth `0`.
+    /// Use [`path_is_symlink`] for those.
+    ///
+    /// [`WalkDir::new`]: struct.WalkDir.html#method.new
+    /// [`follow_root_links`]: struct.WalkDir.html#method.follow_root_links
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`file_type`]: struct.DirEntry.html#method.file_type
+    /// [`path_is_symlink`]: struct.DirEntry.html#method.path_is_symlink
+    pub fn root_was_symlink(&self) -> bool {
+        self.depth == 0 && self.path_is_symlink()
+    }
+
     /// Return the metadata for the file that this entry points to.
     ///
     /// This will follow symbolic links if and only if the [`WalkDir`] value
-    /// has [`follow_links`] enabled.
+    /// has [`follow_links`] enabled. That is, this is the same as
+    /// [`metadata_follow`] when [`follow_links`] is enabled, and as
+    /// [`metadata_no_follow`] otherwise.
     ///
     /// # Platform behavior
     ///
-    /// This always calls [`std::fs::symlink_metadata`].
+    /// On Unix, this calls [`std::fs::metadata`] if [`follow_links`] is
+    /// enabled, and [`std::fs::symlink_metadata`] otherwise.
     ///
-    /// If this entry is a symbolic link and [`follow_links`] is enabled, then
-    /// [`std::fs::metadata`] is called instead.
+    /// On Windows, the metadata is the one that was read when the entry was
+    /// created, so this doesn't touch the file system at all. That is the
+    /// metadata read along with the entry's directory, or, for a symbolic
+    /// link that was followed, the metadata of its target. It has the file's
+    /// attributes, size and times, but not the number of links or the file
+    /// index, which need a handle to the file. See [`nlink`] for the former.
     ///
     /// # Errors
     ///
@@ -121,30 +192,160 @@ impl DirEntry {
     ///
     /// [`WalkDir`]: struct.WalkDir.html
     /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`nlink`]: struct.DirEntry.html#method.nlink
+    /// [`metadata_follow`]: struct.DirEntry.html#method.metadata_follow
+    /// [`metadata_no_follow`]: struct.DirEntry.html#method.metadata_no_follow
     /// [`std::fs::metadata`]: https://doc.rust-lang.org/std/fs/fn.metadata.html
     /// [`std::fs::symlink_metadata`]: https://doc.rust-lang.org/stable/std/fs/fn.symlink_metadata.html
     pub fn metadata(&self) -> Result<fs::Metadata> {
-        self.metadata_internal()
+        if self.follow_link {
+            self.metadata_follow()
+        } else {
+            self.metadata_no_follow()
+        }
+    }
+
+    /// Return the metadata for the file that this entry points to, following
+    /// symbolic links regardless of the [`follow_links`] setting.
+    ///
+    /// This is what [`std::fs::metadata`] returns for this entry's path. On
+    /// Windows, the metadata read along with the entry's directory is used
+    /// when the entry isn't a symbolic link, or when it was followed already.
+    ///
+    /// Otherwise, the file system is asked again on every call. The result
+    /// isn't cached on the entry, since it may change while the walk goes on
+    /// and since most entries never need it. Keep the returned value to
+    /// avoid repeating the system call.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`std::fs::metadata`]: https://doc.rust-lang.org/std/fs/fn.metadata.html
+    pub fn metadata_follow(&self) -> Result<fs::Metadata> {
+        self.metadata_follow_internal()
+            .map_err(|err| Error::from_entry(self, err))
+    }
+
+    /// Return the metadata for this entry itself, never following symbolic
+    /// links regardless of the [`follow_links`] setting.
+    ///
+    /// This is what [`std::fs::symlink_metadata`] returns for this entry's
+    /// path. On Windows, the metadata read along with the entry's directory
+    /// is used unless the entry is a symbolic link that was followed.
+    ///
+    /// Like [`metadata_follow`], this isn't cached otherwise, so each call
+    /// makes a system call.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`metadata_follow`]: struct.DirEntry.html#method.metadata_follow
+    /// [`std::fs::symlink_metadata`]: https://doc.rust-lang.org/stable/std/fs/fn.symlink_metadata.html
+    pub fn metadata_no_follow(&self) -> Result<fs::Metadata> {
+        self.metadata_no_follow_internal()
+            .map_err(|err| Error::from_entry(self, err))
     }
 
     #[cfg(windows)]
-    fn metadata_internal(&self) -> Result<fs::Metadata> {
-        if self.follow_link {
-            fs::metadata(&self.path)
-        } else {
+    fn metadata_follow_internal(&self) -> io::Result<fs::Metadata> {
+        if self.follow_link || !self.ty.is_symlink() {
             Ok(self.metadata.clone())
+        } else {
+            fs::metadata(&self.path)
         }
-        .map_err(|err| Error::from_entry(self, err))
     }
 
     #[cfg(not(windows))]
-    fn metadata_internal(&self) -> Result<fs::Metadata> {
+    fn metadata_follow_internal(&self) -> io::Result<fs::Metadata> {
+        fs::metadata(&self.path)
+    }
+
+    #[cfg(windows)]
+    fn metadata_no_follow_internal(&self) -> io::Result<fs::Metadata> {
         if self.follow_link {
-            fs::metadata(&self.path)
-        } else {
             fs::symlink_metadata(&self.path)
+        } else {
+            Ok(self.metadata.clone())
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn metadata_no_follow_internal(&self) -> io::Result<fs::Metadata> {
+        fs::symlink_metadata(&self.path)
+    }
+
+    /// Returns the number of hard links to the file that this entry points
+    /// to.
+    ///
+    /// A regular file with more than one link can be reached under several
+    /// names. See [`HardLinkMap`] for finding out which entries are links to
+    /// the same file.
+    ///
+    /// This follows symbolic links if and only if [`follow_links`] is
+    /// enabled, just like [`metadata`]. It makes a system call the first time
+    /// it's called (a `stat` on Unix, or opening a handle on Windows), and
+    /// later calls return the same count. If that fails, or if the platform
+    /// is neither Unix nor Windows, then `None` is returned, and the next
+    /// call tries again.
+    ///
+    /// [`HardLinkMap`]: struct.HardLinkMap.html
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`metadata`]: struct.DirEntry.html#method.metadata
+    pub fn nlink(&self) -> Option<u64> {
+        match self.nlink.load(Ordering::Relaxed) {
+            0 => {}
+            nlink => return Some(nlink as u64),
+        }
+        let nlink = self.nlink_internal()?;
+        // A count of `0` (for a file that was removed) isn't worth caching,
+        // and neither is one too big to store.
+        if let Ok(cached) = usize::try_from(nlink) {
+            self.nlink.store(cached, Ordering::Relaxed);
+        }
+        Some(nlink)
+    }
+
+    #[cfg(unix)]
+    fn nlink_internal(&self) -> Option<u64> {
+        use std::os::unix::fs::MetadataExt;
+
+        self.metadata().ok().map(|md| md.nlink())
+    }
+
+    #[cfg(windows)]
+    fn nlink_internal(&self) -> Option<u64> {
+        crate::util::nlink(&self.path).ok()
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn nlink_internal(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns an estimate of the number of sub-directories in this
+    /// directory.
+    ///
+    /// This is meant for things like progress bars and scheduling work,
+    /// which benefit from knowing roughly how much is below a directory
+    /// before descending into it. It is *only* an estimate:
+    ///
+    /// * On Unix, it is derived from the directory's link count. On many
+    ///   file systems (including ext4 and tmpfs), a directory has two links
+    ///   plus one for every sub-directory, so the estimate is exact. Other
+    ///   file systems don't follow this convention. For example, btrfs
+    ///   always reports a single link for directories, in which case `None`
+    ///   is returned. Files that aren't directories are not counted.
+    /// * On all other platforms, `None` is always returned.
+    ///
+    /// `None` is also returned if this entry isn't a directory or if its
+    /// link count can't be read. This uses [`nlink`], so it only makes a
+    /// system call the first time it's called.
+    ///
+    /// [`nlink`]: struct.DirEntry.html#method.nlink
+    pub fn estimated_child_count(&self) -> Option<u64> {
+        if !self.is_dir() || !cfg!(unix) {
+            return None;
+        }
+        match self.nlink() {
+            Some(n) if n >= 2 => Some(n - 2),
+            _ => None,
         }
-        .map_err(|err| Error::from_entry(self, err))
     }
 
     /// Return the file type for the file that this entry points to.
@@ -167,6 +368,31 @@ impl DirEntry {
         self.path.file_name().unwrap_or_else(|| self.path.as_os_str())
     }
 
+    /// Return the file name of this entry, borrowed from its path.
+    ///
+    /// This is the same as [`file_name`]. It's provided for code that wants
+    /// to make it explicit that no allocation or conversion happens: on every
+    /// platform, the returned name points into the path stored in this entry.
+    ///
+    /// If this entry has no file name (e.g., `/`), then the full path is
+    /// returned.
+    ///
+    /// [`file_name`]: #method.file_name
+    pub fn file_name_os(&self) -> &OsStr {
+        self.file_name()
+    }
+
+    /// Return the file name of this entry as a string, if it's valid
+    /// Unicode.
+    ///
+    /// This is the same as [`file_name`] followed by [`OsStr::to_str`].
+    ///
+    /// [`file_name`]: #method.file_name
+    /// [`OsStr::to_str`]: https://doc.rust-lang.org/stable/std/ffi/struct.OsStr.html#method.to_str
+    pub fn file_name_str(&self) -> Option<&str> {
+        self.file_name().to_str()
+    }
+
     /// Returns the depth at which this entry was created relative to the root.
     ///
     /// The smallest depth is `0` and always corresponds to the path given
@@ -176,11 +402,231 @@ impl DirEntry {
         self.depth
     }
 
+    /// Returns true if and only if this entry marks the end of a directory's
+    /// contents.
+    ///
+    /// Such entries are only yielded when [`emit_dir_close`] is enabled.
+    /// Apart from this, they are the same as the entry that was yielded for
+    /// the directory before its contents.
+    ///
+    /// [`emit_dir_close`]: struct.WalkDir.html#method.emit_dir_close
+    pub fn is_dir_close(&self) -> bool {
+        self.dir_close
+    }
+
+    /// Returns an iterator over the ancestors of this entry, starting with
+    /// the root and ending with this entry's parent directory.
+    ///
+    /// Exactly [`depth`] paths are yielded, each borrowed from this entry's
+    /// path. In particular, the root entry has no ancestors. Note that the
+    /// root is yielded as a prefix of this entry's path, which may differ
+    /// cosmetically from the path given to [`WalkDir::new`] (e.g., by not
+    /// having a trailing slash).
+    ///
+    /// This doesn't allocate.
+    ///
+    /// [`depth`]: struct.DirEntry.html#method.depth
+    /// [`WalkDir::new`]: struct.WalkDir.html#method.new
+    pub fn ancestors(&self) -> impl Iterator<Item = &Path> + '_ {
+        // Paths can only be shortened from the end, so each ancestor is found
+        // by going up from this entry. That's quadratic in the depth, but
+        // depths are small and nothing needs to be collected first.
+        (1..=self.depth).rev().map(move |up| {
+            self.path.ancestors().nth(up).expect("BUG: depth exceeds path")
+        })
+    }
+
+    /// Returns an iterator over the components of this entry's path below
+    /// the root, starting with the child of the root and ending with this
+    /// entry's file name.
+    ///
+    /// Exactly [`depth`] components are yielded. This is equivalent to
+    /// stripping the root from this entry's path and iterating over what
+    /// remains, and doesn't allocate.
+    ///
+    /// [`depth`]: struct.DirEntry.html#method.depth
+    pub fn components_from_root(&self) -> impl Iterator<Item = &OsStr> {
+        self.path_from_root().iter()
+    }
+
+    /// Returns the part of this entry's path below the root.
+    fn path_from_root(&self) -> &Path {
+        // The root is found by going up `depth` levels, so that it's the
+        // same prefix of this path regardless of how the root was spelled.
+        self.path
+            .ancestors()
+            .nth(self.depth)
+            .and_then(|root| self.path.strip_prefix(root).ok())
+            .expect("BUG: depth exceeds path")
+    }
+
+    /// Converts this entry into a value whose methods have the same
+    /// signatures as those of [`std::fs::DirEntry`].
+    ///
+    /// This is useful for porting code written against
+    /// [`std::fs::read_dir`]. See [`StdDirEntry`] for how its behavior
+    /// differs from [`std::fs::DirEntry`].
+    ///
+    /// [`std::fs::DirEntry`]: https://doc.rust-lang.org/stable/std/fs/struct.DirEntry.html
+    /// [`std::fs::read_dir`]: https://doc.rust-lang.org/stable/std/fs/fn.read_dir.html
+    /// [`StdDirEntry`]: struct.StdDirEntry.html
+    pub fn into_std_like(self) -> StdDirEntry {
+        StdDirEntry { dent: self }
+    }
+
+    /// Returns the first component of this entry's path below the root.
+    ///
+    /// This is the same as the first item yielded by
+    /// [`components_from_root`]. For the root entry, this returns `None`.
+    ///
+    /// [`components_from_root`]: struct.DirEntry.html#method.components_from_root
+    pub fn first_component_from_root(&self) -> Option<&OsStr> {
+        if self.depth == 0 {
+            return None;
+        }
+        self.path.iter().rev().nth(self.depth - 1)
+    }
+
+    /// Returns the device number of the file system containing this entry.
+    ///
+    /// On Unix, this is the `st_dev` field of the entry's metadata. On
+    /// Windows, this is the serial number of the volume containing the entry.
+    ///
+    /// This never makes any system calls. Instead, it reports a value that
+    /// the iterator already knew. That is, when [`same_file_system`] or
+    /// [`track_file_systems`] is enabled, the device of every directory is
+    /// looked up before descending into it, and the remaining entries
+    /// inherit the device of the directory they were found in. (Only
+    /// directories can be mount points.) The root entry reports the device
+    /// of the directory that is actually traversed, which is the target of
+    /// the root if it is a symbolic link.
+    ///
+    /// When the iterator had no reason to find out the device, such as when
+    /// both options are disabled or for a non-directory reached by
+    /// following a symbolic link, this returns `None`.
+    ///
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    /// [`track_file_systems`]: struct.WalkDir.html#method.track_file_systems
+    pub fn device(&self) -> Option<u64> {
+        self.device
+    }
+
+    /// Returns the path of the ancestor that this entry leads back to, if
+    /// it was found to cause a loop.
+    ///
+    /// This is only ever set when [`on_loop`] is [`LoopPolicy::Report`], in
+    /// which case a symbolic link to one of its ancestors (or, with
+    /// [`follow_mounts`] disabled, a directory that is the same as one of
+    /// its ancestors) is yielded with this set instead of an error, and
+    /// isn't descended into.
+    ///
+    /// [`on_loop`]: struct.WalkDir.html#method.on_loop
+    /// [`LoopPolicy::Report`]: enum.LoopPolicy.html#variant.Report
+    /// [`follow_mounts`]: struct.WalkDir.html#method.follow_mounts
+    pub fn loop_target(&self) -> Option<&Path> {
+        self.loop_target.as_deref()
+    }
+
+    /// Returns true if this entry and `other` are known to refer to the same
+    /// file, e.g., because they are hard links to it.
+    ///
+    /// This compares identities the iterator already knew, without making
+    /// any system calls. On Unix, the identity of an entry is its [`device`]
+    /// along with its [inode number]. Since the device is only known when
+    /// [`same_file_system`] is enabled, this always returns `false`
+    /// otherwise. It also returns `false` if the inode numbers of the two
+    /// entries came from [different sources], since those can't be compared
+    /// reliably.
+    ///
+    /// On other platforms, including Windows, the identity of an entry isn't
+    /// known ahead of time, so this always returns `false`. To compare files
+    /// in all cases, use the [`same-file`] crate.
+    ///
+    /// [`device`]: struct.DirEntry.html#method.device
+    /// [inode number]: trait.DirEntryExt.html#tymethod.ino
+    /// [different sources]: trait.DirEntryExt.html#method.ino_source
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    /// [`same-file`]: https://docs.rs/same-file
+    pub fn same_file(&self, other: &DirEntry) -> bool {
+        #[cfg(unix)]
+        {
+            match (self.device, other.device) {
+                (Some(a), Some(b)) => {
+                    a == b
+                        && self.ino == other.ino
+                        && self.ino_source == other.ino_source
+                }
+                _ => false,
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = other;
+            false
+        }
+    }
+
+    /// Returns a key identifying the file this entry corresponds to.
+    ///
+    /// The key is the device and inode number of the file on Unix, and the
+    /// volume serial number and file index of the file on Windows. Unlike
+    /// the path of an entry, it doesn't depend on how the file was reached.
+    /// So two entries have the same key if and only if they are the same
+    /// file, even when their paths differ only in case on a case-insensitive
+    /// file system, when they were reached through different symbolic links
+    /// or when they are hard links to the same file. This makes it suitable
+    /// for deduplicating entries, including across walks.
+    ///
+    /// This requires a `stat` call on Unix and opening the file on Windows.
+    /// On Unix, a symbolic link is only followed if this entry was created
+    /// by following it (see [`follow_links`]). On Windows, symbolic links
+    /// are always followed. On other platforms, this always returns an
+    /// error.
+    ///
+    /// Note that the walk itself detects loops by file identity too, so
+    /// [`follow_links`] can't be tricked into looping by paths that differ
+    /// only in case either.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    pub fn normalized_key(&self) -> Result<(u64, u64)> {
+        util::file_id(&self.path, self.follow_link)
+            .map_err(|err| Error::from_entry(self, err))
+    }
+
     /// Returns true if and only if this entry points to a directory.
     pub(crate) fn is_dir(&self) -> bool {
         self.ty.is_dir()
     }
 
+    /// Returns a copy of this entry as the root of a new walk.
+    ///
+    /// Anything that only describes the entry's place in the walk it came
+    /// from is reset.
+    pub(crate) fn to_root(&self) -> DirEntry {
+        let mut dent = self.clone();
+        dent.depth = 0;
+        dent.dir_close = false;
+        dent.loop_target = None;
+        dent
+    }
+
+    /// Returns a copy of this entry that marks the end of its contents.
+    pub(crate) fn to_dir_close(&self) -> DirEntry {
+        let mut dent = self.clone();
+        dent.dir_close = true;
+        dent
+    }
+
+    /// Records the ancestor that this entry leads back to.
+    pub(crate) fn set_loop_target(&mut self, ancestor: PathBuf) {
+        self.loop_target = Some(ancestor);
+    }
+
+    /// Records the device of this entry.
+    pub(crate) fn set_device(&mut self, device: Option<u64>) {
+        self.device = device;
+    }
+
     #[cfg(windows)]
     pub(crate) fn from_entry(
         depth: usize,
@@ -193,7 +639,17 @@ impl DirEntry {
         let md = ent
             .metadata()
             .map_err(|err| Error::from_path(depth, path.clone(), err))?;
-        Ok(DirEntry { path, ty, follow_link: false, depth, metadata: md })
+        Ok(DirEntry {
+            path,
+            ty,
+            follow_link: false,
+            depth,
+            dir_close: false,
+            device: None,
+            loop_target: None,
+            nlink: AtomicUsize::new(0),
+            metadata: md,
+        })
     }
 
     #[cfg(unix)]
@@ -211,7 +667,12 @@ impl DirEntry {
             ty,
             follow_link: false,
             depth,
+            dir_close: false,
+            device: None,
+            loop_target: None,
+            nlink: AtomicUsize::new(0),
             ino: ent.ino(),
+            ino_source: InoSource::Dirent,
         })
     }
 
@@ -223,7 +684,16 @@ impl DirEntry {
         let ty = ent
             .file_type()
             .map_err(|err| Error::from_path(depth, ent.path(), err))?;
-        Ok(DirEntry { path: ent.path(), ty, follow_link: false, depth })
+        Ok(DirEntry {
+            path: ent.path(),
+            ty,
+            follow_link: false,
+            depth,
+            dir_close: false,
+            device: None,
+            loop_target: None,
+            nlink: AtomicUsize::new(0),
+        })
     }
 
     #[cfg(windows)]
@@ -244,6 +714,10 @@ impl DirEntry {
             ty: md.file_type(),
             follow_link: follow,
             depth,
+            dir_close: false,
+            device: None,
+            loop_target: None,
+            nlink: AtomicUsize::new(0),
             metadata: md,
         })
     }
@@ -268,7 +742,12 @@ impl DirEntry {
             ty: md.file_type(),
             follow_link: follow,
             depth,
+            dir_close: false,
+            device: None,
+            loop_target: None,
+            nlink: AtomicUsize::new(0),
             ino: md.ino(),
+            ino_source: InoSource::Stat,
         })
     }
 
@@ -290,6 +769,10 @@ impl DirEntry {
             ty: md.file_type(),
             follow_link: follow,
             depth,
+            dir_close: false,
+            device: None,
+            loop_target: None,
+            nlink: AtomicUsize::new(0),
         })
     }
 }
@@ -302,6 +785,10 @@ impl Clone for DirEntry {
             ty: self.ty,
             follow_link: self.follow_link,
             depth: self.depth,
+            dir_close: self.dir_close,
+            device: self.device,
+            loop_target: self.loop_target.clone(),
+            nlink: AtomicUsize::new(self.nlink.load(Ordering::Relaxed)),
             metadata: self.metadata.clone(),
         }
     }
@@ -313,7 +800,12 @@ impl Clone for DirEntry {
             ty: self.ty,
             follow_link: self.follow_link,
             depth: self.depth,
+            dir_close: self.dir_close,
+            device: self.device,
+            loop_target: self.loop_target.clone(),
+            nlink: AtomicUsize::new(self.nlink.load(Ordering::Relaxed)),
             ino: self.ino,
+            ino_source: self.ino_source,
         }
     }
 
@@ -324,13 +816,45 @@ impl Clone for DirEntry {
             ty: self.ty,
             follow_link: self.follow_link,
             depth: self.depth,
+            dir_close: self.dir_close,
+            device: self.device,
+            loop_target: self.loop_target.clone(),
+            nlink: AtomicUsize::new(self.nlink.load(Ordering::Relaxed)),
         }
     }
 }
 
 impl fmt::Debug for DirEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "DirEntry({:?})", self.path)
+        let kind = if self.ty.is_dir() {
+            "dir"
+        } else if self.ty.is_file() {
+            "file"
+        } else if self.ty.is_symlink() {
+            "symlink"
+        } else {
+            "other"
+        };
+        let mut d = f.debug_struct("DirEntry");
+        d.field("path", &self.path)
+            .field("depth", &self.depth)
+            .field("file_type", &format_args!("{}", kind));
+        if self.dir_close {
+            d.field("dir_close", &true);
+        }
+        if let Some(ref target) = self.loop_target {
+            d.field("loop_target", target);
+        }
+        d.finish()
+    }
+}
+
+/// Writes this entry's path, as with [`Path::display`].
+///
+/// [`Path::display`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html#method.display
+impl fmt::Display for DirEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.path.display().fmt(f)
     }
 }
 
@@ -339,7 +863,50 @@ impl fmt::Debug for DirEntry {
 pub trait DirEntryExt {
     /// Returns the underlying `d_ino` field in the contained `dirent`
     /// structure.
+    ///
+    /// This never makes any system calls. For entries that weren't read from
+    /// a directory (the root, and symbolic links that were followed), this
+    /// is the `st_ino` field of the `stat` call that created the entry
+    /// instead. Use [`ino_source`] to tell these apart.
+    ///
+    /// [`ino_source`]: trait.DirEntryExt.html#method.ino_source
     fn ino(&self) -> u64;
+
+    /// Returns where the value returned by [`ino`] came from.
+    ///
+    /// Some file systems report different inode numbers in directory entries
+    /// than in the results of `stat`. Notably, on overlayfs, `d_ino` may be
+    /// the inode number in the underlying layer while `st_ino` is the inode
+    /// number in the overlay. Tools that compare inode numbers across
+    /// entries (or with `stat` results) should only compare numbers from the
+    /// same source.
+    ///
+    /// The default implementation returns [`InoSource::Dirent`].
+    ///
+    /// [`ino`]: trait.DirEntryExt.html#tymethod.ino
+    /// [`InoSource::Dirent`]: enum.InoSource.html#variant.Dirent
+    fn ino_source(&self) -> InoSource {
+        InoSource::Dirent
+    }
+
+    /// Returns true if any of the owner, group or other execute bits are set
+    /// in the permissions of this entry.
+    ///
+    /// This looks up the entry's [`metadata`], so it follows symbolic links
+    /// in the same cases. Only the mode bits are consulted, so this doesn't
+    /// say whether the current process may actually execute the file: ACLs,
+    /// the owner and group of the file and `noexec` mounts are all ignored.
+    /// Note that directories usually have their execute bits set too.
+    ///
+    /// The default implementation returns an error of kind `Unsupported`.
+    ///
+    /// [`metadata`]: struct.DirEntry.html#method.metadata
+    fn is_executable(&self) -> io::Result<bool> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "is_executable isn't implemented for this type",
+        ))
+    }
 }
 
 #[cfg(unix)]
@@ -349,4 +916,86 @@ impl DirEntryExt for DirEntry {
     fn ino(&self) -> u64 {
         self.ino
     }
+
+    fn ino_source(&self) -> InoSource {
+        self.ino_source
+    }
+
+    fn is_executable(&self) -> io::Result<bool> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = self.metadata()?.permissions().mode();
+        Ok(mode & 0o111 != 0)
+    }
+}
+
+/// The source of the inode number of a directory entry.
+///
+/// See [`DirEntryExt::ino_source`].
+///
+/// [`DirEntryExt::ino_source`]: trait.DirEntryExt.html#method.ino_source
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InoSource {
+    /// The inode number is the `d_ino` field of the directory entry read
+    /// from its parent directory.
+    Dirent,
+    /// The inode number is the `st_ino` field of a `stat` call.
+    Stat,
+}
+
+/// A directory entry with the same method signatures as
+/// [`std::fs::DirEntry`].
+///
+/// Values of this type are created with [`DirEntry::into_std_like`]. They
+/// are meant to ease porting code written against [`std::fs::read_dir`],
+/// but there are some differences in behavior:
+///
+/// * [`file_type`] never fails and never makes a system call, since the
+///   file type was already needed to walk the directory tree.
+/// * If [`follow_links`] was enabled on the originating iterator, then
+///   [`file_type`] and [`metadata`] describe the target of a symbolic link.
+///   [`std::fs::DirEntry`] never follows symbolic links.
+/// * Errors returned by [`metadata`] are converted from [`walkdir::Error`],
+///   and so include the entry's path in their message.
+///
+/// [`std::fs::DirEntry`]: https://doc.rust-lang.org/stable/std/fs/struct.DirEntry.html
+/// [`std::fs::read_dir`]: https://doc.rust-lang.org/stable/std/fs/fn.read_dir.html
+/// [`DirEntry::into_std_like`]: struct.DirEntry.html#method.into_std_like
+/// [`file_type`]: #method.file_type
+/// [`metadata`]: #method.metadata
+/// [`follow_links`]: struct.WalkDir.html#method.follow_links
+/// [`walkdir::Error`]: struct.Error.html
+#[derive(Clone, Debug)]
+pub struct StdDirEntry {
+    dent: DirEntry,
+}
+
+impl StdDirEntry {
+    /// Returns the full path to the file that this entry represents.
+    pub fn path(&self) -> PathBuf {
+        self.dent.path().to_path_buf()
+    }
+
+    /// Returns the file name of this entry.
+    pub fn file_name(&self) -> OsString {
+        self.dent.file_name().to_os_string()
+    }
+
+    /// Returns the file type for the file that this entry points to.
+    ///
+    /// This never fails.
+    pub fn file_type(&self) -> io::Result<FileType> {
+        Ok(self.dent.file_type())
+    }
+
+    /// Returns the metadata for the file that this entry points to.
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        self.dent.metadata().map_err(io::Error::from)
+    }
+
+    /// Converts this value back into the walkdir entry it was created from.
+    pub fn into_inner(self) -> DirEntry {
+        self.dent
+    }
 }
@@ -1,4 +1,5 @@
 use std::error;
+use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -11,10 +12,10 @@ use crate::DirEntry;
 /// particular, it adds the following information:
 ///
 /// * The depth at which the error occurred in the file tree, relative to the
-/// root.
+///   root.
 /// * The path, if any, associated with the IO error.
-/// * An indication that a loop occurred when following symbolic links. In this
-/// case, there is no underlying IO error.
+/// * An indication that a loop occurred when following symbolic links. In
+///   this case, there is no underlying IO error.
 ///
 /// To maintain good ergonomics, this type has a
 /// [`impl From<Error> for std::io::Error`][impl] defined which preserves the original context.
@@ -32,8 +33,31 @@ pub struct Error {
 
 #[derive(Debug)]
 enum ErrorInner {
-    Io { path: Option<PathBuf>, err: io::Error },
-    Loop { ancestor: PathBuf, child: PathBuf },
+    Io {
+        path: Option<PathBuf>,
+        err: io::Error,
+        /// Whether this error occurred while listing a directory's contents.
+        enumeration: bool,
+    },
+    Loop {
+        ancestor: PathBuf,
+        child: PathBuf,
+    },
+    Timeout,
+    DepthLimit {
+        path: PathBuf,
+    },
+    UnsupportedOption {
+        option: &'static str,
+    },
+    ConflictingOptions {
+        option: &'static str,
+        other: &'static str,
+    },
+    NonUnicodeName {
+        path: PathBuf,
+        name: OsString,
+    },
 }
 
 impl Error {
@@ -48,6 +72,11 @@ impl Error {
             ErrorInner::Io { path: None, .. } => None,
             ErrorInner::Io { path: Some(ref path), .. } => Some(path),
             ErrorInner::Loop { ref child, .. } => Some(child),
+            ErrorInner::DepthLimit { ref path }
+            | ErrorInner::NonUnicodeName { ref path, .. } => Some(path),
+            ErrorInner::Timeout
+            | ErrorInner::UnsupportedOption { .. }
+            | ErrorInner::ConflictingOptions { .. } => None,
         }
     }
 
@@ -70,6 +99,99 @@ impl Error {
         }
     }
 
+    /// Returns true if and only if this error occurred while listing the
+    /// contents of a directory.
+    ///
+    /// This distinguishes errors from opening or reading a directory (e.g.,
+    /// a directory with search but without read permission) from errors for
+    /// looking up a single entry (e.g., a `stat` call that failed). In the
+    /// former case, the directory's own entry has still been yielded (before
+    /// this error, or after it when [`contents_first`] is enabled) and the
+    /// error's [`path`] is the directory, if known. Only its contents are
+    /// missing.
+    ///
+    /// [`contents_first`]: struct.WalkDir.html#method.contents_first
+    /// [`path`]: struct.Error.html#method.path
+    pub fn is_enumeration_error(&self) -> bool {
+        matches!(self.inner, ErrorInner::Io { enumeration: true, .. })
+    }
+
+    /// Returns true if and only if this error was produced because a deadline
+    /// passed.
+    ///
+    /// Errors of this kind are only produced by [`IntoIter::next_deadline`].
+    /// They do not indicate a problem with the directory tree, and iteration
+    /// may be resumed after receiving one.
+    ///
+    /// [`IntoIter::next_deadline`]: struct.IntoIter.html#method.next_deadline
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.inner, ErrorInner::Timeout)
+    }
+
+    /// Returns true if and only if this error was produced because a
+    /// directory was at the depth set by [`WalkDir::absolute_max_depth`].
+    ///
+    /// The [`path`] of such an error is the directory that wasn't descended
+    /// into.
+    ///
+    /// [`WalkDir::absolute_max_depth`]: struct.WalkDir.html#method.absolute_max_depth
+    /// [`path`]: struct.Error.html#method.path
+    pub fn is_depth_limit(&self) -> bool {
+        matches!(self.inner, ErrorInner::DepthLimit { .. })
+    }
+
+    /// Returns the name of the option that isn't supported on this platform,
+    /// if that's what caused this error.
+    ///
+    /// Such an error is yielded before anything else, and before the file
+    /// system is accessed at all, so a traversal can be retried without the
+    /// option. For example, the name of [`same_file_system`] is
+    /// `"same_file_system"`.
+    ///
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    pub fn unsupported_option(&self) -> Option<&'static str> {
+        match self.inner {
+            ErrorInner::UnsupportedOption { option } => Some(option),
+            _ => None,
+        }
+    }
+
+    /// Returns the names of two options that can't be enabled together, if
+    /// that's what caused this error.
+    ///
+    /// Like an [unsupported option], such an error is yielded before
+    /// anything else. For example, enabling [`low_memory`] and setting a
+    /// sorter gives `("low_memory", "sort_by")`.
+    ///
+    /// [unsupported option]: struct.Error.html#method.unsupported_option
+    /// [`low_memory`]: struct.WalkDir.html#method.low_memory
+    pub fn conflicting_options(&self) -> Option<(&'static str, &'static str)> {
+        match self.inner {
+            ErrorInner::ConflictingOptions { option, other } => {
+                Some((option, other))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the file name that isn't valid Unicode, if that's what caused
+    /// this error.
+    ///
+    /// Such errors are only yielded when [`require_unicode`] is enabled, in
+    /// place of the entry with that name. The [`path`] of the error is the
+    /// entry's path. Use [`OsStr::to_string_lossy`] for a printable version
+    /// of the name.
+    ///
+    /// [`require_unicode`]: struct.WalkDir.html#method.require_unicode
+    /// [`path`]: struct.Error.html#method.path
+    /// [`OsStr::to_string_lossy`]: https://doc.rust-lang.org/stable/std/ffi/struct.OsStr.html#method.to_string_lossy
+    pub fn non_unicode_name(&self) -> Option<&OsStr> {
+        match self.inner {
+            ErrorInner::NonUnicodeName { ref name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
     /// Returns the depth at which this error occurred relative to the root.
     ///
     /// The smallest depth is `0` and always corresponds to the path given to
@@ -94,7 +216,7 @@ impl Error {
     ///
     /// > This is the original [`io::Error`] and is _not_ the same as
     /// > [`impl From<Error> for std::io::Error`][impl] which contains additional context about the
-    /// error.
+    /// > error.
     ///
     /// # Example
     ///
@@ -143,7 +265,12 @@ impl Error {
     pub fn io_error(&self) -> Option<&io::Error> {
         match self.inner {
             ErrorInner::Io { ref err, .. } => Some(err),
-            ErrorInner::Loop { .. } => None,
+            ErrorInner::Loop { .. }
+            | ErrorInner::Timeout
+            | ErrorInner::DepthLimit { .. }
+            | ErrorInner::UnsupportedOption { .. }
+            | ErrorInner::ConflictingOptions { .. }
+            | ErrorInner::NonUnicodeName { .. } => None,
         }
     }
 
@@ -155,7 +282,24 @@ impl Error {
     pub fn into_io_error(self) -> Option<io::Error> {
         match self.inner {
             ErrorInner::Io { err, .. } => Some(err),
-            ErrorInner::Loop { .. } => None,
+            ErrorInner::Loop { .. }
+            | ErrorInner::Timeout
+            | ErrorInner::DepthLimit { .. }
+            | ErrorInner::UnsupportedOption { .. }
+            | ErrorInner::ConflictingOptions { .. }
+            | ErrorInner::NonUnicodeName { .. } => None,
+        }
+    }
+
+    /// Returns true if this error was caused by an entry that doesn't exist
+    /// or that can't be accessed.
+    pub(crate) fn is_unreadable(&self) -> bool {
+        match self.inner {
+            ErrorInner::Io { ref err, .. } => matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+            ),
+            _ => false,
         }
     }
 
@@ -164,7 +308,10 @@ impl Error {
         pb: PathBuf,
         err: io::Error,
     ) -> Self {
-        Error { depth, inner: ErrorInner::Io { path: Some(pb), err } }
+        Error {
+            depth,
+            inner: ErrorInner::Io { path: Some(pb), err, enumeration: false },
+        }
     }
 
     pub(crate) fn from_entry(dent: &DirEntry, err: io::Error) -> Self {
@@ -173,12 +320,26 @@ impl Error {
             inner: ErrorInner::Io {
                 path: Some(dent.path().to_path_buf()),
                 err,
+                enumeration: false,
             },
         }
     }
 
     pub(crate) fn from_io(depth: usize, err: io::Error) -> Self {
-        Error { depth, inner: ErrorInner::Io { path: None, err } }
+        Error {
+            depth,
+            inner: ErrorInner::Io { path: None, err, enumeration: false },
+        }
+    }
+
+    /// Creates an error that occurred while opening or reading a directory
+    /// to list its contents.
+    pub(crate) fn from_enumeration(
+        depth: usize,
+        path: Option<PathBuf>,
+        err: io::Error,
+    ) -> Self {
+        Error { depth, inner: ErrorInner::Io { path, err, enumeration: true } }
     }
 
     pub(crate) fn from_loop(
@@ -194,6 +355,48 @@ impl Error {
             },
         }
     }
+
+    pub(crate) fn from_timeout(depth: usize) -> Self {
+        Error { depth, inner: ErrorInner::Timeout }
+    }
+
+    pub(crate) fn from_depth_limit(dent: &DirEntry) -> Self {
+        Error {
+            depth: dent.depth(),
+            inner: ErrorInner::DepthLimit { path: dent.path().to_path_buf() },
+        }
+    }
+
+    /// Creates the error for a walk whose root is the empty path.
+    pub(crate) fn from_empty_root() -> Self {
+        let err =
+            io::Error::new(io::ErrorKind::InvalidInput, "empty root path");
+        Error::from_path(0, PathBuf::new(), err)
+    }
+
+    pub(crate) fn from_unsupported_option(option: &'static str) -> Self {
+        Error { depth: 0, inner: ErrorInner::UnsupportedOption { option } }
+    }
+
+    pub(crate) fn from_conflicting_options(
+        option: &'static str,
+        other: &'static str,
+    ) -> Self {
+        Error {
+            depth: 0,
+            inner: ErrorInner::ConflictingOptions { option, other },
+        }
+    }
+
+    pub(crate) fn from_non_unicode_name(dent: &DirEntry) -> Self {
+        Error {
+            depth: dent.depth(),
+            inner: ErrorInner::NonUnicodeName {
+                path: dent.path().to_path_buf(),
+                name: dent.file_name().to_os_string(),
+            },
+        }
+    }
 }
 
 impl error::Error for Error {
@@ -202,6 +405,13 @@ impl error::Error for Error {
         match self.inner {
             ErrorInner::Io { ref err, .. } => err.description(),
             ErrorInner::Loop { .. } => "file system loop found",
+            ErrorInner::Timeout => "deadline passed",
+            ErrorInner::DepthLimit { .. } => "depth limit reached",
+            ErrorInner::UnsupportedOption { .. } => "unsupported option",
+            ErrorInner::ConflictingOptions { .. } => "conflicting options",
+            ErrorInner::NonUnicodeName { .. } => {
+                "file name isn't valid Unicode"
+            }
         }
     }
 
@@ -212,7 +422,12 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self.inner {
             ErrorInner::Io { ref err, .. } => Some(err),
-            ErrorInner::Loop { .. } => None,
+            ErrorInner::Loop { .. }
+            | ErrorInner::Timeout
+            | ErrorInner::DepthLimit { .. }
+            | ErrorInner::UnsupportedOption { .. }
+            | ErrorInner::ConflictingOptions { .. }
+            | ErrorInner::NonUnicodeName { .. } => None,
         }
     }
 }
@@ -220,8 +435,8 @@ impl error::Error for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.inner {
-            ErrorInner::Io { path: None, ref err } => err.fmt(f),
-            ErrorInner::Io { path: Some(ref path), ref err } => write!(
+            ErrorInner::Io { path: None, ref err, .. } => err.fmt(f),
+            ErrorInner::Io { path: Some(ref path), ref err, .. } => write!(
                 f,
                 "IO error for operation on {}: {}",
                 path.display(),
@@ -234,6 +449,32 @@ impl fmt::Display for Error {
                 child.display(),
                 ancestor.display()
             ),
+            ErrorInner::Timeout => {
+                write!(f, "deadline passed before the next entry was read")
+            }
+            ErrorInner::DepthLimit { ref path } => write!(
+                f,
+                "depth limit reached, not descending into {}",
+                path.display()
+            ),
+            ErrorInner::UnsupportedOption { option } => {
+                write!(
+                    f,
+                    "the {} option is not supported on this platform",
+                    option
+                )
+            }
+            ErrorInner::ConflictingOptions { option, other } => write!(
+                f,
+                "the {} option can't be used together with {}",
+                option, other
+            ),
+            ErrorInner::NonUnicodeName { ref path, ref name } => write!(
+                f,
+                "file name {} isn't valid Unicode: {}",
+                name.to_string_lossy(),
+                path.display()
+            ),
         }
     }
 }
@@ -256,7 +497,64 @@ impl From<Error> for io::Error {
             Error { inner: ErrorInner::Loop { .. }, .. } => {
                 io::ErrorKind::Other
             }
+            Error { inner: ErrorInner::Timeout, .. } => {
+                io::ErrorKind::TimedOut
+            }
+            Error { inner: ErrorInner::DepthLimit { .. }, .. } => {
+                io::ErrorKind::Other
+            }
+            Error { inner: ErrorInner::UnsupportedOption { .. }, .. } => {
+                io::ErrorKind::Unsupported
+            }
+            Error { inner: ErrorInner::ConflictingOptions { .. }, .. } => {
+                io::ErrorKind::InvalidInput
+            }
+            Error { inner: ErrorInner::NonUnicodeName { .. }, .. } => {
+                io::ErrorKind::InvalidData
+            }
         };
         io::Error::new(kind, walk_err)
     }
 }
+
+/// An error produced when an invalid range of depths is given to
+/// [`WalkDir::try_depths`].
+///
+/// The range is invalid when the minimum depth is greater than the maximum
+/// depth.
+///
+/// [`WalkDir::try_depths`]: struct.WalkDir.html#method.try_depths
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DepthError {
+    min: usize,
+    max: usize,
+}
+
+impl DepthError {
+    /// Returns the minimum depth that was requested.
+    pub fn min_depth(&self) -> usize {
+        self.min
+    }
+
+    /// Returns the maximum depth that was requested.
+    pub fn max_depth(&self) -> usize {
+        self.max
+    }
+
+    pub(crate) fn new(min: usize, max: usize) -> Self {
+        DepthError { min, max }
+    }
+}
+
+impl error::Error for DepthError {}
+
+impl fmt::Display for DepthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid depth range: minimum depth {} is greater than \
+             maximum depth {}",
+            self.min, self.max
+        )
+    }
+}
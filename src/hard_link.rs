@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::{util, DirEntry, Result};
+
+/// A map for finding entries that are hard links to the same file.
+///
+/// Entries are fed to [`insert`] as they are yielded by a walk. When an
+/// entry is a hard link to a file that was already inserted under another
+/// name, the path of that earlier entry is returned. This makes it possible
+/// to group hard links without maintaining a platform specific map by hand.
+///
+/// Files are identified by their device and inode number on Unix, and by
+/// their volume serial number and file index on Windows. Only regular files
+/// with more than one link are remembered, so the map stays small for
+/// typical directory trees.
+///
+/// Currently, this is only supported on Unix and Windows. On other
+/// platforms, [`insert`] returns an error for every regular file.
+///
+/// # Example
+///
+/// ```no_run
+/// use walkdir::{HardLinkMap, WalkDir};
+///
+/// # fn try_main() -> Result<(), walkdir::Error> {
+/// let mut links = HardLinkMap::new();
+/// for entry in WalkDir::new("foo") {
+///     let entry = entry?;
+///     if let Some(first) = links.insert(&entry)? {
+///         println!(
+///             "{} is the same file as {}",
+///             entry.path().display(),
+///             first.display(),
+///         );
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`insert`]: struct.HardLinkMap.html#method.insert
+#[derive(Clone, Debug, Default)]
+pub struct HardLinkMap {
+    seen: HashMap<(u64, u64), PathBuf>,
+}
+
+impl HardLinkMap {
+    /// Create a new empty map.
+    pub fn new() -> HardLinkMap {
+        HardLinkMap::default()
+    }
+
+    /// Add an entry to this map.
+    ///
+    /// If the entry is a hard link to a file that was inserted before, then
+    /// the path of the first entry inserted for that file is returned.
+    /// Otherwise, `None` is returned.
+    ///
+    /// Entries that aren't regular files (after following symbolic links,
+    /// if [`follow_links`] is enabled) are never remembered, and this
+    /// returns `None` for them without making any system calls. For regular
+    /// files, this makes one system call (a `stat` on Unix, or opening a
+    /// handle on Windows).
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    pub fn insert(&mut self, dent: &DirEntry) -> Result<Option<&Path>> {
+        if !dent.file_type().is_file() {
+            return Ok(None);
+        }
+        let id = match util::hard_link_id(dent.path())
+            .map_err(|err| Error::from_entry(dent, err))?
+        {
+            None => return Ok(None),
+            Some(id) => id,
+        };
+        // We can't return a borrow from `entry` in the first case while
+        // also inserting in the second, so check first.
+        if self.seen.contains_key(&id) {
+            return Ok(self.seen.get(&id).map(|p| p.as_path()));
+        }
+        self.seen.insert(id, dent.path().to_path_buf());
+        Ok(None)
+    }
+
+    /// Returns the number of distinct files with more than one link that
+    /// have been inserted.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Returns true if no files with more than one link have been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+}
@@ -0,0 +1,157 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The signature that a `CACHEDIR.TAG` file must begin with.
+///
+/// See: https://bford.info/cachedir/
+const CACHEDIR_TAG_NAME: &str = "CACHEDIR.TAG";
+const CACHEDIR_TAG_SIGNATURE: &[u8] =
+    b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Returns true if the given directory contains a marker file with the given
+/// name.
+///
+/// If the name is `CACHEDIR.TAG`, then the file must also begin with the
+/// signature required by the Cache Directory Tagging Specification.
+pub fn has_marker(dir: &Path, name: &OsStr) -> bool {
+    let path = dir.join(name);
+    if name != CACHEDIR_TAG_NAME {
+        return path.is_file();
+    }
+    let mut buf = [0; 43];
+    match File::open(&path).and_then(|mut f| f.read_exact(&mut buf)) {
+        Ok(()) => buf[..] == *CACHEDIR_TAG_SIGNATURE,
+        Err(_) => false,
+    }
+}
+
+/// The rules read from a single ignore file.
+#[derive(Clone, Debug)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    glob: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRules {
+    /// Reads the ignore file with the given name in the given directory.
+    ///
+    /// If the file doesn't exist, then `None` is returned.
+    pub fn read(dir: &Path, name: &OsStr) -> io::Result<Option<IgnoreRules>> {
+        let mut contents = String::new();
+        match File::open(dir.join(name)) {
+            Ok(mut f) => f.read_to_string(&mut contents)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err),
+        };
+        Ok(Some(IgnoreRules::parse(&contents)))
+    }
+
+    fn parse(contents: &str) -> IgnoreRules {
+        let mut rules = vec![];
+        for line in contents.lines() {
+            let mut line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let negated = line.starts_with('!');
+            if negated {
+                line = &line[1..];
+            }
+            let dir_only = line.ends_with('/');
+            if dir_only {
+                line = &line[..line.len() - 1];
+            }
+            if line.is_empty() {
+                continue;
+            }
+            rules.push(Rule {
+                glob: line.chars().collect(),
+                negated,
+                dir_only,
+            });
+        }
+        IgnoreRules { rules }
+    }
+
+    /// Returns `Some(true)` if the last rule matching the given file name
+    /// ignores it, `Some(false)` if it re-includes it and `None` if no rule
+    /// matches.
+    pub fn matched(&self, name: &OsStr, is_dir: bool) -> Option<bool> {
+        let name: Vec<char> = name.to_string_lossy().chars().collect();
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| (is_dir || !r.dir_only) && glob_match(&r.glob, &name))
+            .map(|r| !r.negated)
+    }
+}
+
+/// Matches a file name against a glob supporting `*`, `?`, `[...]` classes
+/// (with `!` for negation and `a-z` ranges) and `\` escapes.
+fn glob_match(glob: &[char], name: &[char]) -> bool {
+    match glob.first() {
+        None => name.is_empty(),
+        Some('*') => {
+            (0..=name.len()).any(|i| glob_match(&glob[1..], &name[i..]))
+        }
+        Some('?') => !name.is_empty() && glob_match(&glob[1..], &name[1..]),
+        Some('[') => match (class_match(&glob[1..], name.first()), name) {
+            (Some((true, rest)), [_, name @ ..]) => glob_match(rest, name),
+            (Some(_), _) => false,
+            // An unclosed class matches a literal `[`.
+            (None, [c, name @ ..]) => {
+                *c == '[' && glob_match(&glob[1..], name)
+            }
+            (None, []) => false,
+        },
+        Some('\\') if glob.len() > 1 => {
+            name.first() == Some(&glob[1])
+                && glob_match(&glob[2..], &name[1..])
+        }
+        Some(c) => {
+            name.first() == Some(c) && glob_match(&glob[1..], &name[1..])
+        }
+    }
+}
+
+/// Matches a character against the class starting right after its `[`.
+///
+/// Returns whether it matched along with the rest of the glob after the
+/// closing `]`, or `None` if the class is never closed.
+fn class_match<'g>(
+    glob: &'g [char],
+    c: Option<&char>,
+) -> Option<(bool, &'g [char])> {
+    let (negated, mut i) = match glob.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut matched = false;
+    let mut first = true;
+    while i < glob.len() {
+        if glob[i] == ']' && !first {
+            return Some((c.is_some() && matched != negated, &glob[i + 1..]));
+        }
+        first = false;
+        let lo = glob[i];
+        if i + 2 < glob.len() && glob[i + 1] == '-' && glob[i + 2] != ']' {
+            let hi = glob[i + 2];
+            matched |= matches!(c, Some(&c) if lo <= c && c <= hi);
+            i += 3;
+        } else {
+            matched |= c == Some(&lo);
+            i += 1;
+        }
+    }
+    None
+}
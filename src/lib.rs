@@ -109,26 +109,49 @@ for entry in walker.filter_entry(|e| !is_hidden(e)) {
 #[cfg(doctest)]
 doc_comment::doctest!("../README.md");
 
-use std::cmp::{min, Ordering};
+use std::cmp::{max, min, Ordering};
+use std::collections::{HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::fs::{self, ReadDir};
 use std::io;
 use std::iter;
 use std::path::{Path, PathBuf};
 use std::result;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::vec;
 
 use same_file::Handle;
 
-pub use crate::dent::DirEntry;
-#[cfg(unix)]
-pub use crate::dent::DirEntryExt;
-pub use crate::error::Error;
+use crate::capabilities::Platform;
 
+pub use crate::capabilities::{
+    fs_capabilities, Capabilities, Support, WalkCapabilities,
+};
+pub use crate::channel::{Progress, WalkHandle, WalkStats};
+pub use crate::checkpoint::Checkpoint;
+pub use crate::dent::{DirEntry, StdDirEntry};
+#[cfg(unix)]
+pub use crate::dent::{DirEntryExt, InoSource};
+pub use crate::error::{DepthError, Error};
+pub use crate::hard_link::HardLinkMap;
+#[cfg(feature = "tree")]
+pub use crate::tree::{Tree, TreeDiff};
+
+mod capabilities;
+mod channel;
+mod checkpoint;
 mod dent;
 mod error;
+mod hard_link;
+#[cfg(feature = "ignore-files")]
+mod ignore;
 #[cfg(test)]
 mod tests;
+#[cfg(feature = "tree")]
+mod tree;
 mod util;
 
 /// Like try, but for iterators that return [`Option<Result<_, _>>`].
@@ -233,27 +256,144 @@ pub type Result<T> = ::std::result::Result<T, Error>;
 #[derive(Debug)]
 pub struct WalkDir {
     opts: WalkDirOptions,
-    root: PathBuf,
+    /// The paths to walk, one after the other. There's only more than one
+    /// when created by `new_glob`.
+    roots: Vec<PathBuf>,
+    /// The entry for the root, if it is already known. See `from_entry`.
+    root_dent: Option<DirEntry>,
 }
 
+#[derive(Clone)]
 struct WalkDirOptions {
     follow_links: bool,
     follow_root_links: bool,
+    /// The reparse tags of the links that `follow_links` follows.
+    #[cfg(windows)]
+    follow_reparse_tags: Vec<u32>,
     max_open: usize,
     min_depth: usize,
     max_depth: usize,
-    sorter: Option<
-        Box<
-            dyn FnMut(&DirEntry, &DirEntry) -> Ordering
-                + Send
-                + Sync
-                + 'static,
-        >,
-    >,
+    absolute_max_depth: Option<usize>,
+    /// The sorter is shared by every iterator created from a borrowed
+    /// `WalkDir`, hence the `Arc<Mutex<...>>`.
+    sorter: Option<Arc<Mutex<Sorter>>>,
+    group_symlinks: Option<GroupOrder>,
+    errors_last: bool,
     contents_first: bool,
+    emit_dir_close: bool,
     same_file_system: bool,
+    track_file_systems: bool,
+    dedup_hard_links: bool,
+    skip_unreadable: bool,
+    stop_dir_on_error: bool,
+    retry_transient: usize,
+    require_unicode: bool,
+    prune_non_unicode: bool,
+    follow_mounts: bool,
+    on_loop: LoopPolicy,
+    cross_fs_via_symlink: bool,
+    normalize_paths: bool,
+    max_entries_per_dir: Option<usize>,
+    eviction_policy: EvictionPolicy,
+    low_memory: bool,
+    /// How many entries to yield between calls to the progress callback,
+    /// and the callback itself.
+    progress: Option<(usize, Arc<Mutex<ProgressFn>>)>,
+    skip_dirs_ci: Vec<OsString>,
+    #[cfg(feature = "ignore-files")]
+    marker_file: Option<OsString>,
+    #[cfg(feature = "ignore-files")]
+    ignore_file: Option<OsString>,
+}
+
+/// Which directory handle to close when [`max_open`] is reached.
+///
+/// See [`WalkDir::eviction_policy`].
+///
+/// [`max_open`]: struct.WalkDir.html#method.max_open
+/// [`WalkDir::eviction_policy`]: struct.WalkDir.html#method.eviction_policy
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EvictionPolicy {
+    /// Close the open directory closest to the root.
+    ///
+    /// This is the default.
+    Shallowest,
+    /// Close the open directory furthest from the root.
+    Deepest,
+}
+
+/// What to do when a directory loop is found.
+///
+/// See [`WalkDir::on_loop`].
+///
+/// [`WalkDir::on_loop`]: struct.WalkDir.html#method.on_loop
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LoopPolicy {
+    /// Yield an error in place of the entry that leads back to an ancestor.
+    ///
+    /// This is the default.
+    Error,
+    /// Skip the entry that leads back to an ancestor, without yielding
+    /// anything for it.
+    SkipSilently,
+    /// Yield the entry that leads back to an ancestor, with its
+    /// [`DirEntry::loop_target`] set to that ancestor, but don't descend
+    /// into it.
+    ///
+    /// [`DirEntry::loop_target`]: struct.DirEntry.html#method.loop_target
+    Report,
+}
+
+/// Where symbolic links go among the other entries of a directory.
+///
+/// See [`WalkDir::group_symlinks`].
+///
+/// [`WalkDir::group_symlinks`]: struct.WalkDir.html#method.group_symlinks
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GroupOrder {
+    /// Yield symbolic links before any other entries.
+    First,
+    /// Yield symbolic links after all other entries.
+    Last,
+}
+
+/// A comparator used to sort the entries of each directory.
+type Sorter = dyn FnMut(&SortContext<'_>, &DirEntry, &DirEntry) -> Ordering
+    + Send
+    + Sync
+    + 'static;
+
+/// The directory whose entries are being compared by a function given to
+/// [`WalkDir::sort_by_with_context`].
+///
+/// [`WalkDir::sort_by_with_context`]: struct.WalkDir.html#method.sort_by_with_context
+#[derive(Clone, Copy, Debug)]
+pub struct SortContext<'a> {
+    parent: &'a Path,
+    depth: usize,
+}
+
+impl<'a> SortContext<'a> {
+    /// Returns the path of the directory whose entries are being sorted.
+    ///
+    /// This is the path of the directory's own entry, so it's the parent of
+    /// the paths of the entries being compared. When a symbolic link to a
+    /// directory is followed, this is the path of the link.
+    pub fn parent(&self) -> &'a Path {
+        self.parent
+    }
+
+    /// Returns the depth of the directory whose entries are being sorted.
+    ///
+    /// The entries being compared are one level deeper.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
 }
 
+/// A callback given to `on_progress`.
+type ProgressFn = dyn FnMut(Progress) + Send + 'static;
+
 impl fmt::Debug for WalkDirOptions {
     fn fmt(
         &self,
@@ -265,16 +405,72 @@ impl fmt::Debug for WalkDirOptions {
         } else {
             "None"
         };
-        f.debug_struct("WalkDirOptions")
-            .field("follow_links", &self.follow_links)
+        let progress_str = match self.progress {
+            // FnMut isn't `Debug`
+            Some((every, _)) => format!("Some({}, ...)", every),
+            None => "None".to_string(),
+        };
+        let mut d = f.debug_struct("WalkDirOptions");
+        d.field("follow_links", &self.follow_links)
             .field("follow_root_link", &self.follow_root_links)
             .field("max_open", &self.max_open)
             .field("min_depth", &self.min_depth)
             .field("max_depth", &self.max_depth)
+            .field("absolute_max_depth", &self.absolute_max_depth)
             .field("sorter", &sorter_str)
+            .field("group_symlinks", &self.group_symlinks)
+            .field("errors_last", &self.errors_last)
             .field("contents_first", &self.contents_first)
+            .field("emit_dir_close", &self.emit_dir_close)
             .field("same_file_system", &self.same_file_system)
-            .finish()
+            .field("track_file_systems", &self.track_file_systems)
+            .field("dedup_hard_links", &self.dedup_hard_links)
+            .field("skip_unreadable", &self.skip_unreadable)
+            .field("stop_dir_on_error", &self.stop_dir_on_error)
+            .field("retry_transient", &self.retry_transient)
+            .field("require_unicode", &self.require_unicode)
+            .field("prune_non_unicode", &self.prune_non_unicode)
+            .field("follow_mounts", &self.follow_mounts)
+            .field("on_loop", &self.on_loop)
+            .field("cross_fs_via_symlink", &self.cross_fs_via_symlink)
+            .field("normalize_paths", &self.normalize_paths)
+            .field("max_entries_per_dir", &self.max_entries_per_dir)
+            .field("eviction_policy", &self.eviction_policy)
+            .field("low_memory", &self.low_memory)
+            .field("progress", &progress_str)
+            .field("skip_dirs_ci", &self.skip_dirs_ci);
+        #[cfg(windows)]
+        d.field("follow_reparse_tags", &self.follow_reparse_tags);
+        #[cfg(feature = "ignore-files")]
+        d.field("marker_file", &self.marker_file)
+            .field("ignore_file", &self.ignore_file);
+        d.finish()
+    }
+}
+
+impl WalkDirOptions {
+    /// Returns an error if an option that isn't supported on this platform
+    /// is enabled.
+    fn check(&self) -> Result<()> {
+        if self.same_file_system && !WalkDir::supports_same_file_system() {
+            return Err(Error::from_unsupported_option("same_file_system"));
+        }
+        if self.track_file_systems && !WalkDir::supports_same_file_system() {
+            return Err(Error::from_unsupported_option("track_file_systems"));
+        }
+        if self.low_memory && self.sorter.is_some() {
+            return Err(Error::from_conflicting_options(
+                "low_memory",
+                "sort_by",
+            ));
+        }
+        if self.low_memory && self.group_symlinks.is_some() {
+            return Err(Error::from_conflicting_options(
+                "low_memory",
+                "group_symlinks",
+            ));
+        }
+        Ok(())
     }
 }
 
@@ -286,20 +482,149 @@ impl WalkDir {
     /// is always followed for the purposes of directory traversal. (A root
     /// `DirEntry` still obeys its documentation with respect to symlinks and
     /// the `follow_links` setting.)
+    ///
+    /// If `root` ends with a path separator, then it must be a directory (or
+    /// a symlink to one), as is the convention on POSIX systems. Otherwise,
+    /// the first and only item yielded is a "not a directory" error.
+    ///
+    /// The root is yielded exactly as given, and the paths of its
+    /// descendants are formed by joining their names onto it. So a root of
+    /// `.` yields `.`, then `./foo` and so on (with `\` as the separator on
+    /// Windows), and a root of `..` yields `..`, then `../foo`. An empty
+    /// root isn't a valid path, so the first and only item yielded for it is
+    /// an error whose [`io::ErrorKind`] is `InvalidInput`.
+    ///
+    /// [`io::ErrorKind`]: https://doc.rust-lang.org/stable/std/io/enum.ErrorKind.html
     pub fn new<P: AsRef<Path>>(root: P) -> Self {
         WalkDir {
             opts: WalkDirOptions {
                 follow_links: false,
                 follow_root_links: true,
+                #[cfg(windows)]
+                follow_reparse_tags: vec![util::IO_REPARSE_TAG_SYMLINK],
                 max_open: 10,
                 min_depth: 0,
-                max_depth: ::std::usize::MAX,
+                max_depth: usize::MAX,
+                absolute_max_depth: None,
                 sorter: None,
+                group_symlinks: None,
+                errors_last: false,
                 contents_first: false,
+                emit_dir_close: false,
                 same_file_system: false,
+                track_file_systems: false,
+                dedup_hard_links: false,
+                skip_unreadable: false,
+                stop_dir_on_error: false,
+                retry_transient: 0,
+                require_unicode: false,
+                prune_non_unicode: false,
+                follow_mounts: true,
+                on_loop: LoopPolicy::Error,
+                cross_fs_via_symlink: false,
+                normalize_paths: false,
+                max_entries_per_dir: None,
+                eviction_policy: EvictionPolicy::Shallowest,
+                low_memory: false,
+                progress: None,
+                skip_dirs_ci: vec![],
+                #[cfg(feature = "ignore-files")]
+                marker_file: None,
+                #[cfg(feature = "ignore-files")]
+                ignore_file: None,
             },
-            root: root.as_ref().to_path_buf(),
+            roots: vec![root.as_ref().to_path_buf()],
+            root_dent: None,
+        }
+    }
+
+    /// Create a builder for a recursive directory iterator over every path
+    /// matching the given glob pattern.
+    ///
+    /// The pattern is expanded with the [`glob`] crate when this is called,
+    /// and each matching path is then walked in turn, in the order given by
+    /// [`glob::glob`] (which is alphabetical), as if by [`WalkDir::new`]. If
+    /// nothing matches, then the iterator yields nothing. A path that can't
+    /// be read while expanding the pattern is still walked, which yields the
+    /// error for it.
+    ///
+    /// This is useful for handling patterns like `src/*/tests` given by
+    /// users of shells that don't expand them, like `cmd` on Windows.
+    ///
+    /// A [checkpoint] of a traversal with several roots only records the
+    /// position within the root that was being walked, so resuming from it
+    /// doesn't walk the remaining roots.
+    ///
+    /// This requires the `glob` feature to be enabled. Unlike the rest of
+    /// this crate, that feature requires Rust 1.63 or newer, since current
+    /// releases of the `glob` crate do.
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// # fn try_main() -> Result<(), glob::PatternError> {
+    /// for entry in WalkDir::new_glob("src/*/tests")? {
+    ///     println!("{}", entry.unwrap().path().display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`glob`]: https://docs.rs/glob
+    /// [`glob::glob`]: https://docs.rs/glob/*/glob/fn.glob.html
+    /// [`WalkDir::new`]: struct.WalkDir.html#method.new
+    /// [checkpoint]: struct.IntoIter.html#method.checkpoint
+    #[cfg(feature = "glob")]
+    pub fn new_glob(
+        pattern: &str,
+    ) -> result::Result<Self, glob::PatternError> {
+        let roots = glob::glob(pattern)?
+            .map(|result| match result {
+                Ok(path) => path,
+                Err(err) => err.path().to_path_buf(),
+            })
+            .collect();
+        let mut wd = WalkDir::new("");
+        wd.roots = roots;
+        Ok(wd)
+    }
+
+    /// Create a builder for a recursive directory iterator starting at an
+    /// entry yielded by a previous walk.
+    ///
+    /// This is like calling [`WalkDir::new`] with `entry.path()`, except
+    /// that what the previous walk already learned about the entry is
+    /// reused. Namely, the new iterator does not need to look up the file
+    /// type of its root, and if the entry knows its [`device`], the root
+    /// device doesn't need to be looked up when [`same_file_system`] is
+    /// enabled. Depths in the new walk start again at `0`.
+    ///
+    /// If the entry corresponds to a symbolic link, then nothing is reused,
+    /// since the root of a walk is always examined without following
+    /// symbolic links.
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// for entry in WalkDir::new("foo").max_depth(1) {
+    ///     let entry = entry.unwrap();
+    ///     if entry.depth() == 1 && entry.file_type().is_dir() {
+    ///         for sub in WalkDir::from_entry(&entry) {
+    ///             println!("{}", sub.unwrap().path().display());
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`WalkDir::new`]: struct.WalkDir.html#method.new
+    /// [`device`]: struct.DirEntry.html#method.device
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    pub fn from_entry(entry: &DirEntry) -> Self {
+        let mut wd = WalkDir::new(entry.path());
+        if !entry.path_is_symlink() {
+            wd.root_dent = Some(entry.to_root());
         }
+        wd
     }
 
     /// Set the minimum depth of entries yielded by the iterator.
@@ -323,7 +648,12 @@ impl WalkDir {
     ///
     /// Note that this will not simply filter the entries of the iterator, but
     /// it will actually avoid descending into directories when the depth is
-    /// exceeded.
+    /// exceeded. Directories at the maximum depth are yielded without being
+    /// opened at all, so with a maximum depth of `0`, the root is the only
+    /// entry yielded (exactly once, regardless of [`contents_first`]) and no
+    /// directory is read.
+    ///
+    /// [`contents_first`]: struct.WalkDir.html#method.contents_first
     pub fn max_depth(mut self, depth: usize) -> Self {
         self.opts.max_depth = depth;
         if self.opts.max_depth < self.opts.min_depth {
@@ -332,17 +662,81 @@ impl WalkDir {
         self
     }
 
+    /// Set both the minimum and maximum depth of entries yielded by the
+    /// iterator.
+    ///
+    /// Unlike [`min_depth`] and [`max_depth`], which silently clamp one
+    /// setting to the other, this returns an error if `min` is greater than
+    /// `max`. This is useful when the depths come from user input, e.g., the
+    /// flags of a command line tool.
+    ///
+    /// ```rust
+    /// use walkdir::WalkDir;
+    ///
+    /// assert!(WalkDir::new("foo").try_depths(1, 3).is_ok());
+    /// assert!(WalkDir::new("foo").try_depths(3, 1).is_err());
+    /// ```
+    ///
+    /// [`min_depth`]: struct.WalkDir.html#method.min_depth
+    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
+    pub fn try_depths(
+        mut self,
+        min: usize,
+        max: usize,
+    ) -> result::Result<Self, DepthError> {
+        if min > max {
+            return Err(DepthError::new(min, max));
+        }
+        self.opts.min_depth = min;
+        self.opts.max_depth = max;
+        Ok(self)
+    }
+
+    /// Set a depth beyond which the iterator reports an error instead of
+    /// descending any further.
+    ///
+    /// This is a safety valve for walking untrusted trees, which may be
+    /// pathologically deep, particularly when [`follow_links`] is enabled
+    /// and symbolic links can chain directories together without forming a
+    /// loop. Unlike [`max_depth`], which quietly prunes the tree, reaching
+    /// this depth is reported: a directory at this depth is still yielded,
+    /// but instead of its contents, the iterator yields an error for which
+    /// [`Error::is_depth_limit`] returns true. The rest of the tree is
+    /// walked as usual.
+    ///
+    /// This is disabled by default. When the directories at this depth
+    /// aren't descended into anyway because of [`max_depth`], no error is
+    /// yielded.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
+    /// [`Error::is_depth_limit`]: struct.Error.html#method.is_depth_limit
+    pub fn absolute_max_depth(mut self, depth: usize) -> Self {
+        self.opts.absolute_max_depth = Some(depth);
+        self
+    }
+
     /// Follow symbolic links. By default, this is disabled.
     ///
     /// When `yes` is `true`, symbolic links are followed as if they were
     /// normal directories and files. If a symbolic link is broken or is
-    /// involved in a loop, an error is yielded.
+    /// involved in a loop, an error is yielded. (What happens for loops can
+    /// be changed with [`on_loop`].)
     ///
     /// When enabled, the yielded [`DirEntry`] values represent the target of
     /// the link while the path corresponds to the link. See the [`DirEntry`]
     /// type for more details.
     ///
+    /// # Platform behavior
+    ///
+    /// On Windows, the standard library treats junctions like symbolic
+    /// links. By default, only actual symbolic links are followed, and
+    /// junctions and other reparse points are yielded without being followed.
+    /// See [`follow_reparse_tags`].
+    ///
     /// [`DirEntry`]: struct.DirEntry.html
+    /// [`on_loop`]: struct.WalkDir.html#method.on_loop
+    /// [`follow_reparse_tags`]: struct.WalkDir.html#method.follow_reparse_tags
     pub fn follow_links(mut self, yes: bool) -> Self {
         self.opts.follow_links = yes;
         self
@@ -367,6 +761,49 @@ impl WalkDir {
         self
     }
 
+    /// Set the reparse tags of the links that [`follow_links`] follows.
+    ///
+    /// Everything that the standard library considers a symbolic link is a
+    /// reparse point on Windows, and its reparse tag says what kind it is.
+    /// When [`follow_links`] is enabled, only links whose tag is in `tags`
+    /// are followed. Other links are yielded as they are, and aren't
+    /// descended into.
+    ///
+    /// This defaults to just `IO_REPARSE_TAG_SYMLINK` (`0xA000000C`). To
+    /// follow junctions as well, add `IO_REPARSE_TAG_MOUNT_POINT`
+    /// (`0xA0000003`).
+    ///
+    /// Finding the tag of a link costs opening a handle to it. The root is
+    /// always followed as described for [`follow_root_links`], regardless
+    /// of its tag.
+    ///
+    /// This is only available on Windows.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA0000003;
+    /// const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000000C;
+    ///
+    /// let tags = [IO_REPARSE_TAG_SYMLINK, IO_REPARSE_TAG_MOUNT_POINT];
+    /// for entry in WalkDir::new("foo")
+    ///     .follow_links(true)
+    ///     .follow_reparse_tags(&tags)
+    /// {
+    ///     println!("{}", entry.unwrap().path().display());
+    /// }
+    /// ```
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`follow_root_links`]: struct.WalkDir.html#method.follow_root_links
+    #[cfg(windows)]
+    pub fn follow_reparse_tags(mut self, tags: &[u32]) -> Self {
+        self.opts.follow_reparse_tags = tags.to_vec();
+        self
+    }
+
     /// Set the maximum number of simultaneously open file descriptors used
     /// by the iterator.
     ///
@@ -389,9 +826,10 @@ impl WalkDir {
     ///
     /// # Platform behavior
     ///
-    /// On Windows, if `follow_links` is enabled, then this limit is not
-    /// respected. In particular, the maximum number of file descriptors opened
-    /// is proportional to the depth of the directory tree traversed.
+    /// On Windows, if `follow_links` is enabled or `follow_mounts` is
+    /// disabled, then this limit is not respected. In particular, the maximum
+    /// number of file descriptors opened is proportional to the depth of the
+    /// directory tree traversed.
     pub fn max_open(mut self, mut n: usize) -> Self {
         if n == 0 {
             n = 1;
@@ -400,6 +838,107 @@ impl WalkDir {
         self
     }
 
+    /// Call a function with the progress of the traversal after every
+    /// `every` results.
+    ///
+    /// Both entries and errors count as results. The function is also called
+    /// once more when the traversal finishes, so that the last counts it
+    /// sees are the totals. If `every` is `0`, then it is set to `1`.
+    ///
+    /// The counts are those of the iterator returned by `into_iter`. In
+    /// particular, entries that are then skipped by [`filter_entry`] are
+    /// counted. Iterators created from the same borrowed `WalkDir` share the
+    /// function, but each one reports its own counts.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let walker = WalkDir::new("foo").on_progress(1000, |progress| {
+    ///     eprint!("\r{} entries", progress.entries_yielded());
+    /// });
+    /// for entry in walker {
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// [`filter_entry`]: struct.IntoIter.html#method.filter_entry
+    pub fn on_progress<F>(mut self, every: usize, f: F) -> Self
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        self.opts.progress = Some((max(every, 1), Arc::new(Mutex::new(f))));
+        self
+    }
+
+    /// Choose which directory handle is closed when [`max_open`] is reached.
+    ///
+    /// A closed directory has all of its remaining entries read into memory
+    /// before its handle is closed. Which directory is picked doesn't change
+    /// the results, only the resources used:
+    ///
+    /// * [`EvictionPolicy::Shallowest`] closes the open directory closest to
+    ///   the root. Those are the directories that will be returned to last,
+    ///   so a long descent into deep directories keeps reading from open
+    ///   handles. However, shallow directories tend to have the most
+    ///   remaining entries, all of which are then kept in memory.
+    /// * [`EvictionPolicy::Deepest`] closes the open directory furthest from
+    ///   the root, i.e., the parent of the directory being opened. Since its
+    ///   entries are partially read already, this tends to minimize the
+    ///   number of buffered entries. However, every further level of a deep
+    ///   descent then closes the level before it.
+    ///
+    /// This is [`EvictionPolicy::Shallowest`] by default.
+    ///
+    /// [`max_open`]: struct.WalkDir.html#method.max_open
+    /// [`EvictionPolicy::Shallowest`]: enum.EvictionPolicy.html#variant.Shallowest
+    /// [`EvictionPolicy::Deepest`]: enum.EvictionPolicy.html#variant.Deepest
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.opts.eviction_policy = policy;
+        self
+    }
+
+    /// Never read the remaining entries of a directory into memory.
+    ///
+    /// Normally, the entries of a directory are buffered in memory when
+    /// [`max_open`] forces its handle to be closed, and when a sorter is set.
+    /// For a directory with millions of entries, that's a lot of memory.
+    ///
+    /// When this option is enabled, every directory on the current path is
+    /// kept open instead, so that its entries are read as they are yielded,
+    /// in the order the file system returns them. The memory used for the
+    /// walk itself is then bounded by `O(depth)`: an open handle (including
+    /// the buffer the operating system reads entries into) per level, plus
+    /// the directory entries that [`contents_first`] defers, of which there
+    /// is at most one per level too. The cost is that [`max_open`] is
+    /// ignored, so the number of open file descriptors grows with the depth
+    /// of the tree.
+    ///
+    /// Sorting needs every entry of a directory at once, so setting a sorter
+    /// (e.g., with [`sort_by`]) conflicts with this option. If both are used,
+    /// then traversal immediately returns an error for which
+    /// [`Error::conflicting_options`] returns
+    /// `Some(("low_memory", "sort_by"))`, and doesn't yield any entries.
+    ///
+    /// Some other options keep memory proportional to the number of entries
+    /// seen, regardless of this option. Notably, [`dedup_hard_links`]
+    /// remembers every file with more than one link, and iterating from the
+    /// back with [`next_back`] reads the rest of the walk into memory.
+    ///
+    /// This is disabled by default.
+    ///
+    /// [`max_open`]: struct.WalkDir.html#method.max_open
+    /// [`contents_first`]: struct.WalkDir.html#method.contents_first
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    /// [`Error::conflicting_options`]: struct.Error.html#method.conflicting_options
+    /// [`dedup_hard_links`]: struct.WalkDir.html#method.dedup_hard_links
+    /// [`next_back`]: struct.IntoIter.html#method.next_back
+    pub fn low_memory(mut self, yes: bool) -> Self {
+        self.opts.low_memory = yes;
+        self
+    }
+
     /// Set a function for sorting directory entries with a comparator
     /// function.
     ///
@@ -407,6 +946,22 @@ impl WalkDir {
     /// paths in sorted order. The compare function will be called to compare
     /// entries from the same directory.
     ///
+    /// Each directory is read in full and sorted once, as soon as it is
+    /// opened. Its sorted entries are then kept in memory until they have all
+    /// been yielded, so the directory is never read or sorted again, even
+    /// when [`max_open`] forces its handle to be closed. The cost is that
+    /// every directory on the current path holds its remaining entries in
+    /// memory, which is also what [`max_open`] does for unsorted directories
+    /// that had to be closed.
+    ///
+    /// Iterators created from the same borrowed `WalkDir` share the compare
+    /// function, so any state it keeps carries over from one to the next.
+    ///
+    /// If the compare function panics, then the panic propagates out of the
+    /// call to `next` and the iterator is done: should the panic be caught,
+    /// every further call to `next` returns `None`. Calling [`reset`] starts
+    /// the walk over.
+    ///
     /// ```rust,no_run
     /// use std::cmp;
     /// use std::ffi::OsString;
@@ -414,11 +969,47 @@ impl WalkDir {
     ///
     /// WalkDir::new("foo").sort_by(|a,b| a.file_name().cmp(b.file_name()));
     /// ```
-    pub fn sort_by<F>(mut self, cmp: F) -> Self
+    ///
+    /// [`max_open`]: struct.WalkDir.html#method.max_open
+    /// [`reset`]: struct.IntoIter.html#method.reset
+    pub fn sort_by<F>(self, mut cmp: F) -> Self
     where
         F: FnMut(&DirEntry, &DirEntry) -> Ordering + Send + Sync + 'static,
     {
-        self.opts.sorter = Some(Box::new(cmp));
+        self.sort_by_with_context(move |_, a, b| cmp(a, b))
+    }
+
+    /// Set a function for sorting directory entries with a comparator
+    /// function that also gets told which directory is being sorted.
+    ///
+    /// This is like [`sort_by`], except the compare function is also given
+    /// a [`SortContext`] with the path and depth of the directory whose
+    /// entries are being compared. This makes it possible to sort
+    /// differently depending on the location in the tree without having to
+    /// look at the parent of each entry's path.
+    ///
+    /// ```rust,no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// WalkDir::new("foo").sort_by_with_context(|ctx, a, b| {
+    ///     if ctx.parent().ends_with("assets") {
+    ///         a.path().extension().cmp(&b.path().extension())
+    ///     } else {
+    ///         a.file_name().cmp(b.file_name())
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    /// [`SortContext`]: struct.SortContext.html
+    pub fn sort_by_with_context<F>(mut self, cmp: F) -> Self
+    where
+        F: FnMut(&SortContext<'_>, &DirEntry, &DirEntry) -> Ordering
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.opts.sorter = Some(Arc::new(Mutex::new(cmp)));
         self
     }
 
@@ -448,13 +1039,102 @@ impl WalkDir {
     ///
     /// This is a convenience function for calling `Self::sort_by()`.
     ///
+    /// File names are compared by their raw representation, without regard
+    /// to locale or Unicode: on Unix, they're compared byte by byte and on
+    /// Windows, they're compared by UTF-16 code unit. On Unix, this is the
+    /// same order as that of [`OsStr`]. On Windows, it differs from the
+    /// order of [`OsStr`] and [`Path`] for names containing characters
+    /// outside of the Basic Multilingual Plane.
+    ///
     /// ```rust,no_run
     /// use walkdir::WalkDir;
     ///
     /// WalkDir::new("foo").sort_by_file_name();
     /// ```
+    ///
+    /// [`OsStr`]: https://doc.rust-lang.org/stable/std/ffi/struct.OsStr.html
+    /// [`Path`]: https://doc.rust-lang.org/stable/std/path/struct.Path.html
     pub fn sort_by_file_name(self) -> Self {
-        self.sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        self.sort_by(|a, b| util::cmp_file_names(a.file_name(), b.file_name()))
+    }
+
+    /// Sort directory entries by file name, ignoring ASCII case.
+    ///
+    /// This is like [`sort_by_file_name`], except ASCII letters are compared
+    /// as if they were lowercase. Other characters, including non-ASCII
+    /// letters, are compared as they are. Names that only differ in ASCII
+    /// case are ordered as by [`sort_by_file_name`], so the order is still
+    /// deterministic.
+    ///
+    /// ```rust,no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// WalkDir::new("foo").sort_by_file_name_lossy_ci();
+    /// ```
+    ///
+    /// [`sort_by_file_name`]: struct.WalkDir.html#method.sort_by_file_name
+    pub fn sort_by_file_name_lossy_ci(self) -> Self {
+        self.sort_by(|a, b| {
+            let (a, b) = (a.file_name(), b.file_name());
+            util::cmp_file_names_ascii_ci(a, b)
+                .then_with(|| util::cmp_file_names(a, b))
+        })
+    }
+
+    /// Group the symbolic links in each directory before or after its other
+    /// entries.
+    ///
+    /// Within each group, entries are sorted by the function given to
+    /// [`sort_by`] (or one of its variants) if there is one, and by file
+    /// name as with [`sort_by_file_name`] otherwise. Whether an entry is a
+    /// symbolic link is decided by the entry itself, regardless of
+    /// [`follow_links`].
+    ///
+    /// Like sorting, this reads each directory in full as soon as it's
+    /// opened, and so it conflicts with [`low_memory`].
+    ///
+    /// ```rust,no_run
+    /// use walkdir::{GroupOrder, WalkDir};
+    ///
+    /// WalkDir::new("foo").group_symlinks(GroupOrder::Last);
+    /// ```
+    ///
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    /// [`sort_by_file_name`]: struct.WalkDir.html#method.sort_by_file_name
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`low_memory`]: struct.WalkDir.html#method.low_memory
+    pub fn group_symlinks(mut self, order: GroupOrder) -> Self {
+        self.opts.group_symlinks = Some(order);
+        self
+    }
+
+    /// Yield the errors for a directory's entries after the entries
+    /// themselves.
+    ///
+    /// By default, when a directory is read in full because a sorter is set
+    /// with [`sort_by`] (or one of its variants) or because of
+    /// [`group_symlinks`], any errors that occurred while reading it are
+    /// yielded before its entries. When this is enabled, they're yielded
+    /// after its last entry instead, so that the sorted entries come out
+    /// without interruption. This includes the error for a sub-directory
+    /// that can't be opened, which would otherwise be yielded right after
+    /// the sub-directory itself. Either way, the [`depth`] of each error is
+    /// unchanged.
+    ///
+    /// Without sorting, a directory is read as it's walked, so errors that
+    /// occur while reading it can't be held back and are yielded as they
+    /// occur. This is only done on a best-effort basis then: the errors for
+    /// sub-directories that can't be opened are still held back until the
+    /// directory is left.
+    ///
+    /// This is disabled by default.
+    ///
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    /// [`group_symlinks`]: struct.WalkDir.html#method.group_symlinks
+    /// [`depth`]: struct.Error.html#method.depth
+    pub fn errors_last(mut self, yes: bool) -> Self {
+        self.opts.errors_last = yes;
+        self
     }
 
     /// Yield a directory's contents before the directory itself. By default,
@@ -519,71 +1199,871 @@ impl WalkDir {
         self
     }
 
+    /// Yield a second entry for each directory once its contents have been
+    /// yielded.
+    ///
+    /// When `yes` is `true`, the iterator yields each directory twice: once
+    /// as usual, before its contents, and once more right after them. The
+    /// second entry is marked by [`DirEntry::is_dir_close`] returning true.
+    /// Every directory that is yielded gets exactly one of these, even if it
+    /// isn't descended into (because of [`max_depth`], [`same_file_system`]
+    /// or [`skip_current_dir`], say), so the markers are always balanced.
+    /// This is useful for, e.g., diffing two directory trees.
+    ///
+    /// This is disabled by default. It has no effect when [`contents_first`]
+    /// is enabled, since each directory is then yielded only once, right
+    /// after its contents.
+    ///
+    /// The predicate given to [`filter_entry`] is applied to the second entry
+    /// too, so a deterministic predicate yields either both entries of a
+    /// directory or neither.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let mut indent = 0;
+    /// for entry in WalkDir::new("foo").emit_dir_close(true) {
+    ///     let entry = entry.unwrap();
+    ///     if entry.is_dir_close() {
+    ///         indent -= 1;
+    ///         continue;
+    ///     }
+    ///     println!("{:2$}{}", "", entry.file_name().to_string_lossy(), indent);
+    ///     if entry.file_type().is_dir() {
+    ///         indent += 1;
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`DirEntry::is_dir_close`]: struct.DirEntry.html#method.is_dir_close
+    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    /// [`skip_current_dir`]: struct.IntoIter.html#method.skip_current_dir
+    /// [`contents_first`]: struct.WalkDir.html#method.contents_first
+    /// [`filter_entry`]: struct.IntoIter.html#method.filter_entry
+    pub fn emit_dir_close(mut self, yes: bool) -> Self {
+        self.opts.emit_dir_close = yes;
+        self
+    }
+
     /// Do not cross file system boundaries.
     ///
     /// When this option is enabled, directory traversal will not descend into
     /// directories that are on a different file system from the root path.
+    /// If the root path is a symbolic link, then the file system of its
+    /// target is used, since a root symlink is followed for traversal
+    /// regardless of [`follow_links`]. The device that was used is reported
+    /// by [`WalkStats::root_device`].
     ///
-    /// Currently, this option is only supported on Unix and Windows. If this
-    /// option is used on an unsupported platform, then directory traversal
-    /// will immediately return an error and will not yield any entries.
+    /// When [`follow_links`] is also enabled, a symbolic link to a directory
+    /// on a different file system is yielded but not descended into. See
+    /// [`cross_fs_via_symlink`] to change this.
+    ///
+    /// Currently, this option is only supported on Unix and Windows, which
+    /// can be checked with [`supports_same_file_system`]. If this option is
+    /// used on an unsupported platform, then directory traversal will
+    /// immediately return an error for which [`Error::unsupported_option`]
+    /// returns `Some("same_file_system")`, and will not yield any entries.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`supports_same_file_system`]: struct.WalkDir.html#method.supports_same_file_system
+    /// [`Error::unsupported_option`]: struct.Error.html#method.unsupported_option
+    /// [`cross_fs_via_symlink`]: struct.WalkDir.html#method.cross_fs_via_symlink
+    /// [`WalkStats::root_device`]: struct.WalkStats.html#method.root_device
     pub fn same_file_system(mut self, yes: bool) -> Self {
         self.opts.same_file_system = yes;
         self
     }
-}
 
-impl IntoIterator for WalkDir {
-    type Item = Result<DirEntry>;
-    type IntoIter = IntoIter;
+    /// Returns true if the [`same_file_system`] option is supported on this
+    /// platform.
+    ///
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    pub const fn supports_same_file_system() -> bool {
+        cfg!(any(unix, windows))
+    }
 
-    fn into_iter(self) -> IntoIter {
-        IntoIter {
-            opts: self.opts,
-            start: Some(self.root),
-            stack_list: vec![],
-            stack_path: vec![],
-            oldest_opened: 0,
-            depth: 0,
-            deferred_dirs: vec![],
-            root_device: None,
-        }
+    /// Reports how well the enabled options are honored on this platform.
+    ///
+    /// This makes it possible to warn about options that won't work as
+    /// expected before starting a long walk. Only options that are enabled
+    /// and whose behavior depends on the platform (or on other options) are
+    /// reported. Options reported as [`Support::Unsupported`] yield errors
+    /// when walking.
+    ///
+    /// This doesn't touch the file system.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::{Support, WalkDir};
+    ///
+    /// let walker = WalkDir::new("foo").same_file_system(true);
+    /// for (option, support) in walker.capabilities().iter() {
+    ///     if support != Support::Supported {
+    ///         eprintln!("warning: {}: {:?}", option, support);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`Support::Unsupported`]: enum.Support.html#variant.Unsupported
+    pub fn capabilities(&self) -> WalkCapabilities {
+        capabilities::walk_capabilities(&self.opts, Platform::CURRENT)
     }
-}
 
-/// An iterator for recursively descending into a directory.
-///
-/// A value with this type must be constructed with the [`WalkDir`] type, which
-/// uses a builder pattern to set options such as min/max depth, max open file
-/// descriptors and whether the iterator should follow symbolic links. After
-/// constructing a `WalkDir`, call [`.into_iter()`] at the end of the chain.
-///
-/// The order of elements yielded by this iterator is unspecified.
-///
-/// [`WalkDir`]: struct.WalkDir.html
-/// [`.into_iter()`]: struct.WalkDir.html#into_iter.v
-#[derive(Debug)]
-pub struct IntoIter {
-    /// Options specified in the builder. Depths, max fds, etc.
-    opts: WalkDirOptions,
-    /// The start path.
+    /// Descend into symbolic links to other file systems, even when
+    /// [`same_file_system`] is enabled.
     ///
-    /// This is only `Some(...)` at the beginning. After the first iteration,
-    /// this is always `None`.
-    start: Option<PathBuf>,
-    /// A stack of open (up to max fd) or closed handles to directories.
-    /// An open handle is a plain [`fs::ReadDir`] while a closed handle is
-    /// a `Vec<fs::DirEntry>` corresponding to the as-of-yet consumed entries.
+    /// The [`same_file_system`] restriction is meant to stop traversal at
+    /// mount points. When this option is enabled and [`follow_links`] is
+    /// enabled too, an explicit symbolic link to a directory on another file
+    /// system is descended into anyway. The restriction then applies
+    /// relative to the file system of the link's target. That is,
+    /// directories below the link are only descended into if they are on
+    /// the same file system as the link's target.
     ///
-    /// [`fs::ReadDir`]: https://doc.rust-lang.org/stable/std/fs/struct.ReadDir.html
-    stack_list: Vec<DirList>,
-    /// A stack of file paths.
+    /// This has no effect unless both [`same_file_system`] and
+    /// [`follow_links`] are enabled.
     ///
-    /// This is *only* used when [`follow_links`] is enabled. In all other
-    /// cases this stack is empty.
+    /// This is disabled by default.
     ///
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
     /// [`follow_links`]: struct.WalkDir.html#method.follow_links
-    stack_path: Vec<Ancestor>,
+    pub fn cross_fs_via_symlink(mut self, yes: bool) -> Self {
+        self.opts.cross_fs_via_symlink = yes;
+        self
+    }
+
+    /// Record which file systems the traversal descends into, without
+    /// pruning anything.
+    ///
+    /// When this option is enabled, the device of the root and of every
+    /// directory is looked up, as [`same_file_system`] does, but no directory
+    /// is skipped because of it. The distinct devices of the directories
+    /// descended into so far are reported by [`IntoIter::file_systems_seen`],
+    /// so that, e.g., a disk usage tool can tell afterwards whether its total
+    /// includes other mounts. [`DirEntry::device`] then reports the device
+    /// of each entry, just like with [`same_file_system`].
+    ///
+    /// This costs one `stat` call (or opening a handle on Windows) per
+    /// directory. No extra calls are made for other entries, since only
+    /// directories can be mount points.
+    ///
+    /// This is disabled by default. Like [`same_file_system`], this is only
+    /// supported on Unix and Windows.
+    ///
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    /// [`IntoIter::file_systems_seen`]: struct.IntoIter.html#method.file_systems_seen
+    /// [`DirEntry::device`]: struct.DirEntry.html#method.device
+    pub fn track_file_systems(mut self, yes: bool) -> Self {
+        self.opts.track_file_systems = yes;
+        self
+    }
+
+    /// Collapse runs of path separators in the paths of yielded entries.
+    ///
+    /// Paths of entries are built by joining file names onto the root path,
+    /// so a root like `foo//bar` produces entries like `foo//bar/baz`. When
+    /// this option is enabled, runs of separators in the root path are
+    /// collapsed into one separator before walking, and thus in the paths of
+    /// all entries too.
+    ///
+    /// This is purely cosmetic and only changes the value returned by
+    /// [`DirEntry::path`]. In particular, `.` and `..` components are left
+    /// alone, since resolving them could change which file a path refers
+    /// to in the presence of symbolic links. On Windows, UNC prefixes are
+    /// kept as they are, and verbatim (`\\?\`) paths aren't changed at all.
+    ///
+    /// This is disabled by default.
+    ///
+    /// [`DirEntry::path`]: struct.DirEntry.html#method.path
+    pub fn normalize_paths(mut self, yes: bool) -> Self {
+        self.opts.normalize_paths = yes;
+        self
+    }
+
+    /// Read at most `limit` entries from each directory.
+    ///
+    /// Once `limit` entries have been read from a directory, the rest of its
+    /// entries are never looked at and, in particular, never descended into.
+    /// This makes the output incomplete by design, which is useful for
+    /// sampling trees that contain very wide directories.
+    ///
+    /// Every entry read from a directory counts towards the limit, including
+    /// errors and entries that are then skipped, e.g., because of
+    /// [`min_depth`] or [`dedup_hard_links`]. Entries skipped because of
+    /// [`skip_unreadable`] don't count. When [`sort_by`] is used, the whole
+    /// directory is still read and sorted, and the first `limit` entries in
+    /// sorted order are kept.
+    ///
+    /// This is `None` (no limit) by default.
+    ///
+    /// [`min_depth`]: struct.WalkDir.html#method.min_depth
+    /// [`dedup_hard_links`]: struct.WalkDir.html#method.dedup_hard_links
+    /// [`skip_unreadable`]: struct.WalkDir.html#method.skip_unreadable
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    pub fn max_entries_per_dir(mut self, limit: Option<usize>) -> Self {
+        self.opts.max_entries_per_dir = limit;
+        self
+    }
+
+    /// Skip directories with any of the given names, ignoring ASCII case.
+    ///
+    /// A directory whose file name matches one of `names` is skipped
+    /// entirely: it is neither yielded nor descended into. The root is never
+    /// skipped. Calling this more than once adds to the names to skip.
+    ///
+    /// Names are compared as they are stored by the platform (bytes on Unix
+    /// and UTF-16 code units on Windows), treating the ASCII letters `A`
+    /// to `Z` and `a` to `z` as equal. No other folding is done, so `Ä` and
+    /// `ä` are different, as are names that a file system would consider
+    /// equivalent under Unicode case folding or normalization. Since
+    /// directory names like `node_modules` or `.git` are almost always
+    /// ASCII, this is enough to skip them on case-insensitive file systems
+    /// like the defaults on Windows and macOS, regardless of how they were
+    /// spelled when created.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let wd = WalkDir::new("foo")
+    ///     .skip_dirs_case_insensitive(["node_modules", ".git"]);
+    /// for entry in wd {
+    ///     println!("{}", entry.unwrap().path().display());
+    /// }
+    /// ```
+    pub fn skip_dirs_case_insensitive<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.opts
+            .skip_dirs_ci
+            .extend(names.into_iter().map(|n| n.as_ref().to_os_string()));
+        self
+    }
+
+    /// Skip directories that contain a marker file with the given name.
+    ///
+    /// A directory containing a file named `name` is skipped entirely: it is
+    /// neither yielded nor descended into. The root is never skipped.
+    ///
+    /// As a special case, if `name` is `CACHEDIR.TAG`, then the marker file
+    /// must also begin with the signature required by the [Cache Directory
+    /// Tagging Specification]. This makes it easy to skip caches created by
+    /// other tools.
+    ///
+    /// Checking for the marker costs one `stat` (or `open` for
+    /// `CACHEDIR.TAG`) for every directory visited.
+    ///
+    /// This is only available when the `ignore-files` feature is enabled.
+    ///
+    /// [Cache Directory Tagging Specification]: https://bford.info/cachedir/
+    #[cfg(feature = "ignore-files")]
+    pub fn respect_ignore_file<S: AsRef<OsStr>>(mut self, name: S) -> Self {
+        self.opts.marker_file = Some(name.as_ref().to_os_string());
+        self
+    }
+
+    /// Skip entries matching patterns read from files with the given name.
+    ///
+    /// Whenever a directory is opened, the file named `name` inside of it is
+    /// read (if it exists). Each of its lines is a glob pattern, and entries
+    /// in that directory or any of its descendants whose file name matches
+    /// one of these patterns are skipped. A skipped directory is not
+    /// descended into.
+    ///
+    /// The patterns are a small subset of `.gitignore` syntax:
+    ///
+    /// * Empty lines and lines starting with `#` are ignored.
+    /// * `*` matches any sequence of characters, `?` matches any single
+    ///   character and `[...]` matches any character in the class (`[!...]`
+    ///   negates it). A `\` matches the next character literally.
+    /// * Patterns are only matched against file names, never against paths.
+    /// * A pattern ending with `/` only matches directories.
+    /// * A pattern starting with `!` re-includes entries that an earlier
+    ///   pattern excluded.
+    ///
+    /// When several patterns match an entry, the last pattern in the ignore
+    /// file closest to the entry wins. In particular, an ignore file in a
+    /// sub-directory can override patterns from its ancestors.
+    ///
+    /// If an ignore file exists but can't be read, then an error is yielded
+    /// for its directory, which is not descended into.
+    ///
+    /// This is only available when the `ignore-files` feature is enabled.
+    #[cfg(feature = "ignore-files")]
+    pub fn respect_simple_ignore<S: AsRef<OsStr>>(mut self, name: S) -> Self {
+        self.opts.ignore_file = Some(name.as_ref().to_os_string());
+        self
+    }
+
+    /// Check that a traversal with these options can start, without
+    /// yielding any entries.
+    ///
+    /// This looks up the root path (following it if it's a symbolic link and
+    /// [`follow_root_links`] is enabled) and, if [`same_file_system`] is
+    /// enabled, its device. This makes it possible to fail fast, for example
+    /// when the root doesn't exist, isn't accessible or when an enabled
+    /// option isn't supported on the current platform.
+    ///
+    /// The error returned is the same as the one the first call to `next`
+    /// would yield. Note that a successful validation doesn't guarantee that
+    /// the traversal won't produce any errors later, since the file system
+    /// may change in the meantime.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let walker = WalkDir::new("foo").same_file_system(true);
+    /// if let Err(err) = walker.validate() {
+    ///     eprintln!("cannot walk foo: {}", err);
+    ///     return;
+    /// }
+    /// for entry in walker {
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// [`follow_root_links`]: struct.WalkDir.html#method.follow_root_links
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    pub fn validate(&self) -> Result<()> {
+        self.opts.check()?;
+        for root in &self.roots {
+            if root.as_os_str().is_empty() {
+                return Err(Error::from_empty_root());
+            }
+            let dent = match self.root_dent {
+                Some(ref dent) => dent.clone(),
+                None => DirEntry::from_path(0, root.clone(), false)?,
+            };
+            self.validate_root(dent)?;
+        }
+        Ok(())
+    }
+
+    fn validate_root(&self, dent: DirEntry) -> Result<()> {
+        if util::has_trailing_separator(dent.path()) && !dent.is_dir() {
+            fs::read_dir(dent.path())
+                .map_err(|err| Error::from_entry(&dent, err))?;
+        }
+        if dent.file_type().is_symlink() && self.opts.follow_root_links {
+            fs::metadata(dent.path())
+                .map_err(|err| Error::from_entry(&dent, err))?;
+        }
+        if self.opts.same_file_system && dent.device().is_none() {
+            util::device_num(dent.path())
+                .map_err(|err| Error::from_entry(&dent, err))?;
+        }
+        Ok(())
+    }
+
+    /// Create an iterator after checking the options and the root.
+    ///
+    /// [`into_iter`] doesn't do anything until the first call to `next`, so
+    /// problems with the options or the root only show up as the first item
+    /// yielded. This instead does the work for the root right away: the
+    /// options are checked, the root is looked up and, if it's a directory
+    /// that is to be descended into, it's opened. If any of that fails, the
+    /// error is returned. In particular, unlike with [`into_iter`], a root
+    /// directory that can't be opened is an error here rather than being
+    /// yielded and followed by an error.
+    ///
+    /// Otherwise, the iterator yields exactly what [`into_iter`] would have,
+    /// starting with the root, and the work for the root isn't repeated.
+    ///
+    /// This is like [`validate`], except that the work isn't done twice and
+    /// that the root is opened as well. When walking several roots, only
+    /// the first one is checked.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// # fn try_main() -> Result<(), walkdir::Error> {
+    /// for entry in WalkDir::new("foo").try_into_iter()? {
+    ///     println!("{}", entry?.path().display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`into_iter`]: struct.WalkDir.html#impl-IntoIterator-for-WalkDir
+    /// [`validate`]: struct.WalkDir.html#method.validate
+    pub fn try_into_iter(self) -> Result<IntoIter> {
+        let mut it = self.into_iter();
+        let start = match it.start.take() {
+            None => return Ok(it),
+            Some(start) => start,
+        };
+        it.eager_root = true;
+        let result = it.start_root(start);
+        it.eager_root = false;
+        match result {
+            None => {}
+            Some(Ok(dent)) => it.pending_entry = Some(dent),
+            Some(Err(err)) => return Err(err),
+        }
+        Ok(it)
+    }
+
+    /// Run this traversal on a new thread, sending its results to a channel.
+    ///
+    /// At most `bound` results are buffered in the channel. When it is full,
+    /// the traversal waits for the receiver to catch up. A `bound` of `0`
+    /// makes every send wait until the result is received.
+    ///
+    /// The traversal stops as soon as it notices that the [`Receiver`] has
+    /// been dropped. This is checked after every result, so no more
+    /// directories are opened once the receiver is gone.
+    ///
+    /// The returned [`WalkHandle`] can be used to wait for the traversal to
+    /// finish and to get statistics about it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let (handle, rx) = WalkDir::new("foo").into_channel(64);
+    /// for result in rx {
+    ///     match result {
+    ///         Ok(entry) => println!("{}", entry.path().display()),
+    ///         Err(err) => eprintln!("ERROR: {}", err),
+    ///     }
+    /// }
+    /// let stats = handle.join();
+    /// println!("{} entries", stats.entries());
+    /// ```
+    ///
+    /// [`Receiver`]: https://doc.rust-lang.org/stable/std/sync/mpsc/struct.Receiver.html
+    /// [`WalkHandle`]: struct.WalkHandle.html
+    pub fn into_channel(
+        self,
+        bound: usize,
+    ) -> (WalkHandle, Receiver<Result<DirEntry>>) {
+        channel::spawn(self, bound)
+    }
+
+    /// Resume a traversal from a checkpoint taken with
+    /// [`IntoIter::checkpoint`].
+    ///
+    /// Each directory recorded in the checkpoint is opened again and the
+    /// entries that had already been read from it are skipped. The options
+    /// of this `WalkDir` are used for the rest of the traversal, so they
+    /// should match the ones used when the checkpoint was taken. If the
+    /// checkpoint was taken before the traversal started, then this is the
+    /// same as calling `into_iter`. The root path given to this `WalkDir` is
+    /// otherwise ignored.
+    ///
+    /// Entries are skipped by count, which relies on each directory
+    /// listing its entries in the same order as before. This is guaranteed
+    /// when [`sort_by`] is used, and is usually, but not always, the case
+    /// otherwise. If the file system was modified in between, then some
+    /// entries may be skipped and others may be yielded twice.
+    ///
+    /// If a directory can't be opened again, then an error for it is the
+    /// first thing yielded and the traversal continues with its parent.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let mut it = WalkDir::new("foo").into_iter();
+    /// for entry in it.by_ref().take(100) {
+    ///     println!("{}", entry.unwrap().path().display());
+    /// }
+    /// let checkpoint = it.checkpoint();
+    /// drop(it);
+    ///
+    /// for entry in WalkDir::new("foo").resume(checkpoint) {
+    ///     println!("{}", entry.unwrap().path().display());
+    /// }
+    /// ```
+    ///
+    /// [`IntoIter::checkpoint`]: struct.IntoIter.html#method.checkpoint
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    pub fn resume(self, checkpoint: Checkpoint) -> IntoIter {
+        let mut it = self.into_iter();
+        if let Some(dirs) = checkpoint.into_dirs() {
+            it.start = None;
+            it.start_dent = None;
+            it.roots = vec![].into_iter();
+            it.restore(dirs);
+        }
+        it
+    }
+
+    /// Yield each hard linked file at most once.
+    ///
+    /// When this option is enabled, a regular file is skipped if another
+    /// name for the same underlying file (as identified by its device and
+    /// inode number on Unix, or its volume serial number and file index on
+    /// Windows) has already been yielded. Which of the names is yielded
+    /// depends on the order in which entries are visited.
+    ///
+    /// This requires an extra `stat` call (or opening a handle on Windows)
+    /// for every regular file visited, and the identity of every file with
+    /// more than one link is kept in memory for the duration of the walk.
+    ///
+    /// Directories, symbolic links and other kinds of files are unaffected
+    /// by this option. (When [`follow_links`] is enabled, a symbolic link to
+    /// a regular file is treated as a regular file.)
+    ///
+    /// Currently, this option is only supported on Unix and Windows. If this
+    /// option is used on an unsupported platform, then an error is yielded
+    /// for every regular file.
+    ///
+    /// This is disabled by default.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    pub fn dedup_hard_links(mut self, yes: bool) -> Self {
+        self.opts.dedup_hard_links = yes;
+        self
+    }
+
+    /// Silently skip entries whose type can't be determined because they
+    /// can't be accessed.
+    ///
+    /// Some entries need an extra `stat` call before the iterator can know
+    /// what kind of file they are. This happens when the file system doesn't
+    /// report the type while reading the directory, and for symbolic links
+    /// when [`follow_links`] is enabled. When this option is enabled and
+    /// that `stat` call fails because the entry doesn't exist (anymore) or
+    /// because permission was denied, then the entry is dropped instead of
+    /// yielding an error. This makes walking pseudo file systems like
+    /// `/proc` and `/sys` on Linux, where entries may vanish or deny access
+    /// while being walked, less noisy.
+    ///
+    /// No other errors are affected. In particular, errors for directories
+    /// that can't be opened, for the root path or for symbolic link loops are
+    /// still yielded.
+    ///
+    /// This is disabled by default.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    pub fn skip_unreadable(mut self, yes: bool) -> Self {
+        self.opts.skip_unreadable = yes;
+        self
+    }
+
+    /// Treat an error while reading a directory's entries as the end of
+    /// that directory.
+    ///
+    /// Reading the entries of a directory that was opened successfully can
+    /// still fail part way through, e.g., when a network file system goes
+    /// away or a disk has a bad sector. Normally, such an error is yielded
+    /// and the directory's listing ends there. When this option is enabled,
+    /// the listing still ends there, but no error is yielded, as if the
+    /// directory simply had no more entries. The entries read before the
+    /// error are yielded as usual, and the walk continues with the rest of
+    /// the tree.
+    ///
+    /// This hides errors by design, so a walk with this enabled may be
+    /// incomplete without any indication. It doesn't affect errors for
+    /// opening a directory or for looking up a single entry.
+    ///
+    /// This is disabled by default.
+    pub fn stop_dir_on_error(mut self, yes: bool) -> Self {
+        self.opts.stop_dir_on_error = yes;
+        self
+    }
+
+    /// Retry opening a directory up to `attempts` more times when it fails
+    /// with a transient error.
+    ///
+    /// Network file systems like NFS and SMB sometimes fail to open a
+    /// directory in a way that succeeds when simply tried again. When this
+    /// is set, such a failure is retried right away, and an error is only
+    /// yielded if every attempt fails. Errors that aren't transient are
+    /// yielded right away, as usual.
+    ///
+    /// The following errors are considered transient:
+    ///
+    /// * On all platforms, errors whose [`io::ErrorKind`] is `TimedOut`,
+    ///   `WouldBlock` or `Interrupted`.
+    /// * On Unix, `EIO`, and `ESTALE` on Linux, Android, macOS, iOS and the
+    ///   BSDs. On Linux and Android, the value of `ESTALE` differs between
+    ///   architectures, and the one for the target architecture is used.
+    /// * On Windows, `ERROR_UNEXP_NET_ERR`, `ERROR_NETNAME_DELETED` and
+    ///   `ERROR_SEM_TIMEOUT`.
+    ///
+    /// Only opening a directory is retried. Once a directory is open, an
+    /// error while reading its entries ends its listing, since the standard
+    /// library doesn't support continuing after one.
+    ///
+    /// This is `0` (no retries) by default.
+    ///
+    /// [`io::ErrorKind`]: https://doc.rust-lang.org/stable/std/io/enum.ErrorKind.html
+    pub fn retry_transient(mut self, attempts: usize) -> Self {
+        self.opts.retry_transient = attempts;
+        self
+    }
+
+    /// Yield an error in place of every entry whose file name isn't valid
+    /// Unicode.
+    ///
+    /// This is useful for programs that need every path to be a string,
+    /// e.g., to serialize it, and that would rather report such names than
+    /// silently mangle them. The error's [`non_unicode_name`] is the name
+    /// as it was found and its [`path`] is the entry's path.
+    ///
+    /// The walk continues after such an error. A directory with such a name
+    /// is still descended into, unless [`prune_non_unicode`] is enabled.
+    ///
+    /// This applies to the root as well. On Windows, a name isn't valid
+    /// Unicode if it contains an unpaired surrogate.
+    ///
+    /// This is disabled by default.
+    ///
+    /// [`non_unicode_name`]: struct.Error.html#method.non_unicode_name
+    /// [`path`]: struct.Error.html#method.path
+    /// [`prune_non_unicode`]: struct.WalkDir.html#method.prune_non_unicode
+    pub fn require_unicode(mut self, yes: bool) -> Self {
+        self.opts.require_unicode = yes;
+        self
+    }
+
+    /// Don't descend into directories whose file names aren't valid Unicode.
+    ///
+    /// The directory itself is still yielded, or reported as an error when
+    /// [`require_unicode`] is enabled. This never applies to the root.
+    ///
+    /// This is disabled by default.
+    ///
+    /// [`require_unicode`]: struct.WalkDir.html#method.require_unicode
+    pub fn prune_non_unicode(mut self, yes: bool) -> Self {
+        self.opts.prune_non_unicode = yes;
+        self
+    }
+
+    /// Descend into directories that are the same as one of their ancestors.
+    ///
+    /// Without following symbolic links, the only way for a directory tree
+    /// to contain itself is through a mount. For example, on Linux, a
+    /// directory can be bind mounted somewhere inside of itself. Since a bind
+    /// mount has the same device number as its source, [`same_file_system`]
+    /// doesn't prevent descending into it, and since it isn't a symbolic
+    /// link, the loop detection done for [`follow_links`] doesn't apply
+    /// either. The walk then never terminates.
+    ///
+    /// When this option is disabled, every directory is compared with its
+    /// ancestors before it is descended into, in the same way that symbolic
+    /// links are checked when [`follow_links`] is enabled. If a directory is
+    /// the same as one of its ancestors, then an error is yielded for which
+    /// [`Error::loop_ancestor`] returns the ancestor, and the directory is
+    /// not descended into. This costs opening a handle to every directory.
+    ///
+    /// Note that this only detects mounts that lead back to an ancestor. To
+    /// avoid descending into other file systems, use [`same_file_system`].
+    /// A bind mount of the same file system that doesn't create a loop can't
+    /// be told apart from a plain directory, since the standard library
+    /// doesn't expose mount IDs.
+    ///
+    /// This is enabled by default.
+    ///
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`Error::loop_ancestor`]: struct.Error.html#method.loop_ancestor
+    pub fn follow_mounts(mut self, yes: bool) -> Self {
+        self.opts.follow_mounts = yes;
+        self
+    }
+
+    /// Choose what happens when a directory loop is found.
+    ///
+    /// Loops are only looked for when [`follow_links`] is enabled, in which
+    /// case a symbolic link may point to one of its ancestors, or when
+    /// [`follow_mounts`] is disabled, in which case a directory may be
+    /// mounted inside of itself. Either way, the entry that leads back to
+    /// an ancestor is never descended into, so every directory reachable
+    /// from the root is walked exactly once. This only decides whether the
+    /// entry is reported:
+    ///
+    /// * [`LoopPolicy::Error`] yields an error in its place, for which
+    ///   [`Error::loop_ancestor`] returns the ancestor.
+    /// * [`LoopPolicy::SkipSilently`] yields nothing for it.
+    /// * [`LoopPolicy::Report`] yields the entry itself, for which
+    ///   [`DirEntry::loop_target`] returns the ancestor. This is useful for
+    ///   trees that contain loops on purpose, e.g., site mirrors, to list
+    ///   everything once and still find out about the loops.
+    ///
+    /// Errors that occur while checking for a loop, e.g., a broken link, are
+    /// still yielded regardless.
+    ///
+    /// This is [`LoopPolicy::Error`] by default.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`follow_mounts`]: struct.WalkDir.html#method.follow_mounts
+    /// [`LoopPolicy::Error`]: enum.LoopPolicy.html#variant.Error
+    /// [`LoopPolicy::SkipSilently`]: enum.LoopPolicy.html#variant.SkipSilently
+    /// [`LoopPolicy::Report`]: enum.LoopPolicy.html#variant.Report
+    /// [`Error::loop_ancestor`]: struct.Error.html#method.loop_ancestor
+    /// [`DirEntry::loop_target`]: struct.DirEntry.html#method.loop_target
+    pub fn on_loop(mut self, policy: LoopPolicy) -> Self {
+        self.opts.on_loop = policy;
+        self
+    }
+}
+
+impl IntoIterator for WalkDir {
+    type Item = Result<DirEntry>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        let walk_roots = (self.roots.clone(), self.root_dent.clone());
+        let mut roots = self.roots.into_iter();
+        IntoIter {
+            opts: self.opts,
+            start: roots.next(),
+            start_dent: self.root_dent,
+            roots,
+            walk_roots,
+            stack_list: vec![],
+            stack_path: vec![],
+            stack_pos: vec![],
+            stack_limit: vec![],
+            stack_close: vec![],
+            stack_errors: vec![],
+            errors_due: VecDeque::new(),
+            closed: vec![],
+            oldest_opened: 0,
+            depth: 0,
+            deferred_dirs: vec![],
+            root_device: None,
+            root_entry: None,
+            file_systems: vec![],
+            hard_links: HashSet::new(),
+            deadline: None,
+            stats: WalkStats::default(),
+            pending_error: None,
+            progress: Progress::default(),
+            progress_reported: None,
+            poisoned: false,
+            rest: None,
+            rest_checkpoint: None,
+            yielded_depth: None,
+            yielded_leaf_dir: false,
+            eager_root: false,
+            pending_entry: None,
+            #[cfg(feature = "ignore-files")]
+            stack_ignore: vec![],
+        }
+    }
+}
+
+/// Iterating over a borrowed `WalkDir` runs the traversal it describes
+/// without consuming it, so the same `WalkDir` can be walked any number of
+/// times.
+///
+/// ```no_run
+/// use walkdir::WalkDir;
+///
+/// let walker = WalkDir::new("foo").sort_by_file_name();
+/// for entry in &walker {
+///     println!("dry run: {}", entry.unwrap().path().display());
+/// }
+/// for entry in &walker {
+///     // ...
+/// }
+/// ```
+impl IntoIterator for &WalkDir {
+    type Item = Result<DirEntry>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        WalkDir {
+            opts: self.opts.clone(),
+            roots: self.roots.clone(),
+            root_dent: self.root_dent.clone(),
+        }
+        .into_iter()
+    }
+}
+
+/// An iterator for recursively descending into a directory.
+///
+/// A value with this type must be constructed with the [`WalkDir`] type, which
+/// uses a builder pattern to set options such as min/max depth, max open file
+/// descriptors and whether the iterator should follow symbolic links. After
+/// constructing a `WalkDir`, call [`.into_iter()`] at the end of the chain.
+///
+/// The order of elements yielded by this iterator is unspecified.
+///
+/// [`WalkDir`]: struct.WalkDir.html
+/// [`.into_iter()`]: struct.WalkDir.html#into_iter.v
+#[derive(Debug)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct IntoIter {
+    /// Options specified in the builder. Depths, max fds, etc.
+    opts: WalkDirOptions,
+    /// The start path.
+    ///
+    /// This is only `Some(...)` at the beginning. After the first iteration,
+    /// this is always `None`.
+    start: Option<PathBuf>,
+    /// The entry for the start path, if it was already known.
+    ///
+    /// When this is `Some(...)`, then the first call to `next` uses it
+    /// instead of looking up the start path.
+    start_dent: Option<DirEntry>,
+    /// The roots that are walked once the start path has been walked.
+    roots: vec::IntoIter<PathBuf>,
+    /// Every root of the walk, along with the known entry for the first one.
+    ///
+    /// These are kept so that `reset` can start the walk over.
+    walk_roots: (Vec<PathBuf>, Option<DirEntry>),
+    /// A stack of open (up to max fd) or closed handles to directories.
+    /// An open handle is a plain [`fs::ReadDir`] while a closed handle is
+    /// a `Vec<fs::DirEntry>` corresponding to the as-of-yet consumed entries.
+    ///
+    /// [`fs::ReadDir`]: https://doc.rust-lang.org/stable/std/fs/struct.ReadDir.html
+    stack_list: Vec<DirList>,
+    /// A stack of file paths.
+    ///
+    /// This is *only* used when [`follow_links`] is enabled or
+    /// [`follow_mounts`] is disabled. In all other cases this stack is empty.
+    ///
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [`follow_mounts`]: struct.WalkDir.html#method.follow_mounts
+    stack_path: Vec<Ancestor>,
+    /// The path of each directory in `stack_list` along with the number of
+    /// entries that have been taken from it so far.
+    ///
+    /// This is what `checkpoint` records.
+    stack_pos: Vec<(PathBuf, u64)>,
+    /// Depth limits set by `limit_descent`, as the length of `stack_list`
+    /// when each was set along with the limit itself.
+    ///
+    /// Limits only ever get tighter going up this stack, so the last one is
+    /// the one in effect.
+    stack_limit: Vec<(usize, usize)>,
+    /// The entry to yield when each directory in `stack_list` is left.
+    ///
+    /// This is only used when `emit_dir_close` is enabled, in which case it
+    /// mirrors `stack_list`. It's `None` for directories that weren't
+    /// yielded.
+    stack_close: Vec<Option<DirEntry>>,
+    /// The errors held back for each directory in `stack_list`, to yield
+    /// once it's left.
+    ///
+    /// This is only used when `errors_last` is enabled, in which case it
+    /// mirrors `stack_list`.
+    stack_errors: Vec<Vec<Error>>,
+    /// Errors held back for directories that were left, in the order they
+    /// occurred, which are yielded before anything else.
+    errors_due: VecDeque<Error>,
+    /// Entries marking directories that were left, in the order they were
+    /// left, which are yielded before anything else.
+    closed: Vec<DirEntry>,
     /// An index into `stack_list` that points to the oldest open directory
     /// handle. If the maximum fd limit is reached and a new directory needs to
     /// be read, the handle at this index is closed before the new directory is
@@ -599,10 +2079,73 @@ pub struct IntoIter {
     /// The device of the root file path when the first call to `next` was
     /// made.
     ///
-    /// If the `same_file_system` option isn't enabled, then this is always
-    /// `None`. Conversely, if it is enabled, this is always `Some(...)` after
-    /// handling the root path.
+    /// If neither the `same_file_system` nor the `track_file_systems` option
+    /// is enabled, then this is always `None`. Conversely, if one is, this is
+    /// always `Some(...)` after handling the root path.
     root_device: Option<u64>,
+    /// The distinct devices descended into so far, in the order they were
+    /// first seen.
+    ///
+    /// This is only used when the `track_file_systems` option is enabled.
+    file_systems: Vec<u64>,
+    /// The entry for the root currently being walked, exactly as it was
+    /// (or would have been) yielded.
+    root_entry: Option<DirEntry>,
+    /// The identities of files with more than one hard link that have been
+    /// yielded so far.
+    ///
+    /// This is only used when the `dedup_hard_links` option is enabled.
+    hard_links: HashSet<(u64, u64)>,
+    /// The deadline given to `next_deadline`, if it is being called.
+    deadline: Option<Instant>,
+    /// Statistics collected by the iterator itself.
+    stats: WalkStats,
+    /// An error to yield before anything else.
+    ///
+    /// This is only set when a directory couldn't be re-opened while
+    /// resuming from a checkpoint.
+    pending_error: Option<Error>,
+    /// The progress reported to the `on_progress` callback.
+    ///
+    /// This is only updated when a callback is set.
+    progress: Progress,
+    /// The last progress reported, if any.
+    progress_reported: Option<Progress>,
+    /// The rest of the walk, once it has been read into memory by
+    /// `next_back`.
+    rest: Option<VecDeque<Result<DirEntry>>>,
+    /// The position of the walk right before `rest` was read.
+    ///
+    /// This is what `checkpoint` returns while `rest` is set, since nothing
+    /// in `rest` is guaranteed to have been consumed.
+    rest_checkpoint: Option<Checkpoint>,
+    /// Whether the sorter panicked.
+    ///
+    /// This is set while the sorter runs, so that if it panics and the panic
+    /// is caught, the walk ends instead of continuing from a directory that
+    /// was only partly set up.
+    poisoned: bool,
+    /// The depth of the last item yielded by `next`, if it was an entry.
+    yielded_depth: Option<usize>,
+    /// Whether the last item yielded by `next` was a directory that wasn't
+    /// descended into, e.g., because of `max_depth`.
+    ///
+    /// `skip_current_dir` does nothing then, since the directory on top of
+    /// the stack is its parent rather than the directory itself.
+    yielded_leaf_dir: bool,
+    /// Whether the root is being set up by `try_into_iter`, in which case
+    /// failing to open it is returned from `push` instead of being yielded.
+    eager_root: bool,
+    /// An entry to yield before anything else.
+    ///
+    /// This is only set to the root by `try_into_iter`.
+    pending_entry: Option<DirEntry>,
+    /// A stack of ignore rules read from each open directory.
+    ///
+    /// This is *only* used when `respect_simple_ignore` is set, in which case
+    /// it mirrors `stack_list`.
+    #[cfg(feature = "ignore-files")]
+    stack_ignore: Vec<Option<ignore::IgnoreRules>>,
 }
 
 /// An ancestor is an item in the directory tree traversed by walkdir, and is
@@ -661,7 +2204,12 @@ impl Ancestor {
 enum DirList {
     /// An opened handle.
     ///
-    /// This includes the depth of the handle itself.
+    /// This includes the depth and device (if known) of the handle itself.
+    /// Entries read from this handle inherit its device.
+    /// When `skip_unreadable` is set, entries whose type can't be determined
+    /// because they vanished or can't be accessed are skipped. When
+    /// `stop_on_error` is set, an error reading the next entry ends the
+    /// listing instead of being yielded.
     ///
     /// If there was an error with the initial [`fs::read_dir`] call, then it
     /// is stored here. (We use an [`Option<...>`] to make yielding the error
@@ -669,44 +2217,154 @@ enum DirList {
     ///
     /// [`fs::read_dir`]: https://doc.rust-lang.org/stable/std/fs/fn.read_dir.html
     /// [`Option<...>`]: https://doc.rust-lang.org/stable/std/option/enum.Option.html
-    Opened { depth: usize, it: result::Result<ReadDir, Option<Error>> },
+    Opened {
+        depth: usize,
+        device: Option<u64>,
+        skip_unreadable: bool,
+        stop_on_error: bool,
+        it: result::Result<ReadDir, Option<Error>>,
+    },
     /// A closed handle.
     ///
     /// All remaining directory entries are read into memory.
     Closed(vec::IntoIter<Result<DirEntry>>),
 }
 
-impl Iterator for IntoIter {
-    type Item = Result<DirEntry>;
-    /// Advances the iterator and returns the next value.
-    ///
-    /// # Errors
-    ///
-    /// If the iterator fails to retrieve the next value, this method returns
-    /// an error value. The error will be wrapped in an Option::Some.
-    fn next(&mut self) -> Option<Result<DirEntry>> {
-        if let Some(start) = self.start.take() {
-            if self.opts.same_file_system {
-                let result = util::device_num(&start)
-                    .map_err(|e| Error::from_path(0, start.clone(), e));
-                self.root_device = Some(itry!(result));
+impl Iterator for IntoIter {
+    type Item = Result<DirEntry>;
+    /// Advances the iterator and returns the next value.
+    ///
+    /// # Errors
+    ///
+    /// If the iterator fails to retrieve the next value, this method returns
+    /// an error value. The error will be wrapped in an Option::Some.
+    fn next(&mut self) -> Option<Result<DirEntry>> {
+        let next = match self.rest {
+            Some(ref mut rest) => {
+                self.yielded_depth = None;
+                self.yielded_leaf_dir = false;
+                rest.pop_front()
+            }
+            None => self.walk_next(),
+        };
+        if let Some(ref result) = next {
+            self.stats.add_result(result);
+        }
+        next
+    }
+}
+
+/// Iterating from the back reads the rest of the walk into memory.
+///
+/// The first call to `next_back` walks everything that hasn't been yielded
+/// yet and buffers it, which takes as much memory as a `Vec` of the
+/// remaining entries. Entries are then taken from either end of the buffer,
+/// so reversing a walk yields exactly what walking forward would, in the
+/// opposite order. This is mostly useful with a sorter, e.g. to show the
+/// last few entries of a walk sorted with [`sort_by_file_name`].
+///
+/// Since the rest of the walk has already been read by then, calling
+/// [`skip_current_dir`] or [`limit_descent`] after the first call to
+/// `next_back` has no effect. [`checkpoint`] keeps returning the position
+/// from right before that call, so resuming from it may yield entries that
+/// were already yielded from either end, but never misses any.
+///
+/// ```no_run
+/// use walkdir::WalkDir;
+///
+/// let it = WalkDir::new("foo").sort_by_file_name().into_iter();
+/// for entry in it.rev().take(10) {
+///     println!("{}", entry.unwrap().path().display());
+/// }
+/// ```
+///
+/// [`sort_by_file_name`]: struct.WalkDir.html#method.sort_by_file_name
+/// [`skip_current_dir`]: struct.IntoIter.html#method.skip_current_dir
+/// [`limit_descent`]: struct.IntoIter.html#method.limit_descent
+/// [`checkpoint`]: struct.IntoIter.html#method.checkpoint
+impl DoubleEndedIterator for IntoIter {
+    fn next_back(&mut self) -> Option<Result<DirEntry>> {
+        if self.rest.is_none() {
+            self.rest_checkpoint = Some(self.checkpoint());
+            // Buffered entries are only counted once they're yielded.
+            let rest = std::iter::from_fn(|| self.walk_next()).collect();
+            self.rest = Some(rest);
+        }
+        let next = self.rest.as_mut().and_then(|rest| rest.pop_back());
+        if let Some(ref result) = next {
+            self.stats.add_result(result);
+        }
+        next
+    }
+}
+
+impl IntoIter {
+    fn walk_next(&mut self) -> Option<Result<DirEntry>> {
+        let next = self.advance();
+        self.yielded_depth = match next {
+            Some(Ok(ref dent)) => Some(dent.depth()),
+            _ => None,
+        };
+        // A directory that was descended into is on top of the stack, right
+        // above its parent. With `contents_first`, directories are yielded
+        // after they're left, and skipping skips their parent instead.
+        self.yielded_leaf_dir = match next {
+            Some(Ok(ref dent)) => {
+                !self.opts.contents_first
+                    && dent.is_dir()
+                    && !dent.is_dir_close()
+                    && self.stack_list.len() <= dent.depth()
             }
-            let dent = itry!(DirEntry::from_path(0, start, false));
-            if let Some(result) = self.handle_entry(dent) {
+            _ => false,
+        };
+        if self.opts.progress.is_some() {
+            self.report_progress(&next);
+        }
+        next
+    }
+
+    fn advance(&mut self) -> Option<Result<DirEntry>> {
+        if self.poisoned {
+            return None;
+        }
+        if self.start.is_some() && self.is_past_deadline() {
+            return Some(Err(Error::from_timeout(0)));
+        }
+        if let Some(dent) = self.pending_entry.take() {
+            return Some(Ok(dent));
+        }
+        if let Some(err) = self.pending_error.take() {
+            return Some(Err(err));
+        }
+        if let Some(start) = self.start.take() {
+            if let Some(result) = self.start_root(start) {
                 return Some(result);
             }
         }
         while !self.stack_list.is_empty() {
+            if let Some(err) = self.errors_due.pop_front() {
+                return Some(Err(err));
+            }
+            if let Some(dent) = self.take_closed() {
+                return Some(Ok(dent));
+            }
             self.depth = self.stack_list.len();
+            if self.is_past_deadline() {
+                return Some(Err(Error::from_timeout(self.depth)));
+            }
             if let Some(dentry) = self.get_deferred_dir() {
-                return Some(Ok(dentry));
+                return Some(self.check_name(dentry));
             }
-            if self.depth > self.opts.max_depth {
+            if self.depth > self.max_depth() {
                 // If we've exceeded the max depth, pop the current dir
                 // so that we don't descend.
                 self.pop();
                 continue;
             }
+            if self.is_dir_limit_reached() {
+                self.pop();
+                continue;
+            }
             // Unwrap is safe here because we've verified above that
             // `self.stack_list` is not empty
             let next = self
@@ -714,6 +2372,12 @@ impl Iterator for IntoIter {
                 .last_mut()
                 .expect("BUG: stack should be non-empty")
                 .next();
+            if next.is_some() {
+                self.stack_pos
+                    .last_mut()
+                    .expect("BUG: list/position stacks out of sync")
+                    .1 += 1;
+            }
             match next {
                 None => self.pop(),
                 Some(Err(err)) => return Some(Err(err)),
@@ -724,134 +2388,806 @@ impl Iterator for IntoIter {
                 }
             }
         }
+        if let Some(err) = self.errors_due.pop_front() {
+            return Some(Err(err));
+        }
+        if let Some(dent) = self.take_closed() {
+            return Some(Ok(dent));
+        }
         if self.opts.contents_first {
             self.depth = self.stack_list.len();
             if let Some(dentry) = self.get_deferred_dir() {
-                return Some(Ok(dentry));
+                return Some(self.check_name(dentry));
             }
         }
+        if let Some(root) = self.roots.next() {
+            self.start = Some(root);
+            return self.advance();
+        }
         None
     }
-}
+}
+
+impl IntoIter {
+    /// Skips the current directory.
+    ///
+    /// This causes the iterator to stop traversing the contents of the least
+    /// recently yielded directory. This means any remaining entries in that
+    /// directory will be skipped (including sub-directories). If that
+    /// directory isn't descended into anyway, e.g., because it's at the
+    /// [`max_depth`], then this does nothing.
+    ///
+    /// When [`contents_first`] is enabled, the directory whose contents are
+    /// being yielded is the parent of the last entry yielded, even if that
+    /// entry is itself a directory, since a directory is only yielded after
+    /// it has been left. Skipping it discards its unread entries and any of
+    /// its sub-directories that haven't been descended into yet, but the
+    /// directory itself and all of its ancestors are still yielded after
+    /// their contents, as usual.
+    ///
+    /// Note that the ergonomics of this method are questionable since it
+    /// borrows the iterator mutably. Namely, you must write out the looping
+    /// condition manually. For example, to skip hidden entries efficiently on
+    /// unix systems:
+    ///
+    /// ```no_run
+    /// use walkdir::{DirEntry, WalkDir};
+    ///
+    /// fn is_hidden(entry: &DirEntry) -> bool {
+    ///     entry.file_name()
+    ///          .to_str()
+    ///          .map(|s| s.starts_with("."))
+    ///          .unwrap_or(false)
+    /// }
+    ///
+    /// let mut it = WalkDir::new("foo").into_iter();
+    /// loop {
+    ///     let entry = match it.next() {
+    ///         None => break,
+    ///         Some(Err(err)) => panic!("ERROR: {}", err),
+    ///         Some(Ok(entry)) => entry,
+    ///     };
+    ///     if is_hidden(&entry) {
+    ///         if entry.file_type().is_dir() {
+    ///             it.skip_current_dir();
+    ///         }
+    ///         continue;
+    ///     }
+    ///     println!("{}", entry.path().display());
+    /// }
+    /// ```
+    ///
+    /// You may find it more convenient to use the [`filter_entry`] iterator
+    /// adapter. (See its documentation for the same example functionality as
+    /// above.)
+    ///
+    /// [`filter_entry`]: #method.filter_entry
+    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
+    /// [`contents_first`]: struct.WalkDir.html#method.contents_first
+    pub fn skip_current_dir(&mut self) {
+        // A directory that wasn't descended into has nothing to skip.
+        if !self.stack_list.is_empty() && !self.yielded_leaf_dir {
+            self.pop();
+        }
+    }
+
+    /// Limits how much deeper the iterator descends into the current
+    /// directory.
+    ///
+    /// This is like [`skip_current_dir`], except that it only prunes the
+    /// entries of the least recently yielded directory that are more than
+    /// `additional_depth` levels below it. Its own entries are always yielded,
+    /// so an `additional_depth` of `0` skips everything beneath it while an
+    /// `additional_depth` of `1` yields its entries but doesn't descend into
+    /// its sub-directories. The rest of the tree isn't affected.
+    ///
+    /// Limits set for nested directories stack, so that a directory's
+    /// descendants are pruned by the tightest limit of any of its ancestors
+    /// as well as by [`max_depth`].
+    ///
+    /// For example, to walk everything except what's deeper than one level
+    /// below any directory named `snapshots`:
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let mut it = WalkDir::new("foo").into_iter();
+    /// while let Some(result) = it.next() {
+    ///     let entry = result.unwrap();
+    ///     if entry.file_type().is_dir() && entry.file_name() == "snapshots" {
+    ///         it.limit_descent(1);
+    ///     }
+    ///     println!("{}", entry.path().display());
+    /// }
+    /// ```
+    ///
+    /// [`skip_current_dir`]: #method.skip_current_dir
+    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
+    pub fn limit_descent(&mut self, additional_depth: usize) {
+        let len = self.stack_list.len();
+        // Nothing below a directory that wasn't descended into is yielded
+        // anyway.
+        if len == 0 || self.yielded_leaf_dir {
+            return;
+        }
+        let limit = (len - 1).saturating_add(additional_depth);
+        if limit >= self.max_depth() {
+            return;
+        }
+        if let Some(&(last, _)) = self.stack_limit.last() {
+            if last == len {
+                self.stack_limit.pop();
+            }
+        }
+        self.stack_limit.push((len, limit));
+    }
+
+    /// Changes the maximum depth of the entries that are still to be yielded.
+    ///
+    /// This works like [`max_depth`], except that it can be called during
+    /// iteration. Lowering the maximum depth stops descending into
+    /// directories deeper than it, and entries of directories that are
+    /// already open are skipped if they're too deep. Raising it allows
+    /// descending into directories that are yielded from now on.
+    ///
+    /// A directory at the maximum depth is yielded without being opened, so
+    /// no handle to it is kept. Raising the maximum depth therefore only
+    /// affects directories that are yielded after the call, and those whose
+    /// handles are still open. Directories at the old maximum depth that
+    /// were already yielded aren't revisited. To deepen one of those, walk
+    /// it again.
+    ///
+    /// Like [`max_depth`], this never sets the maximum depth below the
+    /// minimum depth.
+    ///
+    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.opts.max_depth = max(depth, self.opts.min_depth);
+    }
+
+    /// Yields only entries which satisfy the given predicate and skips
+    /// descending into directories that do not satisfy the given predicate.
+    ///
+    /// The predicate is applied to all entries. If the predicate is
+    /// true, iteration carries on as normal. If the predicate is false, the
+    /// entry is ignored and if it is a directory, it is not descended into.
+    ///
+    /// This is often more convenient to use than [`skip_current_dir`]. For
+    /// example, to skip hidden files and directories efficiently on unix
+    /// systems:
+    ///
+    /// ```no_run
+    /// use walkdir::{DirEntry, WalkDir};
+    /// # use walkdir::Error;
+    ///
+    /// fn is_hidden(entry: &DirEntry) -> bool {
+    ///     entry.file_name()
+    ///          .to_str()
+    ///          .map(|s| s.starts_with("."))
+    ///          .unwrap_or(false)
+    /// }
+    ///
+    /// # fn try_main() -> Result<(), Error> {
+    /// for entry in WalkDir::new("foo")
+    ///                      .into_iter()
+    ///                      .filter_entry(|e| !is_hidden(e)) {
+    ///     println!("{}", entry?.path().display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Note that the iterator will still yield errors for reading entries that
+    /// may not satisfy the predicate.
+    ///
+    /// Note that entries skipped with [`min_depth`] and [`max_depth`] are not
+    /// passed to this predicate.
+    ///
+    /// Note that if the iterator has `contents_first` enabled, then this
+    /// method is no different than calling the standard `Iterator::filter`
+    /// method (because directory entries are yielded after they've been
+    /// descended into).
+    ///
+    /// [`skip_current_dir`]: #method.skip_current_dir
+    /// [`min_depth`]: struct.WalkDir.html#method.min_depth
+    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
+    pub fn filter_entry<P>(self, predicate: P) -> FilterEntry<Self, P>
+    where
+        P: FnMut(&DirEntry) -> bool,
+    {
+        FilterEntry { it: self, predicate }
+    }
+
+    /// Advances the iterator, unless the given deadline has passed.
+    ///
+    /// This behaves like [`next`], except that the deadline is checked
+    /// before each step of traversal: before reading the next entry of a
+    /// directory and before handling the root. If the deadline has passed
+    /// at any of those points, then an error is returned for which
+    /// [`Error::is_timeout`] is true. No entries are lost when this happens,
+    /// and iteration can be resumed with another call to `next_deadline`
+    /// or `next`. Note that this means a deadline that has already passed
+    /// never makes any progress.
+    ///
+    /// A deadline cannot interrupt a single operation that is in progress.
+    /// In particular, a blocking call to read a directory entry, open a
+    /// directory or stat a file (for example, on a network file system that
+    /// has stopped responding) may still make this method return well after
+    /// the deadline. The deadline only guarantees that no *new* operation is
+    /// started once it has passed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use walkdir::WalkDir;
+    ///
+    /// let mut it = WalkDir::new("foo").into_iter();
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// while let Some(result) = it.next_deadline(deadline) {
+    ///     match result {
+    ///         Ok(entry) => println!("{}", entry.path().display()),
+    ///         // Out of time. Resume with `it` later.
+    ///         Err(err) if err.is_timeout() => break,
+    ///         Err(err) => eprintln!("ERROR: {}", err),
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`next`]: #method.next
+    /// [`Error::is_timeout`]: struct.Error.html#method.is_timeout
+    pub fn next_deadline(
+        &mut self,
+        deadline: Instant,
+    ) -> Option<Result<DirEntry>> {
+        self.deadline = Some(deadline);
+        let next = self.next();
+        self.deadline = None;
+        next
+    }
+
+    /// Collects all entries, stopping at the first error.
+    ///
+    /// This is a convenience for `self.collect::<Result<Vec<_>>>()`. If an
+    /// error is encountered, then it is returned and the rest of the
+    /// directory tree is not traversed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// # fn try_main() -> Result<(), walkdir::Error> {
+    /// let entries = WalkDir::new("foo").into_iter().collect_entries()?;
+    /// println!("found {} entries", entries.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn collect_entries(self) -> Result<Vec<DirEntry>> {
+        self.collect()
+    }
+
+    /// Traverses the entire directory tree, collecting entries and errors
+    /// separately.
+    ///
+    /// Entries and errors are each kept in the order in which they were
+    /// yielded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let (entries, errors) = WalkDir::new("foo").into_iter().partition_results();
+    /// for err in &errors {
+    ///     eprintln!("ERROR: {}", err);
+    /// }
+    /// println!("found {} entries", entries.len());
+    /// ```
+    pub fn partition_results(self) -> (Vec<DirEntry>, Vec<Error>) {
+        let (mut ents, mut errs) = (vec![], vec![]);
+        for result in self {
+            match result {
+                Ok(ent) => ents.push(ent),
+                Err(err) => errs.push(err),
+            }
+        }
+        (ents, errs)
+    }
+
+    /// Returns the statistics collected by this iterator so far.
+    ///
+    /// Entries and errors are counted as they're yielded, so this doesn't
+    /// include anything that the iterator has read ahead but not yielded
+    /// yet, e.g., after a call to `next_back`.
+    pub fn stats(&self) -> &WalkStats {
+        &self.stats
+    }
+
+    /// Returns the distinct devices that the traversal has descended into so
+    /// far, in the order they were first seen.
+    ///
+    /// This is always empty unless [`track_file_systems`] is enabled. The
+    /// device of the root comes first. Other devices are added when a
+    /// directory on them is descended into, so with [`same_file_system`]
+    /// also enabled, the devices of directories that it skipped aren't
+    /// included.
+    ///
+    /// [`track_file_systems`]: struct.WalkDir.html#method.track_file_systems
+    /// [`same_file_system`]: struct.WalkDir.html#method.same_file_system
+    pub fn file_systems_seen(&self) -> &[u64] {
+        &self.file_systems
+    }
+
+    /// Returns the paths of the ancestors of the entry that was yielded
+    /// last, starting with the root.
+    ///
+    /// These are the directories that are open on the way to the entry, so
+    /// the paths aren't derived by splitting the entry's path. For the root
+    /// itself, nothing is returned.
+    ///
+    /// This only reflects the item yielded by the most recent call to
+    /// `next`. If that was an error, or if iteration hasn't started yet or
+    /// has switched to [`next_back`], nothing is returned.
+    ///
+    /// [`next_back`]: struct.IntoIter.html#impl-DoubleEndedIterator
+    pub fn ancestors(&self) -> impl Iterator<Item = &Path> + '_ {
+        let depth = min(self.yielded_depth.unwrap_or(0), self.stack_pos.len());
+        self.stack_pos[..depth].iter().map(|(path, _)| path.as_path())
+    }
+
+    /// Returns whether the entry that was yielded last is the last entry
+    /// read from its directory, if that is known.
+    ///
+    /// This is known when the rest of the directory has already been read
+    /// into memory, which is always the case when a sorter is set with
+    /// [`sort_by`] or one of its variants. Otherwise, and for the root, this
+    /// returns `None`. It also returns `None` under the same conditions as
+    /// [`ancestors`] returns nothing.
+    ///
+    /// An entry that is read but not yielded, e.g., because it's a
+    /// duplicate hard link or because its type couldn't be determined with
+    /// [`skip_unreadable`] enabled, still counts. So with such options, this
+    /// may return `Some(false)` for the last entry that is yielded from a
+    /// directory. Errors count as well.
+    ///
+    /// This makes it possible to draw a tree:
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let mut it = WalkDir::new("foo").sort_by_file_name().into_iter();
+    /// // Whether the last entry at each depth was the last in its directory.
+    /// let mut last = vec![];
+    /// while let Some(result) = it.next() {
+    ///     let entry = result.unwrap();
+    ///     if entry.depth() == 0 {
+    ///         println!("{}", entry.path().display());
+    ///         continue;
+    ///     }
+    ///     last.resize(entry.depth() - 1, false);
+    ///     for &done in &last {
+    ///         print!("{}", if done { "    " } else { "\u{2502}   " });
+    ///     }
+    ///     let is_last = it.is_last_in_dir() == Some(true);
+    ///     let branch = if is_last { "\u{2514}" } else { "\u{251c}" };
+    ///     let name = entry.file_name().to_string_lossy();
+    ///     println!("{}\u{2500}\u{2500} {}", branch, name);
+    ///     last.push(is_last);
+    /// }
+    /// ```
+    ///
+    /// [`sort_by`]: struct.WalkDir.html#method.sort_by
+    /// [`ancestors`]: #method.ancestors
+    /// [`skip_unreadable`]: struct.WalkDir.html#method.skip_unreadable
+    pub fn is_last_in_dir(&self) -> Option<bool> {
+        let depth = self.yielded_depth?;
+        if depth == 0 {
+            return None;
+        }
+        match self.stack_list.get(depth - 1) {
+            Some(DirList::Closed(it)) => Some(it.as_slice().is_empty()),
+            _ => None,
+        }
+    }
+
+    /// Returns the entry for the root currently being walked.
+    ///
+    /// This is the entry exactly as it is yielded, or would have been
+    /// yielded if [`min_depth`] didn't exclude it. In particular, if the root
+    /// is a symbolic link and [`follow_links`] is enabled, then this is the
+    /// entry for its target. This makes the root's file type and device
+    /// available without looking them up again.
+    ///
+    /// This returns `None` before the first call to `next`, if the root
+    /// couldn't be read and for a walk [resumed] from a checkpoint.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use walkdir::WalkDir;
+    ///
+    /// let mut it = WalkDir::new("foo").min_depth(1).into_iter();
+    /// let first = it.next();
+    /// if let Some(root) = it.root_entry() {
+    ///     println!("walking {:?}", root.file_type());
+    /// }
+    /// ```
+    ///
+    /// [`min_depth`]: struct.WalkDir.html#method.min_depth
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    /// [resumed]: struct.WalkDir.html#method.resume
+    pub fn root_entry(&self) -> Option<&DirEntry> {
+        self.root_entry.as_ref()
+    }
 
-impl IntoIter {
-    /// Skips the current directory.
+    /// Consumes this iterator and returns its statistics, including the
+    /// directories it hadn't finished reading.
     ///
-    /// This causes the iterator to stop traversing the contents of the least
-    /// recently yielded directory. This means any remaining entries in that
-    /// directory will be skipped (including sub-directories).
+    /// This can be called at any point, which makes it useful for reporting
+    /// on a traversal that was abandoned early, e.g., on the first error.
+    /// The counters are the same as those returned by [`stats`], and
+    /// [`WalkStats::pending_dirs`] lists every directory that is still on the
+    /// stack, whether or not its handle is still open.
     ///
-    /// Note that the ergonomics of this method are questionable since it
-    /// borrows the iterator mutably. Namely, you must write out the looping
-    /// condition manually. For example, to skip hidden entries efficiently on
-    /// unix systems:
+    /// # Example
     ///
     /// ```no_run
-    /// use walkdir::{DirEntry, WalkDir};
-    ///
-    /// fn is_hidden(entry: &DirEntry) -> bool {
-    ///     entry.file_name()
-    ///          .to_str()
-    ///          .map(|s| s.starts_with("."))
-    ///          .unwrap_or(false)
-    /// }
+    /// use walkdir::WalkDir;
     ///
     /// let mut it = WalkDir::new("foo").into_iter();
-    /// loop {
-    ///     let entry = match it.next() {
-    ///         None => break,
-    ///         Some(Err(err)) => panic!("ERROR: {}", err),
-    ///         Some(Ok(entry)) => entry,
-    ///     };
-    ///     if is_hidden(&entry) {
-    ///         if entry.file_type().is_dir() {
-    ///             it.skip_current_dir();
-    ///         }
-    ///         continue;
+    /// while let Some(result) = it.next() {
+    ///     if let Err(err) = result {
+    ///         eprintln!("ERROR: {}", err);
+    ///         break;
     ///     }
-    ///     println!("{}", entry.path().display());
+    /// }
+    /// for (path, depth) in it.into_stats().pending_dirs() {
+    ///     eprintln!("not finished (depth {}): {}", depth, path.display());
     /// }
     /// ```
     ///
-    /// You may find it more convenient to use the [`filter_entry`] iterator
-    /// adapter. (See its documentation for the same example functionality as
-    /// above.)
+    /// [`stats`]: struct.IntoIter.html#method.stats
+    /// [`WalkStats::pending_dirs`]: struct.WalkStats.html#method.pending_dirs
+    pub fn into_stats(self) -> WalkStats {
+        let mut stats = self.stats;
+        stats.set_pending_dirs(
+            self.stack_pos
+                .into_iter()
+                .enumerate()
+                .map(|(depth, (path, _))| (path, depth))
+                .collect(),
+        );
+        stats
+    }
+
+    /// Returns the current position of this iterator.
     ///
-    /// [`filter_entry`]: #method.filter_entry
-    pub fn skip_current_dir(&mut self) {
-        if !self.stack_list.is_empty() {
-            self.pop();
+    /// The traversal can be continued from this position later, even from
+    /// another process, with [`WalkDir::resume`]. Everything yielded before
+    /// this call is not yielded again, unless the walk was read from the
+    /// back with [`next_back`].
+    ///
+    /// [`WalkDir::resume`]: struct.WalkDir.html#method.resume
+    /// [`next_back`]: struct.IntoIter.html#method.next_back
+    pub fn checkpoint(&self) -> Checkpoint {
+        if let Some(ref checkpoint) = self.rest_checkpoint {
+            return checkpoint.clone();
         }
+        if self.start.is_some() {
+            return Checkpoint::default();
+        }
+        Checkpoint::new(self.stack_pos.clone())
     }
 
-    /// Yields only entries which satisfy the given predicate and skips
-    /// descending into directories that do not satisfy the given predicate.
+    /// Restarts this walk from its root, as if it were newly created by the
+    /// same [`WalkDir`].
     ///
-    /// The predicate is applied to all entries. If the predicate is
-    /// true, iteration carries on as normal. If the predicate is false, the
-    /// entry is ignored and if it is a directory, it is not descended into.
+    /// Every open directory handle is closed, and the statistics and progress
+    /// of the walk are cleared. Any buffers used by the walk are kept, so
+    /// that walking again doesn't need to allocate them again.
     ///
-    /// This is often more convenient to use than [`skip_current_dir`]. For
-    /// example, to skip hidden files and directories efficiently on unix
-    /// systems:
+    /// This also restarts a walk that was [resumed] from a checkpoint, and it
+    /// restarts it from its root rather than from the checkpoint.
     ///
-    /// ```no_run
-    /// use walkdir::{DirEntry, WalkDir};
-    /// # use walkdir::Error;
+    /// # Example
     ///
-    /// fn is_hidden(entry: &DirEntry) -> bool {
-    ///     entry.file_name()
-    ///          .to_str()
-    ///          .map(|s| s.starts_with("."))
-    ///          .unwrap_or(false)
-    /// }
+    /// ```no_run
+    /// use walkdir::WalkDir;
     ///
-    /// # fn try_main() -> Result<(), Error> {
-    /// for entry in WalkDir::new("foo")
-    ///                      .into_iter()
-    ///                      .filter_entry(|e| !is_hidden(e)) {
-    ///     println!("{}", entry?.path().display());
+    /// let mut it = WalkDir::new("foo").into_iter();
+    /// if it.by_ref().any(|result| result.is_err()) {
+    ///     // Try once more.
+    ///     it.reset();
+    ///     for entry in it {
+    ///         println!("{}", entry.unwrap().path().display());
+    ///     }
     /// }
-    /// # Ok(())
-    /// # }
     /// ```
     ///
-    /// Note that the iterator will still yield errors for reading entries that
-    /// may not satisfy the predicate.
-    ///
-    /// Note that entries skipped with [`min_depth`] and [`max_depth`] are not
-    /// passed to this predicate.
-    ///
-    /// Note that if the iterator has `contents_first` enabled, then this
-    /// method is no different than calling the standard `Iterator::filter`
-    /// method (because directory entries are yielded after they've been
-    /// descended into).
-    ///
-    /// [`skip_current_dir`]: #method.skip_current_dir
-    /// [`min_depth`]: struct.WalkDir.html#method.min_depth
-    /// [`max_depth`]: struct.WalkDir.html#method.max_depth
-    pub fn filter_entry<P>(self, predicate: P) -> FilterEntry<Self, P>
-    where
-        P: FnMut(&DirEntry) -> bool,
-    {
-        FilterEntry { it: self, predicate }
+    /// [`WalkDir`]: struct.WalkDir.html
+    /// [resumed]: struct.WalkDir.html#method.resume
+    pub fn reset(&mut self) {
+        let (ref roots, ref root_dent) = self.walk_roots;
+        let mut rest = roots.clone().into_iter();
+        self.start = rest.next();
+        self.start_dent = root_dent.clone();
+        self.roots = rest;
+        self.stack_list.clear();
+        self.stack_path.clear();
+        self.stack_pos.clear();
+        self.stack_limit.clear();
+        self.stack_close.clear();
+        self.stack_errors.clear();
+        self.errors_due.clear();
+        self.closed.clear();
+        self.oldest_opened = 0;
+        self.depth = 0;
+        self.deferred_dirs.clear();
+        self.root_device = None;
+        self.root_entry = None;
+        self.file_systems.clear();
+        self.hard_links.clear();
+        self.stats = WalkStats::default();
+        self.pending_error = None;
+        self.progress = Progress::default();
+        self.progress_reported = None;
+        self.rest = None;
+        self.rest_checkpoint = None;
+        self.poisoned = false;
+        self.yielded_depth = None;
+        self.yielded_leaf_dir = false;
+        self.pending_entry = None;
+        #[cfg(feature = "ignore-files")]
+        self.stack_ignore.clear();
+    }
+
+    /// Re-opens the directories of a checkpoint and skips the entries that
+    /// were already read from them.
+    fn restore(&mut self, dirs: Vec<(PathBuf, u64)>) {
+        if let Err(err) = self.opts.check() {
+            self.pending_error = Some(err);
+            return;
+        }
+        for (depth, (path, consumed)) in dirs.into_iter().enumerate() {
+            self.depth = depth;
+            let follow = depth > 0 && self.opts.follow_links;
+            let dent = match self.restore_dent(depth, path, follow) {
+                Ok(dent) => dent,
+                Err(err) => {
+                    self.pending_error = Some(err);
+                    return;
+                }
+            };
+            if let Err(err) = self.push(&dent) {
+                self.pending_error = Some(err);
+                return;
+            }
+            if self.pending_error.is_some() {
+                // The directory was too deep to descend into.
+                return;
+            }
+            let list = self.stack_list.last_mut().unwrap();
+            for _ in 0..consumed {
+                if list.next().is_none() {
+                    break;
+                }
+            }
+            self.stack_pos.last_mut().unwrap().1 = consumed;
+            if self.opts.emit_dir_close
+                && !self.opts.contents_first
+                && !self.skippable()
+            {
+                self.set_dir_close(&dent, true);
+            }
+            // A symbolic link root is yielded right away, so only a real
+            // directory still has to be yielded after its contents.
+            if self.opts.contents_first && !dent.file_type().is_symlink() {
+                self.deferred_dirs.push(dent);
+            }
+        }
+    }
+
+    fn restore_dent(
+        &mut self,
+        depth: usize,
+        path: PathBuf,
+        follow: bool,
+    ) -> Result<DirEntry> {
+        let mut dent = DirEntry::from_path(depth, path, follow)?;
+        if self.opts.same_file_system || self.opts.track_file_systems {
+            let device = util::device_num(dent.path())
+                .map_err(|err| Error::from_entry(&dent, err))?;
+            if depth == 0 {
+                self.root_device = Some(device);
+                if self.opts.same_file_system {
+                    self.stats.set_root_device(device);
+                }
+            }
+            self.saw_device(device);
+            dent.set_device(Some(device));
+        }
+        Ok(dent)
+    }
+
+    fn report_progress(&mut self, next: &Option<Result<DirEntry>>) {
+        let (every, callback) = match self.opts.progress {
+            None => return,
+            Some((every, ref callback)) => (every as u64, callback),
+        };
+        let total = |p: &Progress| p.entries_yielded() + p.errors();
+        match *next {
+            Some(ref result) => {
+                self.progress.add_result(result);
+                let reported =
+                    self.progress_reported.as_ref().map_or(0, total);
+                if total(&self.progress) - reported < every {
+                    return;
+                }
+                self.progress.set_dirs_entered(self.stats.dirs_opened());
+            }
+            None => {
+                self.progress.set_dirs_entered(self.stats.dirs_opened());
+                // Report the final counts once, unless they just were.
+                if self.progress_reported == Some(self.progress) {
+                    return;
+                }
+            }
+        }
+        self.progress_reported = Some(self.progress);
+        let mut callback =
+            callback.lock().unwrap_or_else(|err| err.into_inner());
+        callback(self.progress);
+    }
+
+    fn is_dir_limit_reached(&self) -> bool {
+        let limit = match self.opts.max_entries_per_dir {
+            None => return false,
+            Some(limit) => limit as u64,
+        };
+        match self.stack_pos.last() {
+            None => false,
+            Some(&(_, consumed)) => consumed >= limit,
+        }
+    }
+
+    fn is_past_deadline(&self) -> bool {
+        match self.deadline {
+            None => false,
+            Some(deadline) => Instant::now() >= deadline,
+        }
+    }
+
+    /// Yields the entry for the given root, and descends into it if it's a
+    /// directory.
+    fn start_root(&mut self, mut start: PathBuf) -> Option<Result<DirEntry>> {
+        if let Err(err) = self.opts.check() {
+            return Some(Err(err));
+        }
+        if start.as_os_str().is_empty() {
+            return Some(Err(Error::from_empty_root()));
+        }
+        let mut start_dent = self.start_dent.take();
+        if self.opts.normalize_paths {
+            let normal = util::collapse_separators(&start);
+            if normal.as_os_str() != start.as_os_str() {
+                // The path of a seeded entry can't be changed, so just
+                // look it up again.
+                start = normal;
+                start_dent = None;
+            }
+        }
+        let mut dent = match start_dent {
+            Some(dent) => dent,
+            None => itry!(DirEntry::from_path(0, start, false)),
+        };
+        if util::has_trailing_separator(dent.path()) && !dent.is_dir() {
+            // Like the OS, require a root with a trailing separator to
+            // be a directory (or a symlink to one), and let the OS say
+            // why it isn't.
+            if let Err(err) = fs::read_dir(dent.path()) {
+                return Some(Err(Error::from_entry(&dent, err)));
+            }
+        }
+        if self.opts.same_file_system || self.opts.track_file_systems {
+            // The root device is the device of the directory that is
+            // actually descended into. Roots are always followed for
+            // traversal, so if the root is a symlink, that's the device
+            // of its target, regardless of `follow_links`. We only know
+            // the device of a seeded root if it isn't a symlink, and
+            // otherwise look it up with `stat`, which follows links.
+            let device = match dent.device() {
+                Some(device) => device,
+                None => itry!(util::device_num(dent.path())
+                    .map_err(|err| Error::from_entry(&dent, err))),
+            };
+            self.root_device = Some(device);
+            if self.opts.same_file_system {
+                self.stats.set_root_device(device);
+            }
+            self.saw_device(device);
+            dent.set_device(self.root_device);
+        }
+        self.handle_entry(dent)
     }
 
     fn handle_entry(
         &mut self,
         mut dent: DirEntry,
     ) -> Option<Result<DirEntry>> {
+        // The device of the directory containing this entry, if known.
+        let parent_device = dent.device();
+        let stack_len = self.stack_list.len();
         if self.opts.follow_links && dent.file_type().is_symlink() {
-            dent = itry!(self.follow(dent));
+            dent = match self.follow(dent) {
+                Ok(dent) => dent,
+                Err(ref err)
+                    if self.opts.skip_unreadable && err.is_unreadable() =>
+                {
+                    return None;
+                }
+                Err(err) => return Some(Err(err)),
+            };
+            if dent.depth() == 0 {
+                // The root device was already looked up through the link.
+                dent.set_device(self.root_device);
+            }
+        }
+        if dent.depth() == 0 {
+            self.root_entry = Some(dent.clone());
         }
         let is_normal_dir = !dent.file_type().is_symlink() && dent.is_dir();
+        #[cfg(feature = "ignore-files")]
+        {
+            if dent.depth() > 0 && self.is_ignored(&dent, is_normal_dir) {
+                return None;
+            }
+        }
+        if dent.depth() > 0 && dent.is_dir() && self.is_skipped_dir(&dent) {
+            return None;
+        }
+        if is_normal_dir
+            && !self.opts.follow_mounts
+            && !dent.path_is_symlink()
+            && dent.depth() > 0
+        {
+            // Followed symlinks have already been checked.
+            itry!(self.check_dir_loop(&mut dent));
+        }
+        if dent.loop_target().is_some()
+            && self.opts.on_loop == LoopPolicy::SkipSilently
+        {
+            return None;
+        }
+        // A directory at the maximum depth is never opened, since none of
+        // its entries would be yielded anyway.
+        let descend = dent.depth() < self.max_depth()
+            && dent.loop_target().is_none()
+            && !(self.opts.prune_non_unicode
+                && dent.depth() > 0
+                && dent.file_name_str().is_none());
         if is_normal_dir {
             if self.opts.same_file_system && dent.depth() > 0 {
-                if itry!(self.is_same_file_system(&dent)) {
+                if itry!(self.is_same_file_system(&mut dent, parent_device))
+                    && descend
+                {
+                    // Only `cross_fs_via_symlink` can lead to a new device.
+                    self.saw_device(dent.device().unwrap());
                     itry!(self.push(&dent));
                 }
             } else {
-                itry!(self.push(&dent));
+                if self.opts.track_file_systems && dent.depth() > 0 {
+                    let device = itry!(util::device_num(dent.path())
+                        .map_err(|err| Error::from_entry(&dent, err)));
+                    dent.set_device(Some(device));
+                    if descend {
+                        self.saw_device(device);
+                    }
+                }
+                if descend {
+                    itry!(self.push(&dent));
+                }
             }
         } else if dent.depth() == 0
             && dent.file_type().is_symlink()
@@ -867,7 +3203,7 @@ impl IntoIter {
             let md = itry!(fs::metadata(dent.path()).map_err(|err| {
                 Error::from_path(dent.depth(), dent.path().to_path_buf(), err)
             }));
-            if md.file_type().is_dir() {
+            if md.file_type().is_dir() && descend {
                 itry!(self.push(&dent));
             }
         }
@@ -876,58 +3212,214 @@ impl IntoIter {
             None
         } else if self.skippable() {
             None
+        } else if self.opts.dedup_hard_links && dent.file_type().is_file() {
+            if itry!(self.is_first_link(&dent)) {
+                Some(self.check_name(dent))
+            } else {
+                None
+            }
+        } else {
+            let result = self.check_name(dent);
+            if let Ok(ref dent) = result {
+                if self.opts.emit_dir_close && !self.opts.contents_first {
+                    let pushed = self.stack_list.len() > stack_len;
+                    self.set_dir_close(dent, pushed);
+                }
+            }
+            Some(result)
+        }
+    }
+
+    /// Replaces the entry with an error if its file name must be, but isn't,
+    /// valid Unicode.
+    fn check_name(&self, dent: DirEntry) -> Result<DirEntry> {
+        if self.opts.require_unicode && dent.file_name_str().is_none() {
+            Err(Error::from_non_unicode_name(&dent))
+        } else {
+            Ok(dent)
+        }
+    }
+
+    /// Arranges for the entry marking the end of the given directory to be
+    /// yielded once it's left, or right away if it wasn't descended into.
+    fn set_dir_close(&mut self, dent: &DirEntry, pushed: bool) {
+        if pushed {
+            *self
+                .stack_close
+                .last_mut()
+                .expect("BUG: close stack should be non-empty") =
+                Some(dent.to_dir_close());
+        } else if dent.is_dir() {
+            self.closed.push(dent.to_dir_close());
+        }
+    }
+
+    fn take_closed(&mut self) -> Option<DirEntry> {
+        if self.closed.is_empty() {
+            None
         } else {
-            Some(Ok(dent))
+            Some(self.closed.remove(0))
         }
     }
 
     fn get_deferred_dir(&mut self) -> Option<DirEntry> {
-        if self.opts.contents_first {
-            if self.depth < self.deferred_dirs.len() {
-                // Unwrap is safe here because we've guaranteed that
-                // `self.deferred_dirs.len()` can never be less than 1
-                let deferred: DirEntry = self
-                    .deferred_dirs
-                    .pop()
-                    .expect("BUG: deferred_dirs should be non-empty");
-                if !self.skippable() {
-                    return Some(deferred);
-                }
+        if !self.opts.contents_first {
+            return None;
+        }
+        // A deferred directory at depth `n` was pushed onto the stack at
+        // index `n`, so it's done once the stack is no taller than `n`.
+        // This is checked with the directory's depth rather than with the
+        // number of deferred directories, since a symlinked root is on the
+        // stack without being deferred.
+        let is_done = match self.deferred_dirs.last() {
+            None => false,
+            Some(dent) => dent.depth() >= self.depth,
+        };
+        if is_done {
+            let deferred: DirEntry = self
+                .deferred_dirs
+                .pop()
+                .expect("BUG: deferred_dirs should be non-empty");
+            if !self.skippable() {
+                return Some(deferred);
             }
         }
         None
     }
 
     fn push(&mut self, dent: &DirEntry) -> Result<()> {
+        if let Some(limit) = self.opts.absolute_max_depth {
+            if dent.depth() >= limit && dent.depth() < self.max_depth() {
+                // Yield the error once the directory itself is yielded,
+                // rather than in its place.
+                self.pending_error = Some(Error::from_depth_limit(dent));
+                return Ok(());
+            }
+        }
         // Make room for another open file descriptor if we've hit the max.
         let free =
             self.stack_list.len().checked_sub(self.oldest_opened).unwrap();
-        if free == self.opts.max_open {
+        let evict_oldest = free == self.opts.max_open
+            && self.opts.eviction_policy == EvictionPolicy::Shallowest
+            && !self.opts.low_memory;
+        if evict_oldest {
             self.stack_list[self.oldest_opened].close();
+        } else if self.opts.eviction_policy == EvictionPolicy::Deepest
+            && !self.opts.low_memory
+        {
+            // Handles are no longer closed in order, so they have to be
+            // counted. This is cheap compared to opening a directory.
+            let open = self.stack_list.iter().filter(|l| l.is_open()).count();
+            if open >= self.opts.max_open {
+                if let Some(list) =
+                    self.stack_list.iter_mut().rev().find(|l| l.is_open())
+                {
+                    list.close();
+                }
+            }
         }
         // Open a handle to reading the directory's entries.
-        let rd = fs::read_dir(dent.path()).map_err(|err| {
-            Some(Error::from_path(self.depth, dent.path().to_path_buf(), err))
-        });
-        let mut list = DirList::Opened { depth: self.depth, it: rd };
-        if let Some(ref mut cmp) = self.opts.sorter {
+        self.stats.add_dir_opened();
+        // Trailing separators are kept in the root's own path, but they'd
+        // otherwise end up doubled in the paths of its children.
+        let dir = util::trim_trailing_separators(dent.path());
+        let mut rd =
+            util::read_dir(dir, self.opts.retry_transient).map_err(|err| {
+                Some(Error::from_enumeration(
+                    self.depth,
+                    Some(dent.path().to_path_buf()),
+                    err,
+                ))
+            });
+        if self.eager_root {
+            if let Err(ref mut err) = rd {
+                return Err(err.take().expect("BUG: error is always set"));
+            }
+        }
+        if self.opts.errors_last {
+            if let (Err(ref mut err), Some(errors)) =
+                (&mut rd, self.stack_errors.last_mut())
+            {
+                // Yield the error once the parent is left rather than right
+                // after this directory.
+                errors.push(err.take().expect("BUG: error is always set"));
+                return Ok(());
+            }
+        }
+        let mut list = DirList::Opened {
+            depth: self.depth,
+            device: dent.device(),
+            skip_unreadable: self.opts.skip_unreadable,
+            stop_on_error: self.opts.stop_dir_on_error,
+            it: rd,
+        };
+        if self.opts.sorter.is_some() || self.opts.group_symlinks.is_some() {
             let mut entries: Vec<_> = list.collect();
+            // A panic in another iterator sharing this sorter doesn't make
+            // it unusable for this one.
+            let mut cmp = self.opts.sorter.as_ref().map(|sorter| {
+                sorter.lock().unwrap_or_else(|err| err.into_inner())
+            });
+            let ctx = SortContext { parent: dent.path(), depth: dent.depth() };
+            let group = self.opts.group_symlinks;
+            let errors = if self.opts.errors_last {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+            self.poisoned = true;
             entries.sort_by(|a, b| match (a, b) {
-                (&Ok(ref a), &Ok(ref b)) => cmp(a, b),
-                (&Err(_), &Err(_)) => Ordering::Equal,
-                (&Ok(_), &Err(_)) => Ordering::Greater,
-                (&Err(_), &Ok(_)) => Ordering::Less,
+                (Ok(a), Ok(b)) => {
+                    let is_link =
+                        |dent: &DirEntry| dent.file_type().is_symlink();
+                    let grouped = match group {
+                        None => Ordering::Equal,
+                        Some(GroupOrder::First) => is_link(b).cmp(&is_link(a)),
+                        Some(GroupOrder::Last) => is_link(a).cmp(&is_link(b)),
+                    };
+                    grouped.then_with(|| match cmp {
+                        Some(ref mut cmp) => cmp(&ctx, a, b),
+                        None => {
+                            util::cmp_file_names(a.file_name(), b.file_name())
+                        }
+                    })
+                }
+                (Err(_), Err(_)) => Ordering::Equal,
+                (Ok(_), Err(_)) => errors.reverse(),
+                (Err(_), Ok(_)) => errors,
             });
+            self.poisoned = false;
             list = DirList::Closed(entries.into_iter());
         }
-        if self.opts.follow_links {
-            let ancestor = Ancestor::new(&dent)
+        #[cfg(feature = "ignore-files")]
+        let rules = match self.opts.ignore_file {
+            None => None,
+            Some(ref name) => Some(
+                ignore::IgnoreRules::read(dent.path(), name)
+                    .map_err(|err| Error::from_entry(dent, err))?,
+            ),
+        };
+        if self.tracks_ancestors() {
+            let ancestor = Ancestor::new(dent)
                 .map_err(|err| Error::from_io(self.depth, err))?;
             self.stack_path.push(ancestor);
         }
+        #[cfg(feature = "ignore-files")]
+        {
+            if let Some(rules) = rules {
+                self.stack_ignore.push(rules);
+            }
+        }
         // We push this after stack_path since creating the Ancestor can fail.
         // If it fails, then we return the error and won't descend.
         self.stack_list.push(list);
+        self.stack_pos.push((dent.path().to_path_buf(), 0));
+        if self.opts.emit_dir_close {
+            self.stack_close.push(None);
+        }
+        if self.opts.errors_last {
+            self.stack_errors.push(vec![]);
+        }
         // If we had to close out a previous directory stream, then we need to
         // increment our index the oldest still-open stream. We do this only
         // after adding to our stack, in order to ensure that the oldest_opened
@@ -937,7 +3429,7 @@ impl IntoIter {
         // We could move the close of the stream above into this if-body, but
         // then we would have more than the maximum number of file descriptors
         // open at a particular point in time.
-        if free == self.opts.max_open {
+        if evict_oldest {
             // Unwrap is safe here because self.oldest_opened is guaranteed to
             // never be greater than `self.stack_list.len()`, which implies
             // that the subtraction won't underflow and that adding 1 will
@@ -949,27 +3441,129 @@ impl IntoIter {
 
     fn pop(&mut self) {
         self.stack_list.pop().expect("BUG: cannot pop from empty stack");
-        if self.opts.follow_links {
+        self.stack_pos.pop().expect("BUG: list/position stacks out of sync");
+        if self.tracks_ancestors() {
             self.stack_path.pop().expect("BUG: list/path stacks out of sync");
         }
+        #[cfg(feature = "ignore-files")]
+        {
+            if self.opts.ignore_file.is_some() {
+                self.stack_ignore
+                    .pop()
+                    .expect("BUG: list/ignore stacks out of sync");
+            }
+        }
+        if self.opts.emit_dir_close {
+            let close = self
+                .stack_close
+                .pop()
+                .expect("BUG: list/close stacks out of sync");
+            self.closed.extend(close);
+        }
+        if self.opts.errors_last {
+            let errors = self
+                .stack_errors
+                .pop()
+                .expect("BUG: list/error stacks out of sync");
+            self.errors_due.extend(errors);
+        }
+        while let Some(&(len, _)) = self.stack_limit.last() {
+            if len <= self.stack_list.len() {
+                break;
+            }
+            self.stack_limit.pop();
+        }
         // If everything in the stack is already closed, then there is
         // room for at least one more open descriptor and it will
         // always be at the top of the stack.
         self.oldest_opened = min(self.oldest_opened, self.stack_list.len());
     }
 
+    /// Returns true if the given directory has a name given to
+    /// `skip_dirs_case_insensitive`.
+    fn is_skipped_dir(&self, dent: &DirEntry) -> bool {
+        let name = dent.file_name();
+        self.opts.skip_dirs_ci.iter().any(|skip| {
+            util::cmp_file_names_ascii_ci(name, skip) == Ordering::Equal
+        })
+    }
+
+    /// Returns true if the given entry should be skipped because of a marker
+    /// file or ignore rules.
+    #[cfg(feature = "ignore-files")]
+    fn is_ignored(&self, dent: &DirEntry, is_normal_dir: bool) -> bool {
+        let is_dir = dent.is_dir();
+        let matched = self
+            .stack_ignore
+            .iter()
+            .rev()
+            .filter_map(|rules| rules.as_ref())
+            .find_map(|rules| rules.matched(dent.file_name(), is_dir));
+        if matched == Some(true) {
+            return true;
+        }
+        match self.opts.marker_file {
+            Some(ref name) if is_normal_dir => {
+                ignore::has_marker(dent.path(), name)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if `stack_path` mirrors `stack_list`.
+    fn tracks_ancestors(&self) -> bool {
+        self.opts.follow_links || !self.opts.follow_mounts
+    }
+
     fn follow(&self, mut dent: DirEntry) -> Result<DirEntry> {
+        if !self.is_followed(&dent)? {
+            return Ok(dent);
+        }
         dent =
             DirEntry::from_path(self.depth, dent.path().to_path_buf(), true)?;
         // The only way a symlink can cause a loop is if it points
         // to a directory. Otherwise, it always points to a leaf
         // and we can omit any loop checks.
         if dent.is_dir() {
-            self.check_loop(dent.path())?;
+            self.check_dir_loop(&mut dent)?;
         }
         Ok(dent)
     }
 
+    /// Checks whether the given directory is the same as one of its
+    /// ancestors. If it is and `on_loop` doesn't ask for an error, then the
+    /// ancestor is recorded in the entry instead.
+    fn check_dir_loop(&self, dent: &mut DirEntry) -> Result<()> {
+        let err = match self.check_loop(dent.path()) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        match err.loop_ancestor() {
+            Some(ancestor) if self.opts.on_loop != LoopPolicy::Error => {
+                dent.set_loop_target(ancestor.to_path_buf());
+                Ok(())
+            }
+            _ => Err(err),
+        }
+    }
+
+    /// Returns true if the given symbolic link is one that `follow_links`
+    /// follows, according to its reparse tag.
+    #[cfg(windows)]
+    fn is_followed(&self, dent: &DirEntry) -> Result<bool> {
+        if dent.depth() == 0 {
+            return Ok(true);
+        }
+        let tag = util::reparse_tag(dent.path())
+            .map_err(|err| Error::from_entry(dent, err))?;
+        Ok(self.opts.follow_reparse_tags.contains(&tag))
+    }
+
+    #[cfg(not(windows))]
+    fn is_followed(&self, _: &DirEntry) -> Result<bool> {
+        Ok(true)
+    }
+
     fn check_loop<P: AsRef<Path>>(&self, child: P) -> Result<()> {
         let hchild = Handle::from_path(&child)
             .map_err(|err| Error::from_io(self.depth, err))?;
@@ -988,15 +3582,57 @@ impl IntoIter {
         Ok(())
     }
 
-    fn is_same_file_system(&mut self, dent: &DirEntry) -> Result<bool> {
+    /// Returns true if the given directory should be descended into
+    /// when `same_file_system` is enabled.
+    ///
+    /// Normally this compares against the device of the parent directory,
+    /// which is the root device unless a symlink was used to cross into
+    /// another file system (see `cross_fs_via_symlink`).
+    fn is_same_file_system(
+        &mut self,
+        dent: &mut DirEntry,
+        parent_device: Option<u64>,
+    ) -> Result<bool> {
         let dent_device = util::device_num(dent.path())
             .map_err(|err| Error::from_entry(dent, err))?;
-        Ok(self
-            .root_device
+        dent.set_device(Some(dent_device));
+        if self.opts.cross_fs_via_symlink && dent.path_is_symlink() {
+            return Ok(true);
+        }
+        Ok(parent_device
+            .or(self.root_device)
             .map(|d| d == dent_device)
             .expect("BUG: called is_same_file_system without root device"))
     }
 
+    /// Records a device descended into when `track_file_systems` is enabled.
+    fn saw_device(&mut self, device: u64) {
+        if self.opts.track_file_systems && !self.file_systems.contains(&device)
+        {
+            self.file_systems.push(device);
+        }
+    }
+
+    /// Returns true if and only if no other hard link to the given file has
+    /// been yielded yet.
+    fn is_first_link(&mut self, dent: &DirEntry) -> Result<bool> {
+        let id = util::hard_link_id(dent.path())
+            .map_err(|err| Error::from_entry(dent, err))?;
+        Ok(match id {
+            None => true,
+            Some(id) => self.hard_links.insert(id),
+        })
+    }
+
+    /// Returns the maximum depth of the current directory's descendants,
+    /// taking any limit set by `limit_descent` into account.
+    fn max_depth(&self) -> usize {
+        match self.stack_limit.last() {
+            None => self.opts.max_depth,
+            Some(&(_, limit)) => limit,
+        }
+    }
+
     fn skippable(&self) -> bool {
         self.depth < self.opts.min_depth || self.depth > self.opts.max_depth
     }
@@ -1005,6 +3641,10 @@ impl IntoIter {
 impl iter::FusedIterator for IntoIter {}
 
 impl DirList {
+    fn is_open(&self) -> bool {
+        matches!(*self, DirList::Opened { .. })
+    }
+
     fn close(&mut self) {
         if let DirList::Opened { .. } = *self {
             *self = DirList::Closed(self.collect::<Vec<_>>().into_iter());
@@ -1019,13 +3659,44 @@ impl Iterator for DirList {
     fn next(&mut self) -> Option<Result<DirEntry>> {
         match *self {
             DirList::Closed(ref mut it) => it.next(),
-            DirList::Opened { depth, ref mut it } => match *it {
-                Err(ref mut err) => err.take().map(Err),
-                Ok(ref mut rd) => rd.next().map(|r| match r {
-                    Ok(r) => DirEntry::from_entry(depth + 1, &r),
-                    Err(err) => Err(Error::from_io(depth + 1, err)),
-                }),
-            },
+            DirList::Opened {
+                depth,
+                device,
+                skip_unreadable,
+                stop_on_error,
+                ref mut it,
+            } => {
+                match *it {
+                    Err(ref mut err) => err.take().map(Err),
+                    Ok(ref mut rd) => loop {
+                        let ent = match util::read_entry(rd)? {
+                            Ok(ent) => ent,
+                            Err(_) if stop_on_error => {
+                                // Not every platform's `ReadDir` is done
+                                // after an error, so make sure this is.
+                                *it = Err(None);
+                                return None;
+                            }
+                            Err(err) => {
+                                return Some(Err(Error::from_enumeration(
+                                    depth + 1,
+                                    None,
+                                    err,
+                                )))
+                            }
+                        };
+                        match DirEntry::from_entry(depth + 1, &ent) {
+                            Ok(mut dent) => {
+                                dent.set_device(device);
+                                return Some(Ok(dent));
+                            }
+                            Err(ref err)
+                                if skip_unreadable && err.is_unreadable() => {}
+                            Err(err) => return Some(Err(err)),
+                        }
+                    },
+                }
+            }
         }
     }
 }
@@ -1052,6 +3723,7 @@ impl Iterator for DirList {
 /// [`min_depth`]: struct.WalkDir.html#method.min_depth
 /// [`max_depth`]: struct.WalkDir.html#method.max_depth
 #[derive(Debug)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
 pub struct FilterEntry<I, P> {
     it: I,
     predicate: P,
@@ -1076,7 +3748,7 @@ where
                 Some(result) => itry!(result),
             };
             if !(self.predicate)(&dent) {
-                if dent.is_dir() {
+                if dent.is_dir() && !dent.is_dir_close() {
                     self.it.skip_current_dir();
                 }
                 continue;
@@ -1191,4 +3863,14 @@ where
     pub fn skip_current_dir(&mut self) {
         self.it.skip_current_dir();
     }
+
+    /// Limits how much deeper the iterator descends into the current
+    /// directory.
+    ///
+    /// See [`IntoIter::limit_descent`] for details.
+    ///
+    /// [`IntoIter::limit_descent`]: struct.IntoIter.html#method.limit_descent
+    pub fn limit_descent(&mut self, additional_depth: usize) {
+        self.it.limit_descent(additional_depth);
+    }
 }
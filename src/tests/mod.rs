@@ -1,4 +1,6 @@
 #[macro_use]
 mod util;
 
+#[cfg(target_os = "linux")]
+mod mount;
 mod recursive;
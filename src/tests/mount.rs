@@ -0,0 +1,143 @@
+//! Tests for `same_file_system` that mount a second file system.
+//!
+//! This needs root and loop device support, so these tests do nothing
+//! unless both are available.
+
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::ptr;
+
+use crate::tests::util::Dir;
+use crate::WalkDir;
+
+/// An ext2 file system in a loopback file, mounted until dropped.
+struct LoopMount {
+    device: PathBuf,
+    target: PathBuf,
+}
+
+impl LoopMount {
+    /// Formats `image` and mounts it on `target`.
+    ///
+    /// This returns `None` if mounting isn't possible here, e.g., because
+    /// we aren't running as root.
+    fn new(image: &Path, target: &Path) -> Option<LoopMount> {
+        if unsafe { libc::geteuid() } != 0 {
+            return None;
+        }
+        fs::File::create(image).unwrap().set_len(4 << 20).unwrap();
+        let status =
+            Command::new("mkfs.ext2").arg("-q").arg("-F").arg(image).status();
+        if !matches!(status, Ok(status) if status.success()) {
+            return None;
+        }
+        let out = Command::new("losetup")
+            .arg("--find")
+            .arg("--show")
+            .arg(image)
+            .output()
+            .ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        let device = String::from_utf8(out.stdout).unwrap();
+        let device = PathBuf::from(device.trim());
+
+        let source = cstring(&device);
+        let dest = cstring(target);
+        let fstype = CString::new("ext2").unwrap();
+        let rc = unsafe {
+            libc::mount(
+                source.as_ptr(),
+                dest.as_ptr(),
+                fstype.as_ptr(),
+                0,
+                ptr::null(),
+            )
+        };
+        if rc != 0 {
+            detach(&device);
+            return None;
+        }
+        Some(LoopMount { device, target: target.to_path_buf() })
+    }
+}
+
+impl Drop for LoopMount {
+    fn drop(&mut self) {
+        let target = cstring(&self.target);
+        let rc = unsafe { libc::umount(target.as_ptr()) };
+        detach(&self.device);
+        if rc != 0 && !::std::thread::panicking() {
+            panic!(
+                "failed to unmount {}: {}",
+                self.target.display(),
+                ::std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+fn cstring(path: &Path) -> CString {
+    CString::new(path.as_os_str().as_bytes()).unwrap()
+}
+
+fn detach(device: &Path) {
+    let _ = Command::new("losetup").arg("-d").arg(device).status();
+}
+
+#[test]
+fn same_file_system_loopback() {
+    let dir = Dir::tmp();
+    dir.touch("a");
+    dir.mkdirp("mnt");
+    let mount = match LoopMount::new(&dir.join("image"), &dir.join("mnt")) {
+        None => return,
+        Some(mount) => mount,
+    };
+    fs::remove_dir(dir.join("mnt/lost+found")).unwrap();
+    dir.mkdirp("mnt/foo");
+    dir.touch("mnt/foo/b");
+
+    // Without same_file_system, the walk descends into the mount.
+    let wd = WalkDir::new(dir.path());
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("image"),
+        dir.join("mnt"),
+        dir.join("mnt/foo"),
+        dir.join("mnt/foo/b"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+
+    // ... but with it, the mount point is yielded without descending.
+    let wd = WalkDir::new(dir.path()).same_file_system(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("image"),
+        dir.join("mnt"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+
+    // Walking from inside the mount stays inside it.
+    let wd = WalkDir::new(dir.join("mnt")).same_file_system(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected =
+        vec![dir.join("mnt"), dir.join("mnt/foo"), dir.join("mnt/foo/b")];
+    assert_eq!(expected, r.sorted_paths());
+
+    drop(mount);
+}
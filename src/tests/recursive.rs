@@ -2,7 +2,7 @@ use std::fs;
 use std::path::PathBuf;
 
 use crate::tests::util::Dir;
-use crate::WalkDir;
+use crate::{DirEntry, LoopPolicy, WalkDir};
 
 #[test]
 fn send_sync_traits() {
@@ -321,6 +321,27 @@ fn siblings() {
     assert_eq!(expected, r.sorted_paths());
 }
 
+#[test]
+fn ancestors() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+
+    let wd = WalkDir::new(dir.path());
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let ents = r.sorted_ents();
+    assert_eq!(4, ents.len());
+    assert_eq!(0, ents[0].ancestors().count());
+
+    let ent = &ents[3];
+    assert_eq!(3, ent.depth());
+    let expected =
+        vec![dir.path().to_path_buf(), dir.join("a"), dir.join("a").join("b")];
+    let got: Vec<PathBuf> = ent.ancestors().map(|p| p.to_path_buf()).collect();
+    assert_eq!(expected, got);
+}
+
 #[test]
 fn sym_root_file_nofollow() {
     let dir = Dir::tmp();
@@ -523,6 +544,139 @@ fn sym_root_dir_follow() {
     assert!(!link_zzz.path_is_symlink());
 }
 
+#[test]
+fn root_was_symlink() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b");
+    dir.symlink_dir("a", "a-link");
+    dir.symlink_dir("b", "a/b-link");
+    dir.touch("a/zzz");
+
+    let root = |wd: WalkDir| {
+        let r = dir.run_recursive(wd.sort_by_file_name());
+        r.assert_no_errors();
+        for ent in &r.ents()[1..] {
+            assert!(!ent.root_was_symlink(), "{}", ent.path().display());
+        }
+        r.ents()[0].clone()
+    };
+
+    let link = dir.join("a-link");
+    let ent = root(WalkDir::new(&link));
+    assert!(ent.root_was_symlink());
+    assert!(ent.file_type().is_symlink());
+    let ent = root(WalkDir::new(&link).follow_links(true));
+    assert!(ent.root_was_symlink());
+    assert!(ent.file_type().is_dir());
+    let ent = root(WalkDir::new(&link).follow_root_links(false));
+    assert!(ent.root_was_symlink());
+
+    let ent = root(WalkDir::new(dir.join("a")));
+    assert!(!ent.root_was_symlink());
+    let ent = root(WalkDir::new(dir.join("a")).follow_links(true));
+    assert!(!ent.root_was_symlink());
+}
+
+#[test]
+fn root_entry() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.touch("a/b/c");
+    dir.touch("file");
+    dir.symlink_dir("a", "a-link");
+
+    let check = |root: &str, follow: bool| {
+        let wd = || {
+            WalkDir::new(dir.join(root))
+                .follow_links(follow)
+                .same_file_system(true)
+        };
+        let mut it = wd().min_depth(2).into_iter();
+        assert!(it.root_entry().is_none());
+        let _ = it.next();
+        let got = it.root_entry().unwrap().clone();
+
+        let want = wd().into_iter().next().unwrap().unwrap();
+        assert_eq!(want.path(), got.path());
+        assert_eq!(want.depth(), got.depth());
+        assert_eq!(want.file_type(), got.file_type());
+        assert_eq!(want.path_is_symlink(), got.path_is_symlink());
+        assert_eq!(want.root_was_symlink(), got.root_was_symlink());
+        assert_eq!(want.device(), got.device());
+        got
+    };
+
+    assert!(check("a", false).file_type().is_dir());
+    assert!(check("file", false).file_type().is_file());
+    assert!(check("a-link", false).file_type().is_symlink());
+    let ent = check("a-link", true);
+    assert!(ent.file_type().is_dir());
+    assert!(ent.path_is_symlink());
+}
+
+#[test]
+fn metadata_follow() {
+    let dir = Dir::tmp();
+    dir.touch("a");
+    dir.symlink_file("a", "a-link");
+
+    let link = |follow| {
+        let wd = WalkDir::new(dir.join("a-link")).follow_links(follow);
+        wd.into_iter().next().unwrap().unwrap()
+    };
+
+    let ent = link(false);
+    assert!(ent.metadata_follow().unwrap().is_file());
+    assert!(ent.metadata_no_follow().unwrap().file_type().is_symlink());
+    assert!(ent.metadata_follow().unwrap().is_file());
+    assert!(ent.metadata().unwrap().file_type().is_symlink());
+
+    let ent = link(true);
+    assert!(ent.metadata_no_follow().unwrap().file_type().is_symlink());
+    assert!(ent.metadata_follow().unwrap().is_file());
+    assert!(ent.metadata_no_follow().unwrap().file_type().is_symlink());
+    assert!(ent.metadata().unwrap().is_file());
+
+    // Both are the same for anything that isn't a link.
+    let ent = WalkDir::new(dir.join("a")).into_iter().next().unwrap().unwrap();
+    assert!(ent.metadata_follow().unwrap().is_file());
+    assert!(ent.metadata_no_follow().unwrap().is_file());
+}
+
+#[cfg(unix)]
+#[test]
+fn special_file_root() {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+    use std::process::Command;
+
+    let dir = Dir::tmp();
+    let status = Command::new("mkfifo").arg(dir.join("fifo")).status();
+    assert!(status.unwrap().success());
+    let _listener = UnixListener::bind(dir.join("socket")).unwrap();
+
+    // Each is yielded once, without trying to read it as a directory.
+    let single = |root: PathBuf| {
+        let mut it = WalkDir::new(&root).into_iter();
+        let ent = it.next().unwrap().unwrap();
+        assert!(it.next().is_none());
+        assert_eq!(0, it.stats().dirs_opened());
+        assert_eq!(root, ent.path());
+        assert_eq!(0, ent.depth());
+        assert!(!ent.file_type().is_dir());
+        ent.file_type()
+    };
+    assert!(single(dir.join("fifo")).is_fifo());
+    assert!(single(dir.join("socket")).is_socket());
+    assert!(single(PathBuf::from("/dev/null")).is_char_device());
+
+    // A special file inside a directory is just another entry.
+    let r = dir.run_recursive(WalkDir::new(dir.path()));
+    r.assert_no_errors();
+    assert_eq!(3, r.ents().len());
+}
+
 #[test]
 fn sym_file_nofollow() {
     let dir = Dir::tmp();
@@ -714,6 +868,70 @@ fn sym_loop_detect() {
     assert!(err.io_error().is_none());
 }
 
+#[test]
+fn sym_loop_policy() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+    dir.symlink_dir("a", "a/b/c/a-link");
+    let link = dir.join("a/b/c/a-link");
+
+    let walk = |policy| {
+        let wd = WalkDir::new(dir.path()).follow_links(true).on_loop(policy);
+        dir.run_recursive(wd)
+    };
+    let mut expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a/b"),
+        dir.join("a/b/c"),
+    ];
+
+    let r = walk(LoopPolicy::Error);
+    assert_eq!(expected, r.sorted_paths());
+    assert_eq!(1, r.errs().len());
+    assert_eq!(Some(dir.join("a").as_path()), r.errs()[0].loop_ancestor());
+    assert!(r.ents().iter().all(|ent| ent.loop_target().is_none()));
+
+    let r = walk(LoopPolicy::SkipSilently);
+    r.assert_no_errors();
+    assert_eq!(expected, r.sorted_paths());
+
+    // The link is yielded, but nothing beneath it.
+    let r = walk(LoopPolicy::Report);
+    r.assert_no_errors();
+    expected.push(link.clone());
+    assert_eq!(expected, r.sorted_paths());
+    let ent = r.ents().iter().find(|ent| ent.path() == link).unwrap();
+    assert_eq!(Some(dir.join("a").as_path()), ent.loop_target());
+    assert_eq!(4, ent.depth());
+    assert!(ent.file_type().is_dir());
+    assert!(ent.path_is_symlink());
+    // Walking from the link starts afresh, so it isn't a loop there.
+    let root = WalkDir::from_entry(ent).into_iter().next().unwrap().unwrap();
+    assert_eq!(link, root.path());
+    assert_eq!(None, root.loop_target());
+    let mut others = r.ents().iter().filter(|ent| ent.path() != link);
+    assert!(others.all(|ent| ent.loop_target().is_none()));
+
+    // Dropping the reported links doesn't skip their siblings, since they
+    // were never descended into.
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.symlink_dir(dir.path(), "a/loop");
+    dir.touch("a/zfile");
+    let wd = WalkDir::new(dir.path())
+        .follow_links(true)
+        .on_loop(LoopPolicy::Report)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|ent| ent.loop_target().is_none());
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let expected =
+        vec![dir.path().to_path_buf(), dir.join("a"), dir.join("a/zfile")];
+    assert_eq!(expected, r.paths());
+}
+
 #[test]
 fn sym_self_loop_no_error() {
     let dir = Dir::tmp();
@@ -887,6 +1105,24 @@ fn min_max_depth_diff_1() {
     assert_eq!(expected, r.sorted_paths());
 }
 
+#[test]
+fn try_depths() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+
+    let err = WalkDir::new(dir.path()).try_depths(5, 2).unwrap_err();
+    assert_eq!(5, err.min_depth());
+    assert_eq!(2, err.max_depth());
+
+    let wd = WalkDir::new(dir.path()).try_depths(2, 5).unwrap();
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected =
+        vec![dir.join("a").join("b"), dir.join("a").join("b").join("c")];
+    assert_eq!(expected, r.sorted_paths());
+}
+
 #[test]
 fn contents_first() {
     let dir = Dir::tmp();
@@ -926,6 +1162,271 @@ fn skip_current_dir() {
     assert_eq!(expected, paths);
 }
 
+#[test]
+fn skip_current_dir_contents_first() {
+    let dir = Dir::tmp();
+    dir.mkdirp("root/a/sub/deep");
+    dir.mkdirp("root/b");
+    dir.touch_all(&[
+        "root/a/f1",
+        "root/a/sub/deep/g",
+        "root/a/sub/h",
+        "root/a/z",
+        "root/b/x",
+    ]);
+
+    // Walks `start` in post-order, skipping the current directory right
+    // after the entry named `skip_at` is yielded.
+    let walk = |start: &str, skip_at: &str| -> Vec<String> {
+        let start = dir.join(start);
+        let mut names = vec![];
+        let mut it = WalkDir::new(&start)
+            .contents_first(true)
+            .sort_by_file_name()
+            .into_iter();
+        while let Some(result) = it.next() {
+            let ent = result.unwrap();
+            let rel = ent.path().strip_prefix(&start).unwrap();
+            let name = rel.to_str().unwrap().replace('\\', "/");
+            if name == skip_at {
+                it.skip_current_dir();
+            }
+            names.push(name);
+        }
+        names
+    };
+
+    // Skipping after a file prunes its unread siblings, including
+    // subdirectories that haven't been descended into yet, but its parent
+    // is still yielded after it.
+    let expected = vec!["a/f1", "a", "b/x", "b", ""];
+    assert_eq!(expected, walk("root", "a/f1"));
+
+    // The same holds deeper down. Every ancestor is still yielded in
+    // post-order.
+    let expected = vec![
+        "a/f1",
+        "a/sub/deep/g",
+        "a/sub/deep",
+        "a/sub/h",
+        "a/sub",
+        "a/z",
+        "a",
+        "b/x",
+        "b",
+        "",
+    ];
+    assert_eq!(expected, walk("root", "a/sub/deep/g"));
+
+    // A directory is yielded after its contents, when it has already been
+    // left, so skipping after it prunes the rest of its parent.
+    let expected = vec![
+        "a/f1",
+        "a/sub/deep/g",
+        "a/sub/deep",
+        "a/sub/h",
+        "a/sub",
+        "a",
+        "b/x",
+        "b",
+        "",
+    ];
+    assert_eq!(expected, walk("root", "a/sub"));
+
+    // Skipping after the last directory prunes nothing but the root, which
+    // is still yielded.
+    let expected = vec![
+        "a/f1",
+        "a/sub/deep/g",
+        "a/sub/deep",
+        "a/sub/h",
+        "a/sub",
+        "a/z",
+        "a",
+        "b/x",
+        "b",
+        "",
+    ];
+    assert_eq!(expected, walk("root", "b"));
+    assert_eq!(expected, walk("root", ""));
+
+    #[cfg(unix)]
+    {
+        // A symlinked root is yielded before its contents, but the
+        // directories beneath it are still yielded in post-order.
+        dir.symlink_dir("root", "link");
+        let expected = vec!["", "a/f1", "a", "b/x", "b"];
+        assert_eq!(expected, walk("link", "a/f1"));
+        let expected = vec![
+            "",
+            "a/f1",
+            "a/sub/deep/g",
+            "a/sub/deep",
+            "a/sub/h",
+            "a/sub",
+            "a",
+            "b/x",
+            "b",
+        ];
+        assert_eq!(expected, walk("link", "a/sub"));
+        // Skipping right after the root prunes everything.
+        assert_eq!(vec![""], walk("link", ""));
+    }
+}
+
+#[test]
+fn limit_descent() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/snapshots/s1/deep");
+    dir.mkdirp("a/z/deep");
+    dir.mkdirp("b/c/snapshots/s2/deep");
+    dir.mkdirp("b/c/snapshots/snapshots/s3");
+    dir.mkdirp("snapshots/s4");
+    dir.touch_all(&["a/snapshots/f", "a/z/deep/f", "b/c/snapshots/s2/f"]);
+
+    let mut paths = vec![];
+    let mut it = WalkDir::new(dir.path()).into_iter();
+    while let Some(result) = it.next() {
+        let ent = result.unwrap();
+        if ent.file_type().is_dir() && ent.file_name() == "snapshots" {
+            it.limit_descent(1);
+        }
+        paths.push(ent.into_path());
+    }
+    paths.sort();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a/snapshots"),
+        dir.join("a/snapshots/f"),
+        dir.join("a/snapshots/s1"),
+        dir.join("a/z"),
+        dir.join("a/z/deep"),
+        dir.join("a/z/deep/f"),
+        dir.join("b"),
+        dir.join("b/c"),
+        dir.join("b/c/snapshots"),
+        dir.join("b/c/snapshots/s2"),
+        // The limit of the outer `snapshots` still applies here.
+        dir.join("b/c/snapshots/snapshots"),
+        dir.join("snapshots"),
+        dir.join("snapshots/s4"),
+    ];
+    assert_eq!(expected, paths);
+
+    // A tighter limit below a looser one takes effect, and so does a
+    // tighter `max_depth`.
+    let mut paths = vec![];
+    let mut it = WalkDir::new(dir.path()).max_depth(4).into_iter();
+    while let Some(result) = it.next() {
+        let ent = result.unwrap();
+        if ent.depth() == 1 && ent.file_name() == "b" {
+            it.limit_descent(10);
+        } else if ent.file_name() == "snapshots" {
+            it.limit_descent(0);
+        }
+        paths.push(ent.into_path());
+    }
+    paths.sort();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a/snapshots"),
+        dir.join("a/z"),
+        dir.join("a/z/deep"),
+        dir.join("a/z/deep/f"),
+        dir.join("b"),
+        dir.join("b/c"),
+        dir.join("b/c/snapshots"),
+        dir.join("snapshots"),
+    ];
+    assert_eq!(expected, paths);
+}
+
+#[test]
+fn emit_dir_close() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+    dir.mkdirp("d");
+    dir.touch_all(&["a/b/c/f", "a/x", "d/y", "z"]);
+
+    // Renders each entry as its relative path, with a `/` appended to
+    // close markers.
+    let render = |ent: &DirEntry| {
+        let rel = ent.path().strip_prefix(dir.path()).unwrap();
+        let name = rel.to_str().unwrap().replace('\\', "/");
+        if ent.is_dir_close() {
+            format!("{}/", name)
+        } else {
+            name
+        }
+    };
+    let walk = |wd: WalkDir| -> Vec<String> {
+        let wd = wd.emit_dir_close(true).sort_by_file_name();
+        let mut depth = 0isize;
+        let mut names = vec![];
+        for result in wd {
+            let ent = result.unwrap();
+            if ent.is_dir_close() {
+                depth -= 1;
+                assert!(depth >= 0, "unbalanced close: {:?}", ent);
+            } else if ent.file_type().is_dir() {
+                depth += 1;
+            }
+            names.push(render(&ent));
+        }
+        assert_eq!(0, depth, "unbalanced markers: {:?}", names);
+        names
+    };
+
+    let expected = vec![
+        "", "a", "a/b", "a/b/c", "a/b/c/f", "a/b/c/", "a/b/", "a/x", "a/",
+        "d", "d/y", "d/", "z", "/",
+    ];
+    assert_eq!(expected, walk(WalkDir::new(dir.path())));
+
+    // Directories that aren't descended into are still closed.
+    let expected = vec!["", "a", "a/", "d", "d/", "z", "/"];
+    assert_eq!(expected, walk(WalkDir::new(dir.path()).max_depth(1)));
+
+    // Markers follow `min_depth` like any other entry.
+    let expected =
+        vec!["a/b", "a/b/c", "a/b/c/f", "a/b/c/", "a/b/", "a/x", "d/y"];
+    assert_eq!(expected, walk(WalkDir::new(dir.path()).min_depth(2)));
+
+    // Skipping a directory closes it right away, and filtering one out
+    // drops both of its entries.
+    let mut names = vec![];
+    let mut it = WalkDir::new(dir.path())
+        .emit_dir_close(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|ent| ent.file_name() != "d");
+    while let Some(result) = it.next() {
+        let ent = result.unwrap();
+        if ent.file_name() == "b" && !ent.is_dir_close() {
+            it.skip_current_dir();
+        }
+        names.push(render(&ent));
+    }
+    let expected = vec!["", "a", "a/b", "a/b/", "a/x", "a/", "z", "/"];
+    assert_eq!(expected, names);
+
+    // There's nothing to add when directories are yielded after their
+    // contents anyway.
+    let wd = WalkDir::new(dir.path())
+        .contents_first(true)
+        .emit_dir_close(true)
+        .sort_by_file_name();
+    let names: Vec<String> =
+        wd.into_iter().map(|r| render(&r.unwrap())).collect();
+    let expected =
+        vec!["a/b/c/f", "a/b/c", "a/b", "a/x", "a", "d/y", "d", "z", ""];
+    assert_eq!(expected, names);
+}
+
 #[test]
 fn filter_entry() {
     let dir = Dir::tmp();
@@ -947,6 +1448,63 @@ fn filter_entry() {
     assert_eq!(expected, r.sorted_paths());
 }
 
+#[test]
+fn filter_entry_max_depth() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp(".hidden");
+    dir.mkdirp("z");
+    dir.touch("zz_file");
+
+    // A directory at the maximum depth isn't descended into, so filtering it
+    // out must not skip the rest of its parent.
+    let wd = WalkDir::new(dir.path())
+        .max_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|ent| {
+            !ent.file_name().to_string_lossy().starts_with('.')
+        });
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("z"),
+        dir.join("zz_file"),
+    ];
+    assert_eq!(expected, r.paths());
+}
+
+#[test]
+fn skip_current_dir_max_depth() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/.git/objects");
+    dir.mkdirp("a/src");
+    dir.touch("a/z");
+
+    let mut paths = vec![];
+    let mut it =
+        WalkDir::new(dir.path()).max_depth(2).sort_by_file_name().into_iter();
+    while let Some(result) = it.next() {
+        let ent = result.unwrap();
+        if ent.file_name() == ".git" {
+            it.skip_current_dir();
+            // Nothing below it would be yielded anyway.
+            it.limit_descent(0);
+            continue;
+        }
+        paths.push(ent.into_path());
+    }
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a/src"),
+        dir.join("a/z"),
+    ];
+    assert_eq!(expected, paths);
+}
+
 #[test]
 fn sort_by() {
     let dir = Dir::tmp();
@@ -1071,6 +1629,107 @@ fn same_file_system() {
     assert_eq!(expected, r.sorted_paths());
 }
 
+#[cfg(target_os = "linux")]
+#[test]
+fn track_file_systems() {
+    use std::path::Path;
+
+    // This uses /sys as another file system, like `same_file_system` above.
+    if !Path::new("/sys").is_dir() {
+        return;
+    }
+
+    let dir = Dir::tmp();
+    dir.touch("a");
+    dir.symlink_dir("/sys", "sys-link");
+
+    let wd = WalkDir::new(dir.path())
+        .track_file_systems(true)
+        .follow_links(true)
+        .max_depth(2);
+    let mut it = wd.into_iter();
+    // Some entries of /sys can't be read, which doesn't matter here.
+    let ents: Vec<_> = it.by_ref().flatten().collect();
+    let seen = it.file_systems_seen();
+    assert_eq!(2, seen.len(), "{:?}", seen);
+
+    let device = |path: &Path| {
+        ents.iter().find(|ent| ent.path() == path).unwrap().device()
+    };
+    assert_eq!(Some(seen[0]), device(dir.path()));
+    assert_eq!(Some(seen[0]), device(&dir.join("a")));
+    assert_eq!(Some(seen[1]), device(&dir.join("sys-link")));
+    assert!(ents
+        .iter()
+        .filter(|ent| ent.depth() == 2)
+        .all(|ent| ent.device() == Some(seen[1])));
+
+    // Without descending into /sys, only the root's device is seen.
+    let wd = WalkDir::new(dir.path()).track_file_systems(true);
+    let mut it = wd.into_iter();
+    for result in it.by_ref() {
+        result.unwrap();
+    }
+    assert_eq!(1, it.file_systems_seen().len());
+}
+
+#[cfg(unix)]
+#[test]
+fn device() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("foo");
+    dir.touch("foo/a");
+    dir.touch("b");
+
+    // Nothing needs to know the device without same_file_system.
+    let wd = WalkDir::new(dir.path());
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert!(r.ents().iter().all(|ent| ent.device().is_none()));
+
+    let wd = WalkDir::new(dir.path()).same_file_system(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    assert_eq!(4, r.ents().len());
+    for ent in r.ents() {
+        let dev = fs::symlink_metadata(ent.path()).unwrap().dev();
+        assert_eq!(Some(dev), ent.device(), "{}", ent.path().display());
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn device_other_file_system() {
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    if !Path::new("/sys").is_dir() {
+        return;
+    }
+
+    let dir = Dir::tmp();
+    dir.symlink_dir("/sys", "sys-link");
+
+    let wd =
+        WalkDir::new(dir.path()).same_file_system(true).follow_links(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let ents = r.sorted_ents();
+    assert_eq!(2, ents.len());
+    let (root, link) = (&ents[0], &ents[1]);
+    assert_eq!(dir.join("sys-link"), link.path());
+
+    let root_dev = fs::metadata(dir.path()).unwrap().dev();
+    let sys_dev = fs::metadata("/sys").unwrap().dev();
+    assert_ne!(root_dev, sys_dev);
+    assert_eq!(Some(root_dev), root.device());
+    assert_eq!(Some(sys_dev), link.device());
+}
+
 // Tests that skip_current_dir doesn't destroy internal invariants.
 //
 // See: https://github.com/BurntSushi/walkdir/issues/118
@@ -1090,3 +1749,2430 @@ fn regression_skip_current_dir() {
     wd.skip_current_dir();
     wd.next();
 }
+
+#[test]
+fn from_entry() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/a/b");
+    dir.touch_all(&["foo/a/b/c", "foo/x"]);
+
+    let foo = WalkDir::new(dir.path())
+        .min_depth(1)
+        .into_iter()
+        .map(|r| r.unwrap())
+        .find(|d| d.file_name() == "foo")
+        .unwrap();
+    assert_eq!(1, foo.depth());
+
+    let sub = dir.run_recursive(WalkDir::from_entry(&foo));
+    sub.assert_no_errors();
+    let fresh = dir.run_recursive(WalkDir::new(foo.path()));
+    fresh.assert_no_errors();
+
+    assert_eq!(fresh.sorted_paths(), sub.sorted_paths());
+    let depths: Vec<usize> =
+        sub.sorted_ents().iter().map(|d| d.depth()).collect();
+    assert_eq!(vec![0, 1, 2, 3, 1], depths);
+
+    // A marker for the end of a directory starts a walk of that directory.
+    let close = WalkDir::new(dir.path())
+        .emit_dir_close(true)
+        .into_iter()
+        .map(|r| r.unwrap())
+        .find(|d| d.is_dir_close() && d.file_name() == "foo")
+        .unwrap();
+    let sub = dir.run_recursive(WalkDir::from_entry(&close));
+    sub.assert_no_errors();
+    assert_eq!(fresh.sorted_paths(), sub.sorted_paths());
+    assert!(sub.ents().iter().all(|d| !d.is_dir_close()));
+}
+
+#[test]
+#[cfg(unix)]
+fn dedup_hard_links() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b");
+    dir.touch_all(&["a/file", "a/other"]);
+    fs::hard_link(dir.join("a/file"), dir.join("b/link")).unwrap();
+
+    let wd = WalkDir::new(dir.path()).sort_by_file_name();
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert_eq!(6, r.ents().len());
+
+    let wd =
+        WalkDir::new(dir.path()).sort_by_file_name().dedup_hard_links(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a").join("file"),
+        dir.join("a").join("other"),
+        dir.join("b"),
+    ];
+    assert_eq!(expected, r.paths());
+}
+
+#[test]
+fn next_deadline() {
+    use std::time::{Duration, Instant};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch_all(&["foo/bar/a", "foo/b"]);
+
+    let mut it = WalkDir::new(dir.path()).into_iter();
+    let past = Instant::now();
+    let err = it.next_deadline(past).unwrap().unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(0, err.depth());
+    assert!(err.path().is_none());
+
+    // The root is still yielded after a timeout.
+    assert_eq!(dir.path(), it.next().unwrap().unwrap().path());
+    assert_eq!(dir.join("foo"), it.next().unwrap().unwrap().path());
+    // No entries are read from `foo` once the deadline has passed.
+    let err = it.next_deadline(past).unwrap().unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(2, err.depth());
+
+    // Nothing is lost when resuming after a timeout.
+    let future = Instant::now() + Duration::from_secs(60 * 60);
+    let mut rest = vec![];
+    while let Some(result) = it.next_deadline(future) {
+        rest.push(result.unwrap().into_path());
+    }
+    rest.sort();
+    let expected =
+        vec![dir.join("foo/b"), dir.join("foo/bar"), dir.join("foo/bar/a")];
+    assert_eq!(expected, rest);
+}
+
+#[test]
+fn components_from_root() {
+    use std::ffi::OsStr;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a b/.hidden/-x");
+    dir.mkdirp("ü/...");
+    dir.touch_all(&["a b/.hidden/-x/y.z", "ü/.../ü", "top"]);
+    #[cfg(unix)]
+    dir.touch("ü/back\\slash");
+
+    for root in &[dir.path().to_path_buf(), dir.path().join("")] {
+        let r = dir.run_recursive(WalkDir::new(root));
+        r.assert_no_errors();
+        for ent in r.ents() {
+            let expected: Vec<&OsStr> =
+                ent.path().strip_prefix(root).unwrap().iter().collect();
+            let got: Vec<&OsStr> = ent.components_from_root().collect();
+            assert_eq!(expected, got);
+            assert_eq!(ent.depth(), got.len());
+            assert_eq!(
+                expected.first().cloned(),
+                ent.first_component_from_root()
+            );
+        }
+    }
+}
+
+#[test]
+fn into_std_like() {
+    let dir = Dir::tmp();
+    dir.touch("foo");
+    fs::write(dir.join("foo"), "hello").unwrap();
+
+    let std_ent = fs::read_dir(dir.path()).unwrap().next().unwrap().unwrap();
+    let ent = WalkDir::new(dir.path())
+        .min_depth(1)
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap()
+        .into_std_like();
+
+    assert_eq!(std_ent.path(), ent.path());
+    assert_eq!(std_ent.file_name(), ent.file_name());
+    assert_eq!(std_ent.file_type().unwrap(), ent.file_type().unwrap());
+    assert!(ent.file_type().unwrap().is_file());
+    assert_eq!(5, ent.metadata().unwrap().len());
+    assert_eq!(dir.join("foo"), ent.into_inner().path());
+}
+
+#[test]
+#[cfg(unix)]
+fn skip_unreadable_broken_symlink() {
+    let dir = Dir::tmp();
+    dir.touch("a");
+    dir.symlink_file("does-not-exist", "broken");
+
+    let wd = WalkDir::new(dir.path()).follow_links(true);
+    let r = dir.run_recursive(wd);
+    assert_eq!(1, r.errs().len());
+
+    let wd = WalkDir::new(dir.path()).follow_links(true).skip_unreadable(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert_eq!(
+        vec![dir.path().to_path_buf(), dir.join("a")],
+        r.sorted_paths()
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn skip_unreadable_sys() {
+    if !std::path::Path::new("/sys/class").is_dir() {
+        return;
+    }
+    let wd = WalkDir::new("/sys/class")
+        .max_depth(2)
+        .follow_links(true)
+        .skip_unreadable(true);
+    let r = Dir::tmp().run_recursive(wd);
+    r.assert_no_errors();
+    assert!(!r.ents().is_empty());
+}
+
+#[test]
+fn display_and_debug() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo");
+    dir.touch("foo/a b");
+
+    let r = dir.run_recursive(WalkDir::new(dir.path()).min_depth(1));
+    r.assert_no_errors();
+    for ent in r.ents() {
+        assert_eq!(ent.path().display().to_string(), format!("{}", ent));
+    }
+
+    let ents = r.sorted_ents();
+    let expected = format!(
+        "DirEntry {{ path: {:?}, depth: 2, file_type: file }}",
+        dir.join("foo").join("a b"),
+    );
+    assert_eq!(expected, format!("{:?}", ents[1]));
+    assert!(format!("{:?}", ents[0]).ends_with("file_type: dir }"));
+}
+
+#[test]
+fn follow_mounts_disabled() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.touch_all(&["a/b/c", "d"]);
+
+    let plain = dir.run_recursive(WalkDir::new(dir.path()));
+    plain.assert_no_errors();
+    let wd = WalkDir::new(dir.path()).follow_mounts(false);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert_eq!(plain.sorted_paths(), r.sorted_paths());
+}
+
+// A recursive bind mount needs privileges to set up, so this simulates one
+// by handing the iterator a plain (non-symlink) directory entry for one of
+// its ancestors.
+#[test]
+fn follow_mounts_disabled_detects_ancestor() {
+    use crate::DirEntry;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+
+    let mut it = WalkDir::new(dir.path())
+        .follow_mounts(false)
+        .sort_by_file_name()
+        .into_iter();
+    assert_eq!(dir.path(), it.next().unwrap().unwrap().path());
+    assert_eq!(dir.join("a"), it.next().unwrap().unwrap().path());
+
+    it.depth = 2;
+    let mount =
+        DirEntry::from_path(2, dir.path().to_path_buf(), false).unwrap();
+    let err = it.handle_entry(mount).unwrap().unwrap_err();
+    assert_eq!(Some(dir.path()), err.loop_ancestor());
+    assert_eq!(2, err.depth());
+
+    // Without the check, the same entry is descended into.
+    let mut it = WalkDir::new(dir.path()).into_iter();
+    it.next().unwrap().unwrap();
+    let mount =
+        DirEntry::from_path(1, dir.path().to_path_buf(), false).unwrap();
+    assert!(it.handle_entry(mount).unwrap().is_ok());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn cross_fs_via_symlink() {
+    use std::path::Path;
+
+    if !Path::new("/sys/kernel").is_dir() {
+        return;
+    }
+
+    let dir = Dir::tmp();
+    dir.touch("a");
+    dir.symlink_dir("/sys/kernel", "sys-link");
+
+    // By default, the symlink to /sys isn't descended into.
+    let wd = WalkDir::new(dir.path())
+        .same_file_system(true)
+        .follow_links(true)
+        .max_depth(2);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let expected =
+        vec![dir.path().to_path_buf(), dir.join("a"), dir.join("sys-link")];
+    assert_eq!(expected, r.sorted_paths());
+
+    // ... but it is when crossing via symlinks is allowed.
+    let wd = WalkDir::new(dir.path())
+        .same_file_system(true)
+        .follow_links(true)
+        .cross_fs_via_symlink(true)
+        .max_depth(2);
+    let r = dir.run_recursive(wd);
+    let paths = r.sorted_paths();
+    assert!(paths.len() > 3);
+    assert!(paths.iter().all(|p| p.starts_with(dir.path())));
+    assert!(paths.iter().any(|p| p.parent() == Some(&dir.join("sys-link"))));
+}
+
+#[test]
+fn into_channel() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.touch_all(&["a/b/c", "d"]);
+
+    let (handle, rx) = WalkDir::new(dir.path()).into_channel(1);
+    let mut paths: Vec<PathBuf> =
+        rx.into_iter().map(|r| r.unwrap().into_path()).collect();
+    paths.sort();
+    let stats = handle.join();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a").join("b"),
+        dir.join("a").join("b").join("c"),
+        dir.join("d"),
+    ];
+    assert_eq!(expected, paths);
+    assert_eq!(5, stats.entries());
+    assert_eq!(0, stats.errors());
+    assert_eq!(3, stats.dirs_opened());
+}
+
+#[test]
+fn into_channel_receiver_dropped() {
+    let dir = Dir::tmp();
+    for i in 0..100 {
+        dir.mkdirp(format!("{}", i));
+        dir.touch(format!("{}/file", i));
+    }
+
+    let (handle, rx) = WalkDir::new(dir.path()).into_channel(1);
+    for result in rx.iter().take(10) {
+        result.unwrap();
+    }
+    drop(rx);
+    let stats = handle.join();
+    assert!(stats.entries() < 20, "{:?}", stats);
+    assert!(stats.dirs_opened() < 20, "{:?}", stats);
+}
+
+#[test]
+fn collect_entries() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.touch("a/b");
+
+    let ents = WalkDir::new(dir.path()).into_iter().collect_entries().unwrap();
+    assert_eq!(3, ents.len());
+
+    let err = WalkDir::new(dir.join("nope"))
+        .into_iter()
+        .collect_entries()
+        .unwrap_err();
+    assert_eq!(Some(dir.join("nope").as_path()), err.path());
+}
+
+#[test]
+fn partition_results() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.touch("a/b");
+
+    let (ents, errs) =
+        WalkDir::new(dir.path()).into_iter().partition_results();
+    assert_eq!(3, ents.len());
+    assert!(errs.is_empty());
+
+    let (ents, errs) =
+        WalkDir::new(dir.join("nope")).into_iter().partition_results();
+    assert!(ents.is_empty());
+    assert_eq!(1, errs.len());
+}
+
+#[cfg(feature = "ignore-files")]
+#[test]
+fn respect_simple_ignore() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("logs");
+    dir.touch_all(&[
+        "x.log",
+        "keep.txt",
+        "a/y.log",
+        "a/important.log",
+        "a/b/z.log",
+        "a/b/keep.tmp",
+        "logs/w.txt",
+    ]);
+    fs::write(dir.join(".ignore"), "# comment\n*.log\nlogs/\n*.tmp\n")
+        .unwrap();
+    // Re-include one file in `a`, and re-include all logs below `a/b`.
+    fs::write(dir.join("a/.ignore"), "!important.log\n").unwrap();
+    fs::write(dir.join("a/b/.ignore"), "!*.log\n").unwrap();
+
+    let wd = WalkDir::new(dir.path()).respect_simple_ignore(".ignore");
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join(".ignore"),
+        dir.join("a"),
+        dir.join("a/.ignore"),
+        dir.join("a/b"),
+        dir.join("a/b/.ignore"),
+        dir.join("a/b/z.log"),
+        dir.join("a/important.log"),
+        dir.join("keep.txt"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+}
+
+#[cfg(feature = "ignore-files")]
+#[test]
+fn respect_ignore_file_cachedir_tag() {
+    let dir = Dir::tmp();
+    dir.mkdirp("cache/sub");
+    dir.mkdirp("fake");
+    dir.touch_all(&["cache/sub/a", "fake/b"]);
+    fs::write(
+        dir.join("cache/CACHEDIR.TAG"),
+        "Signature: 8a477f597d28d172789f06886806bc55\n# a cache\n",
+    )
+    .unwrap();
+    // Without the signature, the tag doesn't count.
+    fs::write(dir.join("fake/CACHEDIR.TAG"), "not a tag\n").unwrap();
+
+    let wd = WalkDir::new(dir.path()).respect_ignore_file("CACHEDIR.TAG");
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("fake"),
+        dir.join("fake/CACHEDIR.TAG"),
+        dir.join("fake/b"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+}
+
+#[cfg(feature = "ignore-files")]
+#[test]
+fn respect_ignore_file_any_name() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b");
+    dir.touch_all(&["a/.nowalk", "a/x", "b/y"]);
+
+    let wd = WalkDir::new(dir.path()).respect_ignore_file(".nowalk");
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected =
+        vec![dir.path().to_path_buf(), dir.join("b"), dir.join("b/y")];
+    assert_eq!(expected, r.sorted_paths());
+}
+
+#[test]
+#[cfg(any(unix, windows))]
+fn hard_link_map() {
+    use crate::HardLinkMap;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b");
+    dir.touch_all(&["a/file", "a/other"]);
+    fs::hard_link(dir.join("a/file"), dir.join("b/link1")).unwrap();
+    fs::hard_link(dir.join("a/file"), dir.join("b/link2")).unwrap();
+
+    let wd = WalkDir::new(dir.path()).sort_by_file_name();
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let mut links = HardLinkMap::new();
+    let mut dups = vec![];
+    for ent in r.ents() {
+        match ent.file_name().to_str().unwrap() {
+            "file" | "link1" | "link2" => assert_eq!(Some(3), ent.nlink()),
+            "other" => assert_eq!(Some(1), ent.nlink()),
+            _ => {}
+        }
+        if let Some(first) = links.insert(ent).unwrap() {
+            dups.push((ent.path().to_path_buf(), first.to_path_buf()));
+        }
+    }
+    let expected = vec![
+        (dir.join("b").join("link1"), dir.join("a").join("file")),
+        (dir.join("b").join("link2"), dir.join("a").join("file")),
+    ];
+    assert_eq!(expected, dups);
+    assert_eq!(1, links.len());
+
+    // The count is looked up once and then kept, clones included.
+    let other = r.ents().iter().find(|ent| ent.file_name() == "other");
+    let other = other.unwrap().clone();
+    fs::hard_link(dir.join("a/other"), dir.join("b/link3")).unwrap();
+    assert_eq!(Some(1), other.nlink());
+    let fresh = WalkDir::new(dir.join("a/other")).into_iter().next();
+    assert_eq!(Some(2), fresh.unwrap().unwrap().nlink());
+}
+
+#[test]
+#[cfg(unix)]
+fn normalize_paths() {
+    use std::ffi::OsString;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar/baz");
+    dir.touch("foo/bar/baz/a");
+
+    let mut root = OsString::from(dir.path());
+    root.push("//foo///bar");
+    let has_double = |p: &PathBuf| p.to_str().unwrap().contains("//");
+
+    let r = dir.run_recursive(WalkDir::new(&root));
+    r.assert_no_errors();
+    assert!(r.paths().iter().all(has_double));
+
+    let r = dir.run_recursive(WalkDir::new(&root).normalize_paths(true));
+    r.assert_no_errors();
+    let paths = r.paths();
+    assert!(!paths.iter().any(has_double), "{:?}", paths);
+    let expected = [
+        dir.join("foo/bar"),
+        dir.join("foo/bar/baz"),
+        dir.join("foo/bar/baz/a"),
+    ];
+    let got: Vec<String> =
+        r.sorted_paths().iter().map(|p| p.display().to_string()).collect();
+    let expected: Vec<String> =
+        expected.iter().map(|p| p.display().to_string()).collect();
+    assert_eq!(expected, got);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn estimated_child_count() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/1");
+    dir.mkdirp("a/2");
+    dir.mkdirp("a/3/nested");
+    dir.touch_all(&["a/file", "a/3/file"]);
+
+    let r = dir.run_recursive(WalkDir::new(dir.join("a")));
+    r.assert_no_errors();
+    let ents = r.sorted_ents();
+    // The temporary directory may live on a file system whose directory link
+    // counts aren't meaningful (e.g., btrfs).
+    if ents[0].estimated_child_count().is_none() {
+        return;
+    }
+    let counts: Vec<Option<u64>> =
+        ents.iter().map(|d| d.estimated_child_count()).collect();
+    // a, a/1, a/2, a/3, a/3/file, a/3/nested, a/file
+    let expected =
+        vec![Some(3), Some(0), Some(0), Some(1), None, Some(0), None];
+    assert_eq!(expected, counts);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn same_file_system_symlink_root() {
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    if !Path::new("/sys/kernel").is_dir() {
+        return;
+    }
+    let dir = Dir::tmp();
+    dir.symlink_dir("/sys/kernel", "root-link");
+    let sys_dev = fs::metadata("/sys/kernel").unwrap().dev();
+    assert_ne!(fs::metadata(dir.path()).unwrap().dev(), sys_dev);
+
+    for &follow_links in &[false, true] {
+        for &same_fs in &[false, true] {
+            let mut it = WalkDir::new(dir.join("root-link"))
+                .follow_links(follow_links)
+                .same_file_system(same_fs)
+                .max_depth(1)
+                .into_iter();
+            let root = it.next().unwrap().unwrap();
+            assert_eq!(!follow_links, root.file_type().is_symlink());
+            let expected = if same_fs { Some(sys_dev) } else { None };
+            assert_eq!(expected, it.stats().root_device());
+            assert_eq!(expected, root.device());
+
+            // The target is descended into in every case.
+            let children = it.filter_map(|r| r.ok()).count();
+            assert!(children > 0, "{} {}", follow_links, same_fs);
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn ino_source() {
+    use std::os::unix::fs::MetadataExt;
+
+    use crate::{DirEntryExt, InoSource};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.touch("a/b");
+    dir.symlink_file("a/b", "link");
+
+    let wd = WalkDir::new(dir.path()).follow_links(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    for ent in r.ents() {
+        let expected = if ent.depth() == 0 || ent.path_is_symlink() {
+            InoSource::Stat
+        } else {
+            InoSource::Dirent
+        };
+        assert_eq!(expected, ent.ino_source(), "{}", ent.path().display());
+        if ent.ino_source() == InoSource::Stat {
+            assert_eq!(fs::metadata(ent.path()).unwrap().ino(), ent.ino());
+        }
+    }
+
+    // Implementations outside of walkdir only need to provide `ino`.
+    struct Raw(u64);
+
+    impl DirEntryExt for Raw {
+        fn ino(&self) -> u64 {
+            self.0
+        }
+    }
+    assert_eq!(InoSource::Dirent, Raw(5).ino_source());
+    let err = Raw(5).is_executable().unwrap_err();
+    assert_eq!(std::io::ErrorKind::Unsupported, err.kind());
+}
+
+#[cfg(unix)]
+#[test]
+fn is_executable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::DirEntryExt;
+
+    let dir = Dir::tmp();
+    let modes = [
+        ("none", 0o644),
+        ("owner", 0o744),
+        ("group", 0o654),
+        ("other", 0o645),
+        ("only-other", 0o001),
+    ];
+    for &(name, mode) in &modes {
+        dir.touch(name);
+        let perms = fs::Permissions::from_mode(mode);
+        fs::set_permissions(dir.join(name), perms).unwrap();
+    }
+    dir.symlink_file("owner", "link");
+
+    let wd = WalkDir::new(dir.path()).min_depth(1).follow_links(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    for ent in r.ents() {
+        let expected = ent.file_name() != "none";
+        assert_eq!(
+            expected,
+            ent.is_executable().unwrap(),
+            "{}",
+            ent.path().display()
+        );
+    }
+    // The mode bits are all that matter, even if the owner can't execute
+    // the file.
+    let only_other =
+        r.ents().iter().find(|e| e.file_name() == "only-other").unwrap();
+    assert!(only_other.is_executable().unwrap());
+}
+
+#[test]
+fn validate() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+
+    WalkDir::new(dir.path()).validate().unwrap();
+    WalkDir::new(dir.join("a")).same_file_system(true).validate().unwrap();
+
+    let err = WalkDir::new(dir.join("missing")).validate().unwrap_err();
+    assert_eq!(Some(dir.join("missing").as_path()), err.path());
+    assert_eq!(0, err.depth());
+    let err = WalkDir::new(dir.join("missing"))
+        .same_file_system(true)
+        .validate()
+        .unwrap_err();
+    assert_eq!(Some(dir.join("missing").as_path()), err.path());
+}
+
+#[cfg(unix)]
+#[test]
+fn validate_broken_root_link() {
+    let dir = Dir::tmp();
+    dir.symlink_dir("missing", "link");
+
+    let err = WalkDir::new(dir.join("link")).validate().unwrap_err();
+    assert_eq!(Some(dir.join("link").as_path()), err.path());
+    WalkDir::new(dir.join("link"))
+        .follow_root_links(false)
+        .validate()
+        .unwrap();
+}
+
+#[test]
+fn try_into_iter() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.touch_all(&["a/b", "file"]);
+
+    let err = WalkDir::new(dir.join("missing")).try_into_iter().unwrap_err();
+    assert_eq!(Some(dir.join("missing").as_path()), err.path());
+    let kind = err.io_error().unwrap().kind();
+    assert_eq!(std::io::ErrorKind::NotFound, kind);
+
+    let err = WalkDir::new(dir.path())
+        .low_memory(true)
+        .sort_by_file_name()
+        .try_into_iter()
+        .unwrap_err();
+    assert_eq!(Some(("low_memory", "sort_by")), err.conflicting_options());
+
+    let it = WalkDir::new(dir.join("file")).try_into_iter().unwrap();
+    assert_eq!(0, it.stats().dirs_opened());
+    let paths: Vec<PathBuf> = it.map(|r| r.unwrap().into_path()).collect();
+    assert_eq!(vec![dir.join("file")], paths);
+
+    // The root is opened right away, and not again when it's yielded.
+    let mut it =
+        WalkDir::new(dir.path()).sort_by_file_name().try_into_iter().unwrap();
+    assert_eq!(1, it.stats().dirs_opened());
+    let root = it.next().unwrap().unwrap();
+    assert_eq!(dir.path(), root.path());
+    assert_eq!(0, root.depth());
+    assert!(root.file_type().is_dir());
+    assert_eq!(1, it.stats().dirs_opened());
+    let paths: Vec<PathBuf> =
+        it.by_ref().map(|r| r.unwrap().into_path()).collect();
+    let expected = vec![dir.join("a"), dir.join("a/b"), dir.join("file")];
+    assert_eq!(expected, paths);
+    assert_eq!(2, it.stats().dirs_opened());
+}
+
+#[cfg(unix)]
+#[test]
+fn try_into_iter_permission_denied() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("noread/child");
+    let noread = dir.join("noread");
+    let set_mode = |mode| {
+        fs::set_permissions(&noread, fs::Permissions::from_mode(mode)).unwrap()
+    };
+    set_mode(0o111);
+    // Privileged users can list the directory anyway.
+    if fs::read_dir(&noread).is_ok() {
+        set_mode(0o755);
+        return;
+    }
+
+    let result = WalkDir::new(&noread).try_into_iter();
+    // Unlike `into_iter`, the root isn't yielded before the error.
+    let lazy: Vec<bool> =
+        WalkDir::new(&noread).into_iter().map(|r| r.is_ok()).collect();
+    set_mode(0o755);
+    let err = result.unwrap_err();
+    assert!(err.is_enumeration_error());
+    assert_eq!(Some(noread.as_path()), err.path());
+    let kind = err.io_error().unwrap().kind();
+    assert_eq!(std::io::ErrorKind::PermissionDenied, kind);
+    assert_eq!(vec![true, false], lazy);
+}
+
+#[cfg(unix)]
+#[test]
+fn enumeration_error_ordering() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("noread/child");
+    let noread = dir.join("noread");
+    let set_mode = |mode| {
+        fs::set_permissions(&noread, fs::Permissions::from_mode(mode)).unwrap()
+    };
+    set_mode(0o111);
+    // Privileged users can list the directory anyway.
+    if fs::read_dir(&noread).is_ok() {
+        set_mode(0o755);
+        return;
+    }
+
+    let walk = |contents_first| {
+        WalkDir::new(dir.path())
+            .contents_first(contents_first)
+            .into_iter()
+            .map(|r| match r {
+                Ok(ent) => Ok(ent.into_path()),
+                Err(err) => {
+                    assert!(err.is_enumeration_error(), "{}", err);
+                    Err(err.path().map(|p| p.to_path_buf()))
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let pre_order = walk(false);
+    let post_order = walk(true);
+    set_mode(0o755);
+
+    let root = dir.path().to_path_buf();
+    let expected =
+        vec![Ok(root.clone()), Ok(noread.clone()), Err(Some(noread.clone()))];
+    assert_eq!(expected, pre_order);
+    let expected =
+        vec![Err(Some(noread.clone())), Ok(noread.clone()), Ok(root)];
+    assert_eq!(expected, post_order);
+
+    let err = WalkDir::new(dir.join("missing")).into_iter().next().unwrap();
+    assert!(!err.unwrap_err().is_enumeration_error());
+}
+
+#[test]
+fn checkpoint_resume() {
+    use crate::Checkpoint;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+    dir.mkdirp("a/d");
+    dir.mkdirp("e");
+    dir.touch_all(&["a/b/c/f1", "a/b/f2", "a/d/f3", "e/f4", "f5"]);
+
+    for &contents_first in &[false, true] {
+        let wd = || WalkDir::new(dir.path()).contents_first(contents_first);
+        let mut all: Vec<PathBuf> =
+            wd().into_iter().map(|r| r.unwrap().into_path()).collect();
+        for half in 0..=all.len() {
+            let mut it = wd().into_iter();
+            let mut got: Vec<PathBuf> = it
+                .by_ref()
+                .take(half)
+                .map(|r| r.unwrap().into_path())
+                .collect();
+            let checkpoint = it.checkpoint();
+            assert_eq!(half > 0, checkpoint.is_started());
+            let dirs =
+                checkpoint.dirs().map(|(p, n)| (p.to_path_buf(), n)).collect();
+            let checkpoint =
+                if half > 0 { Checkpoint::new(dirs) } else { checkpoint };
+            drop(it);
+
+            got.extend(
+                wd().resume(checkpoint).map(|r| r.unwrap().into_path()),
+            );
+            got.sort();
+            all.sort();
+            assert_eq!(all, got, "half: {}", half);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn sort_by_small_max_open_sorts_once() {
+    use std::sync::{Arc, Mutex};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a/x");
+    dir.mkdirp("a/y");
+    dir.mkdirp("b/z");
+    dir.touch_all(&["a/1", "a/2", "a/x/3", "a/x/4", "b/5", "b/z/6", "7"]);
+
+    let sorted_in = Arc::new(Mutex::new(vec![]));
+    let log = sorted_in.clone();
+    let wd = WalkDir::new(dir.path()).max_open(1).sort_by(move |a, b| {
+        let parent = a.path().parent().unwrap().to_path_buf();
+        let mut log = log.lock().unwrap();
+        if log.last() != Some(&parent) {
+            log.push(parent);
+        }
+        a.file_name().cmp(b.file_name())
+    });
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    // Every directory with more than one entry is sorted in one go, and
+    // none of them is sorted again after being closed.
+    let mut got = sorted_in.lock().unwrap().clone();
+    let passes = got.len();
+    got.sort();
+    got.dedup();
+    assert_eq!(got.len(), passes);
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a/x"),
+        dir.join("b"),
+    ];
+    assert_eq!(expected, got);
+}
+
+#[test]
+fn file_name_os() {
+    let dir = Dir::tmp();
+    dir.touch("foo");
+
+    let ent = WalkDir::new(dir.join("foo")).into_iter().next().unwrap();
+    let ent = ent.unwrap();
+    assert_eq!("foo", ent.file_name_os());
+    assert_eq!(ent.file_name(), ent.file_name_os());
+    // The name is borrowed from the entry's path, so it's the same memory
+    // every time.
+    let first = ent.file_name_os().to_str().unwrap().as_ptr();
+    let second = ent.file_name_os().to_str().unwrap().as_ptr();
+    assert_eq!(first, second);
+    let path = ent.path().as_os_str().to_str().unwrap();
+    assert_eq!(path[path.len() - 3..].as_ptr(), first);
+}
+
+#[test]
+fn into_stats_pending_dirs() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("c");
+    dir.touch_all(&["a/b/1", "a/b/2", "a/3", "c/4"]);
+
+    let mut it = WalkDir::new(dir.path()).sort_by_file_name().into_iter();
+    // root, a, a/3, a/b, a/b/1
+    for result in it.by_ref().take(5) {
+        result.unwrap();
+    }
+    assert_eq!(5, it.stats().entries());
+    assert_eq!(0, it.stats().errors());
+    let stats = it.into_stats();
+    assert_eq!(3, stats.dirs_opened());
+    let expected = vec![
+        (dir.path().to_path_buf(), 0),
+        (dir.join("a"), 1),
+        (dir.join("a/b"), 2),
+    ];
+    assert_eq!(expected, stats.pending_dirs());
+
+    let mut it = WalkDir::new(dir.path()).into_iter();
+    for result in it.by_ref() {
+        result.unwrap();
+    }
+    assert!(it.into_stats().pending_dirs().is_empty());
+
+    // Reading the rest from the back only counts what is yielded.
+    let mut it = WalkDir::new(dir.path()).sort_by_file_name().into_iter();
+    it.next().unwrap().unwrap();
+    it.next_back().unwrap().unwrap();
+    assert_eq!(2, it.stats().entries());
+    assert_eq!(4, it.stats().dirs_opened());
+    assert_eq!(8, it.count() + 2);
+}
+
+#[test]
+fn max_entries_per_dir() {
+    let dir = Dir::tmp();
+    dir.mkdirp("wide");
+    for i in 0..20 {
+        dir.mkdirp(format!("wide/{:02}", i));
+        dir.touch(format!("wide/{:02}/file", i));
+    }
+
+    let wd = WalkDir::new(dir.join("wide"))
+        .sort_by_file_name()
+        .max_entries_per_dir(Some(3));
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected = vec![
+        dir.join("wide"),
+        dir.join("wide/00"),
+        dir.join("wide/00/file"),
+        dir.join("wide/01"),
+        dir.join("wide/01/file"),
+        dir.join("wide/02"),
+        dir.join("wide/02/file"),
+    ];
+    assert_eq!(expected, r.paths());
+
+    let wd = WalkDir::new(dir.join("wide")).max_entries_per_dir(Some(3));
+    let r = dir.run_recursive(wd);
+    let children = r.ents().iter().filter(|ent| ent.depth() == 1).count();
+    assert_eq!(3, children);
+    assert_eq!(7, r.ents().len());
+}
+
+#[test]
+fn root_trailing_separators() {
+    use std::ffi::OsString;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch("foo/bar/baz");
+
+    let seps: &[&str] =
+        if cfg!(windows) { &["/", "//", "\\", "\\/\\"] } else { &["/", "//"] };
+    for sep in seps {
+        let mut root = OsString::from(dir.join("foo"));
+        root.push(sep);
+
+        let r = dir.run_recursive(WalkDir::new(&root));
+        r.assert_no_errors();
+        let got: Vec<OsString> =
+            r.paths().into_iter().map(PathBuf::into_os_string).collect();
+        let expected = vec![
+            root.clone(),
+            dir.join("foo").join("bar").into_os_string(),
+            dir.join("foo").join("bar").join("baz").into_os_string(),
+        ];
+        assert_eq!(expected, got, "root: {:?}", root);
+    }
+}
+
+#[test]
+fn borrowed_walkdir_twice() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch_all(&["foo/a", "foo/bar/b", "c", "d"]);
+
+    let wd = WalkDir::new(dir.path())
+        .max_open(1)
+        .sort_by(|a, b| b.file_name().cmp(a.file_name()));
+    let first: Vec<PathBuf> =
+        (&wd).into_iter().map(|r| r.unwrap().into_path()).collect();
+    let second: Vec<PathBuf> =
+        (&wd).into_iter().map(|r| r.unwrap().into_path()).collect();
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("foo"),
+        dir.join("foo/bar"),
+        dir.join("foo/bar/b"),
+        dir.join("foo/a"),
+        dir.join("d"),
+        dir.join("c"),
+    ];
+    assert_eq!(expected, first);
+    assert_eq!(first, second);
+    let consumed: Vec<PathBuf> =
+        wd.into_iter().map(|r| r.unwrap().into_path()).collect();
+    assert_eq!(first, consumed);
+}
+
+#[test]
+fn eviction_policy() {
+    use crate::EvictionPolicy;
+
+    let dir = Dir::tmp();
+    for top in &["a", "b", "c"] {
+        for mid in &["x", "y", "z"] {
+            let deep = format!("{}/{}/1/2/3", top, mid);
+            dir.mkdirp(&deep);
+            dir.touch(format!("{}/file", deep));
+            dir.touch(format!("{}/{}/file", top, mid));
+        }
+    }
+
+    let walk = |policy| {
+        let wd = WalkDir::new(dir.path()).max_open(2).eviction_policy(policy);
+        let r = dir.run_recursive(wd);
+        r.assert_no_errors();
+        r.sorted_paths()
+    };
+    let expected = dir.run_recursive(WalkDir::new(dir.path())).sorted_paths();
+    assert_eq!(expected.len(), 1 + 3 * (1 + 3 * 6));
+    assert_eq!(expected, walk(EvictionPolicy::Shallowest));
+    assert_eq!(expected, walk(EvictionPolicy::Deepest));
+}
+
+#[test]
+fn low_memory() {
+    use crate::tests::util::peak_allocated;
+
+    let dir = Dir::tmp();
+    for i in 0..100_000 {
+        if i % 1_000 == 0 {
+            let sub = format!("sub{}", i);
+            dir.mkdirp(&sub);
+            dir.touch(format!("{}/file", sub));
+        } else {
+            fs::File::create(dir.join(format!("file{}", i))).unwrap();
+        }
+    }
+
+    let walk = |low_memory| {
+        let wd = WalkDir::new(dir.path())
+            .max_open(1)
+            .contents_first(true)
+            .low_memory(low_memory);
+        let mut count = 0;
+        let peak = peak_allocated(|| {
+            for result in wd {
+                result.unwrap();
+                count += 1;
+            }
+        });
+        assert_eq!(1 + 100_000 + 100, count);
+        peak
+    };
+    // Once the root's handle is closed, its remaining entries fill up many
+    // megabytes. Otherwise, only the current path is kept.
+    let threshold = 1 << 20;
+    let peak = walk(false);
+    assert!(peak > threshold, "{} bytes", peak);
+    let peak = walk(true);
+    assert!(peak < threshold, "{} bytes", peak);
+
+    let wd = WalkDir::new(dir.path()).low_memory(true).sort_by_file_name();
+    let results: Vec<_> = wd.into_iter().collect();
+    assert_eq!(1, results.len());
+    let err = results.into_iter().next().unwrap().unwrap_err();
+    assert_eq!(Some(("low_memory", "sort_by")), err.conflicting_options());
+}
+
+#[cfg(unix)]
+#[test]
+fn group_symlinks() {
+    use crate::GroupOrder;
+
+    let dir = Dir::tmp();
+    dir.touch_all(&["b", "d"]);
+    dir.mkdirp("c");
+    dir.symlink_file("b", "a-link");
+    dir.symlink_dir("c", "e-link");
+
+    let names = |wd: WalkDir| -> Vec<String> {
+        let r = dir.run_recursive(wd.max_depth(1));
+        r.assert_no_errors();
+        r.ents()[1..]
+            .iter()
+            .map(|ent| ent.file_name().to_str().unwrap().to_string())
+            .collect()
+    };
+
+    let wd = WalkDir::new(dir.path()).group_symlinks(GroupOrder::Last);
+    assert_eq!(names(wd), vec!["b", "c", "d", "a-link", "e-link"]);
+    let wd = WalkDir::new(dir.path()).group_symlinks(GroupOrder::First);
+    assert_eq!(names(wd), vec!["a-link", "e-link", "b", "c", "d"]);
+
+    // Grouping comes before the sorter, which orders each group.
+    let wd = WalkDir::new(dir.path())
+        .group_symlinks(GroupOrder::Last)
+        .follow_links(true)
+        .sort_by(|a, b| b.file_name().cmp(a.file_name()));
+    assert_eq!(names(wd), vec!["d", "c", "b", "e-link", "a-link"]);
+}
+
+#[test]
+fn into_components() {
+    use std::ffi::OsString;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch("foo/bar/baz");
+
+    let ent = WalkDir::new(dir.join("foo"))
+        .min_depth(2)
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap();
+    let components = ent.into_components();
+    let n = components.len();
+    assert!(n > 3);
+    let expected: Vec<OsString> =
+        vec!["foo".into(), "bar".into(), "baz".into()];
+    assert_eq!(expected, components[n - 3..]);
+    let mut root = PathBuf::new();
+    root.extend(&components[..n - 3]);
+    assert_eq!(dir.path(), root);
+}
+
+#[test]
+fn into_path_is_a_move() {
+    let dir = Dir::tmp();
+    dir.mkdirp("foo");
+    dir.touch("foo/bar");
+
+    for result in WalkDir::new(dir.path()) {
+        let ent = result.unwrap();
+        let ptr = ent.path().to_str().unwrap().as_ptr();
+        let capacity = ent.path_buf_capacity();
+        let path = ent.into_path();
+        assert_eq!(ptr, path.to_str().unwrap().as_ptr());
+        assert_eq!(capacity, path.capacity());
+    }
+}
+
+#[test]
+fn on_progress() {
+    use std::sync::{Arc, Mutex};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("foo/bar");
+    dir.touch_all(&["foo/a", "foo/bar/b", "c", "d"]);
+
+    let reports = Arc::new(Mutex::new(vec![]));
+    let log = reports.clone();
+    let wd = WalkDir::new(dir.path())
+        .on_progress(2, move |progress| log.lock().unwrap().push(progress));
+    let mut it = wd.into_iter();
+    for result in it.by_ref() {
+        result.unwrap();
+    }
+    assert!(it.next().is_none());
+
+    let reports = reports.lock().unwrap();
+    let yielded: Vec<u64> =
+        reports.iter().map(|p| p.entries_yielded()).collect();
+    assert_eq!(vec![2, 4, 6, 7], yielded);
+    let last = reports.last().unwrap();
+    assert_eq!(3, last.dirs_entered());
+    assert_eq!(0, last.errors());
+}
+
+#[test]
+fn unsupported_option() {
+    use crate::Error;
+
+    let dir = Dir::tmp();
+    dir.touch("foo");
+
+    let supported = WalkDir::supports_same_file_system();
+    assert_eq!(cfg!(any(unix, windows)), supported);
+    let wd = WalkDir::new(dir.path()).same_file_system(true);
+    if supported {
+        wd.validate().unwrap();
+        let r = dir.run_recursive(wd);
+        r.assert_no_errors();
+        assert_eq!(2, r.ents().len());
+    } else {
+        let err = wd.validate().unwrap_err();
+        assert_eq!(Some("same_file_system"), err.unsupported_option());
+    }
+
+    let err = Error::from_unsupported_option("same_file_system");
+    assert_eq!(Some("same_file_system"), err.unsupported_option());
+    assert_eq!(0, err.depth());
+    assert!(err.path().is_none() && err.io_error().is_none());
+    assert_eq!(
+        "the same_file_system option is not supported on this platform",
+        err.to_string()
+    );
+    let err = std::io::Error::from(err);
+    assert_eq!(std::io::ErrorKind::Unsupported, err.kind());
+}
+
+#[test]
+fn sort_by_with_context() {
+    use std::sync::{Arc, Mutex};
+
+    let dir = Dir::tmp();
+    dir.mkdirp("src");
+    dir.mkdirp("assets");
+    dir.touch_all(&["src/b.rs", "src/c.md", "src/a.txt"]);
+    dir.touch_all(&["assets/a.png", "assets/b.css", "assets/c.avi"]);
+    #[cfg(unix)]
+    dir.symlink_dir("assets", "linked");
+
+    let contexts = Arc::new(Mutex::new(vec![]));
+    let log = contexts.clone();
+    let wd = WalkDir::new(dir.path()).follow_links(true).sort_by_with_context(
+        move |ctx, a, b| {
+            log.lock()
+                .unwrap()
+                .push((ctx.parent().to_path_buf(), ctx.depth()));
+            assert_eq!(Some(ctx.parent()), a.path().parent());
+            if ctx.parent().file_name() == Some("assets".as_ref()) {
+                a.path().extension().cmp(&b.path().extension())
+            } else {
+                a.file_name().cmp(b.file_name())
+            }
+        },
+    );
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let mut expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("assets"),
+        dir.join("assets/c.avi"),
+        dir.join("assets/b.css"),
+        dir.join("assets/a.png"),
+    ];
+    if cfg!(unix) {
+        expected.extend(vec![
+            // Sorted by name, since the directory is named by the link.
+            dir.join("linked"),
+            dir.join("linked/a.png"),
+            dir.join("linked/b.css"),
+            dir.join("linked/c.avi"),
+        ]);
+    }
+    expected.extend(vec![
+        dir.join("src"),
+        dir.join("src/a.txt"),
+        dir.join("src/b.rs"),
+        dir.join("src/c.md"),
+    ]);
+    let got = r.paths();
+    assert_eq!(expected, got);
+
+    let mut contexts = contexts.lock().unwrap().clone();
+    contexts.sort();
+    contexts.dedup();
+    let mut expected =
+        vec![(dir.path().to_path_buf(), 0), (dir.join("assets"), 1)];
+    if cfg!(unix) {
+        expected.push((dir.join("linked"), 1));
+    }
+    expected.push((dir.join("src"), 1));
+    assert_eq!(expected, contexts);
+}
+
+#[test]
+fn root_with_parent_components() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b/c");
+    dir.touch("b/c/d");
+    dir.symlink_dir("b", "b/c/up");
+
+    // Entries are joined onto the root as is, so `..` components in the
+    // root are kept and the depth counts from the root.
+    let root = dir.join("a").join("..").join("b");
+    let r = dir.run_recursive(WalkDir::new(&root));
+    r.assert_no_errors();
+    let got: Vec<_> = r
+        .sorted_ents()
+        .into_iter()
+        .map(|ent| (ent.path().to_path_buf(), ent.depth()))
+        .collect();
+    let expected = vec![
+        (root.clone(), 0),
+        (root.join("c"), 1),
+        (root.join("c").join("d"), 2),
+        (root.join("c").join("up"), 2),
+    ];
+    assert_eq!(expected, got);
+
+    // A directory reached by going up from the root is still detected as
+    // a loop.
+    let wd = WalkDir::new(root.join("c").join("..")).follow_links(true);
+    let r = dir.run_recursive(wd);
+    assert_eq!(1, r.errs().len(), "{:?}", r.errs());
+    let err = &r.errs()[0];
+    assert_eq!(Some(&*root.join("c").join("..")), err.loop_ancestor());
+
+    // Collapsing separators leaves `..` alone.
+    let sep = std::path::MAIN_SEPARATOR;
+    let root = format!(
+        "{}{sep}a{sep}{sep}..{sep}{sep}b",
+        dir.path().display(),
+        sep = sep
+    );
+    let wd = WalkDir::new(&root).normalize_paths(true).max_depth(0);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert_eq!(vec![dir.join("a").join("..").join("b")], r.paths());
+
+    // The root is still yielded last when contents come first.
+    let wd = WalkDir::new(&root).contents_first(true).sort_by_file_name();
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let last = r.ents().last().unwrap();
+    assert_eq!(0, last.depth());
+    assert_eq!(std::path::Path::new(&root), last.path());
+}
+
+#[cfg(unix)]
+#[test]
+fn absolute_max_depth() {
+    let dir = Dir::tmp();
+    // A chain of directories linked to one another, which is as deep as it
+    // is long when links are followed, but has no loop.
+    for i in 0..10 {
+        dir.mkdirp(format!("chain/c{}", i));
+    }
+    for i in 0..9 {
+        dir.symlink_dir(
+            format!("chain/c{}", i + 1),
+            format!("chain/c{}/next", i),
+        );
+    }
+    dir.symlink_dir("chain/c0", "start");
+
+    let wd = WalkDir::new(dir.join("start")).follow_links(true);
+    let r = dir.run_recursive(wd.absolute_max_depth(5));
+    let mut deepest = dir.join("start");
+    for _ in 0..5 {
+        deepest.push("next");
+    }
+    assert_eq!(6, r.ents().len());
+    assert_eq!(Some(deepest.as_path()), r.ents().last().map(|e| e.path()));
+    assert_eq!(1, r.errs().len());
+    let err = &r.errs()[0];
+    assert!(err.is_depth_limit());
+    assert_eq!(Some(deepest.as_path()), err.path());
+    assert_eq!(5, err.depth());
+
+    // Without the limit, the whole chain is walked.
+    let wd = WalkDir::new(dir.join("start")).follow_links(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert_eq!(10, r.ents().len());
+
+    // A tighter max_depth quietly wins.
+    let wd = WalkDir::new(dir.join("start")).follow_links(true);
+    let r = dir.run_recursive(wd.max_depth(5).absolute_max_depth(5));
+    r.assert_no_errors();
+    assert_eq!(6, r.ents().len());
+}
+
+#[test]
+fn root_relative_special() {
+    // These roots are relative to the current directory, which is the root
+    // of this crate when running tests.
+    let sep = std::path::MAIN_SEPARATOR;
+    let walk = |root: &str| -> Vec<(String, usize)> {
+        let wd = WalkDir::new(root).max_depth(1).sort_by_file_name();
+        wd.into_iter()
+            .map(|r| {
+                let ent = r.unwrap();
+                (ent.path().to_str().unwrap().to_string(), ent.depth())
+            })
+            .collect()
+    };
+
+    for &root in &[".", "./"] {
+        let got = walk(root);
+        assert_eq!((root.to_string(), 0), got[0]);
+        assert!(got[1..].iter().all(|(p, depth)| {
+            *depth == 1 && p.starts_with(&format!(".{}", sep))
+        }));
+        let manifest = format!(".{}Cargo.toml", sep);
+        assert!(got.contains(&(manifest, 1)), "{:?}", got);
+    }
+
+    let got = walk("..");
+    assert_eq!(("..".to_string(), 0), got[0]);
+    let cwd = std::env::current_dir().unwrap();
+    let name = cwd.file_name().unwrap().to_str().unwrap();
+    assert!(got.contains(&(format!("..{}{}", sep, name), 1)), "{:?}", got);
+
+    let results: Vec<_> = WalkDir::new("").into_iter().collect();
+    assert_eq!(1, results.len());
+    let err = results.into_iter().next().unwrap().unwrap_err();
+    assert_eq!(
+        std::io::ErrorKind::InvalidInput,
+        err.io_error().unwrap().kind()
+    );
+    assert!(WalkDir::new("").validate().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn root_trailing_separator_not_a_directory() {
+    let dir = Dir::tmp();
+    dir.touch("foo");
+    dir.mkdirp("bar");
+    dir.symlink_dir("bar", "barlink");
+
+    let root = dir.path().join("foo/");
+    let results: Vec<_> = WalkDir::new(&root).into_iter().collect();
+    assert_eq!(1, results.len());
+    let err = results.into_iter().next().unwrap().unwrap_err();
+    assert_eq!(Some(root.as_path()), err.path());
+    // ENOTDIR
+    assert_eq!(Some(20), err.io_error().unwrap().raw_os_error());
+    assert!(WalkDir::new(&root).validate().is_err());
+
+    let r = dir.run_recursive(WalkDir::new(dir.join("foo")));
+    r.assert_no_errors();
+    assert_eq!(vec![dir.join("foo")], r.paths());
+    let r = dir.run_recursive(WalkDir::new(dir.path().join("barlink/")));
+    r.assert_no_errors();
+}
+
+#[test]
+fn sort_by_file_name_raw_order() {
+    use std::ffi::OsString;
+
+    let dir = Dir::tmp();
+    let mut names: Vec<OsString> = vec![
+        "B".into(),
+        "a".into(),
+        "C".into(),
+        "_x".into(),
+        "\u{e9}".into(),
+        "\u{ff21}".into(),
+        "\u{1f600}".into(),
+    ];
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        names.push(OsString::from_vec(vec![b'a', 0xFF]));
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStringExt;
+        names.push(OsString::from_wide(&[b'a' as u16, 0xD800]));
+    }
+    for name in &names {
+        dir.touch(name);
+    }
+
+    let sorted = |wd: WalkDir| -> Vec<OsString> {
+        let r = dir.run_recursive(wd.min_depth(1));
+        r.assert_no_errors();
+        r.ents().iter().map(|ent| ent.file_name().to_os_string()).collect()
+    };
+    let invalid = names.pop().unwrap();
+    // By UTF-16 code unit, U+1F600 is encoded with surrogates, which come
+    // before U+FF21. By byte, it's the other way around.
+    let non_ascii: Vec<OsString> = if cfg!(windows) {
+        vec!["\u{e9}".into(), "\u{1f600}".into(), "\u{ff21}".into()]
+    } else {
+        vec!["\u{e9}".into(), "\u{ff21}".into(), "\u{1f600}".into()]
+    };
+    let mut expected: Vec<OsString> =
+        vec!["B".into(), "C".into(), "_x".into(), "a".into(), invalid.clone()];
+    expected.extend(non_ascii.clone());
+    let mut expected_ci: Vec<OsString> =
+        vec!["_x".into(), "a".into(), invalid, "B".into(), "C".into()];
+    expected_ci.extend(non_ascii);
+    let wd = WalkDir::new(dir.path());
+    assert_eq!(expected, sorted(wd.sort_by_file_name()));
+    let wd = WalkDir::new(dir.path());
+    assert_eq!(expected_ci, sorted(wd.sort_by_file_name_lossy_ci()));
+}
+
+#[cfg(unix)]
+#[test]
+fn same_file() {
+    let dir = Dir::tmp();
+    dir.touch_all(&["a", "c"]);
+    fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+
+    let ents = |same_file_system| {
+        let wd = WalkDir::new(dir.path())
+            .same_file_system(same_file_system)
+            .sort_by_file_name()
+            .min_depth(1);
+        let r = dir.run_recursive(wd);
+        r.assert_no_errors();
+        r.ents().to_vec()
+    };
+    let with_dev = ents(true);
+    assert!(with_dev[0].same_file(&with_dev[1]));
+    assert!(with_dev[1].same_file(&with_dev[0]));
+    assert!(!with_dev[0].same_file(&with_dev[2]));
+
+    let without_dev = ents(false);
+    assert!(!without_dev[0].same_file(&without_dev[1]));
+}
+
+#[cfg(feature = "glob")]
+#[test]
+fn new_glob() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/tests");
+    dir.mkdirp("b/tests");
+    dir.mkdirp("c/other");
+    dir.touch_all(&["a/tests/x", "b/tests/y", "c/other/z"]);
+
+    let pattern = dir.path().join("*").join("tests");
+    let wd = WalkDir::new_glob(pattern.to_str().unwrap()).unwrap();
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+
+    let expected = vec![
+        dir.join("a/tests"),
+        dir.join("a/tests/x"),
+        dir.join("b/tests"),
+        dir.join("b/tests/y"),
+    ];
+    assert_eq!(expected, r.paths());
+    assert_eq!(2, r.ents().iter().filter(|e| e.depth() == 0).count());
+
+    let wd = WalkDir::new_glob(dir.join("*/nope").to_str().unwrap()).unwrap();
+    assert_eq!(0, wd.into_iter().count());
+}
+
+#[test]
+fn reset() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("c");
+    dir.touch_all(&["a/b/x", "a/y", "c/z"]);
+
+    let wd = WalkDir::new(dir.path()).sort_by_file_name().max_open(1);
+    let mut it = wd.into_iter();
+    let first: Vec<PathBuf> =
+        it.by_ref().map(|r| r.unwrap().into_path()).collect();
+    let stats = it.stats().clone();
+    assert_eq!(7, first.len());
+
+    it.reset();
+    assert_eq!(0, it.stats().entries());
+    let second: Vec<PathBuf> =
+        it.by_ref().map(|r| r.unwrap().into_path()).collect();
+    assert_eq!(first, second);
+    assert_eq!(&stats, it.stats());
+
+    // Resetting part of the way through starts over, too.
+    it.reset();
+    assert_eq!(3, it.by_ref().take(3).count());
+    it.reset();
+    let third: Vec<PathBuf> = it.map(|r| r.unwrap().into_path()).collect();
+    assert_eq!(first, third);
+}
+
+#[test]
+fn fs_capabilities() {
+    use crate::capabilities::PROBES;
+    use crate::fs_capabilities;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b");
+
+    // Concurrent calls from different threads only probe once.
+    let probes = || PROBES.load(std::sync::atomic::Ordering::SeqCst);
+    let before = probes();
+    let threads: Vec<_> = ["a", "b"]
+        .iter()
+        .map(|name| {
+            let path = dir.join(name);
+            std::thread::spawn(move || fs_capabilities(path).unwrap())
+        })
+        .collect();
+    let caps: Vec<_> =
+        threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert_eq!(caps[0], caps[1]);
+    let caps = caps[0];
+    assert_eq!(before + 1, probes());
+    // The probe cleans up after itself.
+    assert_eq!(0, fs::read_dir(dir.join("a")).unwrap().count());
+    assert_eq!(0, fs::read_dir(dir.join("b")).unwrap().count());
+
+    // Later calls use the cached result too.
+    assert_eq!(caps, fs_capabilities(dir.join("b")).unwrap());
+    assert_eq!(before + 1, probes());
+
+    #[cfg(target_os = "linux")]
+    {
+        assert!(caps.symlinks());
+        assert!(caps.case_sensitive());
+        assert!(caps.supports_hardlinks());
+    }
+
+    assert!(fs_capabilities(dir.join("missing")).is_err());
+}
+
+#[cfg(any(unix, windows))]
+#[test]
+fn normalized_key() {
+    let dir = Dir::tmp();
+    dir.mkdirp("Foo");
+    dir.touch_all(&["Foo/a", "b"]);
+    fs::hard_link(dir.join("Foo/a"), dir.join("Foo/c")).unwrap();
+
+    let key_of = |path: PathBuf| {
+        let mut it = WalkDir::new(path).into_iter();
+        it.next().unwrap().unwrap().normalized_key().unwrap()
+    };
+    let foo = key_of(dir.join("Foo"));
+    assert_eq!(key_of(dir.join("Foo/a")), key_of(dir.join("Foo/c")));
+    assert_ne!(key_of(dir.join("Foo/a")), key_of(dir.join("b")));
+    assert_ne!(foo, key_of(dir.join("b")));
+
+    let r = dir.run_recursive(WalkDir::new(dir.path()).sort_by_file_name());
+    r.assert_no_errors();
+    assert_eq!(foo, r.ents()[1].normalized_key().unwrap());
+
+    // Only a case-insensitive file system lets `Foo` be reached as `foo`.
+    if !crate::fs_capabilities(dir.path()).unwrap().case_sensitive() {
+        assert_eq!(foo, key_of(dir.join("foo")));
+    }
+}
+
+#[test]
+fn skip_dirs_case_insensitive() {
+    let dir = Dir::tmp();
+    dir.mkdirp("Node_Modules/pkg");
+    dir.mkdirp("src/node_modules");
+    dir.mkdirp("src/NODE_MODULES_OLD");
+    dir.touch_all(&["Node_Modules/pkg/a", "src/b", "node_modules.txt"]);
+
+    let wd = WalkDir::new(dir.join("Node_Modules"))
+        .skip_dirs_case_insensitive(["node_modules"]);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    // The root is never skipped.
+    assert_eq!(3, r.ents().len());
+
+    let wd =
+        WalkDir::new(dir.path()).skip_dirs_case_insensitive(["node_modules"]);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("node_modules.txt"),
+        dir.join("src"),
+        dir.join("src/NODE_MODULES_OLD"),
+        dir.join("src/b"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+}
+
+#[test]
+fn max_depth_zero() {
+    let dir = Dir::tmp();
+    dir.mkdirp("dir/sub");
+    dir.touch_all(&["file", "dir/a"]);
+    dir.symlink_dir("dir", "link");
+
+    for &root in &["file", "dir", "link"] {
+        for &contents_first in &[false, true] {
+            for &sorted in &[false, true] {
+                let mut wd = WalkDir::new(dir.join(root))
+                    .max_depth(0)
+                    .contents_first(contents_first);
+                if sorted {
+                    wd = wd.sort_by_file_name();
+                }
+                let mut it = wd.into_iter();
+                let mut paths = vec![];
+                for result in it.by_ref() {
+                    paths.push(result.unwrap().into_path());
+                }
+                let case = format!(
+                    "root: {}, contents_first: {}, sorted: {}",
+                    root, contents_first, sorted
+                );
+                assert_eq!(vec![dir.join(root)], paths, "{}", case);
+                assert_eq!(0, it.stats().dirs_opened(), "{}", case);
+            }
+        }
+    }
+
+    // Directories at the maximum depth aren't opened either.
+    let mut it = WalkDir::new(dir.path()).max_depth(1).into_iter();
+    assert_eq!(4, it.by_ref().count());
+    assert_eq!(1, it.stats().dirs_opened());
+}
+
+#[test]
+fn rev() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.mkdirp("c");
+    dir.touch_all(&["a/b/x", "a/y", "c/z", "d"]);
+
+    let wd = || WalkDir::new(dir.path()).sort_by_file_name();
+    let forward: Vec<PathBuf> =
+        wd().into_iter().map(|r| r.unwrap().into_path()).collect();
+    let mut backward: Vec<PathBuf> =
+        wd().into_iter().rev().map(|r| r.unwrap().into_path()).collect();
+    backward.reverse();
+    assert_eq!(forward, backward);
+
+    // Both ends can be mixed, even after walking forward for a bit.
+    let mut it = wd().into_iter();
+    let first = it.next().unwrap().unwrap().into_path();
+    let last = it.next_back().unwrap().unwrap().into_path();
+    let mut middle: Vec<PathBuf> =
+        it.by_ref().map(|r| r.unwrap().into_path()).collect();
+    assert!(it.next_back().is_none());
+    middle.insert(0, first);
+    middle.push(last);
+    assert_eq!(forward, middle);
+
+    // A checkpoint taken after reading from the back doesn't lose the
+    // entries that were read ahead but not yielded yet.
+    let mut it = wd().into_iter();
+    let mut got: Vec<PathBuf> =
+        it.by_ref().take(3).map(|r| r.unwrap().into_path()).collect();
+    let before = it.checkpoint();
+    got.push(it.next_back().unwrap().unwrap().into_path());
+    let checkpoint = it.checkpoint();
+    assert_eq!(before, checkpoint);
+    assert!(checkpoint.dirs().next().is_some());
+    drop(it);
+    got.extend(wd().resume(checkpoint).map(|r| r.unwrap().into_path()));
+    got.sort();
+    got.dedup();
+    let mut all = forward.clone();
+    all.sort();
+    assert_eq!(all, got);
+}
+
+#[test]
+fn walk_capabilities() {
+    use crate::capabilities::{walk_capabilities, Platform};
+    use crate::Support::*;
+
+    let report = |wd: &WalkDir, platform| {
+        walk_capabilities(&wd.opts, platform).iter().collect::<Vec<_>>()
+    };
+
+    // Nothing platform dependent is enabled by default.
+    let wd = WalkDir::new("foo");
+    for platform in [Platform::Unix, Platform::Windows, Platform::Other] {
+        assert_eq!(report(&wd, platform), vec![]);
+    }
+
+    let wd = WalkDir::new("foo")
+        .same_file_system(true)
+        .cross_fs_via_symlink(true)
+        .dedup_hard_links(true);
+    let cross_fs = Degraded(
+        "no effect unless same_file_system and follow_links are enabled",
+    );
+    let expected = vec![
+        ("same_file_system", Supported),
+        ("cross_fs_via_symlink", cross_fs),
+        ("dedup_hard_links", Supported),
+    ];
+    assert_eq!(report(&wd, Platform::Unix), expected);
+    assert_eq!(report(&wd, Platform::Windows), expected);
+    let expected = vec![
+        ("same_file_system", Unsupported),
+        ("cross_fs_via_symlink", Unsupported),
+        ("dedup_hard_links", Unsupported),
+    ];
+    assert_eq!(report(&wd, Platform::Other), expected);
+
+    let wd = wd.follow_links(true).follow_mounts(false);
+    let expected = vec![
+        ("same_file_system", Supported),
+        ("cross_fs_via_symlink", Supported),
+        ("follow_links", Supported),
+        ("follow_mounts", Supported),
+        ("dedup_hard_links", Supported),
+    ];
+    assert_eq!(report(&wd, Platform::Unix), expected);
+    let max_open = Degraded(
+        "not respected on Windows when follow_links is enabled or \
+         follow_mounts is disabled",
+    );
+    let mut windows = expected.clone();
+    windows.push(("max_open", max_open));
+    assert_eq!(report(&wd, Platform::Windows), windows);
+    assert!(walk_capabilities(&wd.opts, Platform::Other)
+        .iter()
+        .all(|(_, support)| support == Unsupported));
+
+    // The report for this platform agrees with the iterator.
+    let wd = WalkDir::new("foo").same_file_system(true);
+    let caps = wd.capabilities();
+    assert_eq!(caps.all_supported(), WalkDir::supports_same_file_system());
+    assert_eq!(caps.get("dedup_hard_links"), None);
+}
+
+#[cfg(feature = "tree")]
+#[test]
+fn tree_round_trip() {
+    use crate::{Tree, TreeDiff};
+
+    let file = |name: &str| Tree::File(name.into());
+    let tree = Tree::Dir(
+        "root".into(),
+        vec![
+            file("a"),
+            Tree::Dir(
+                "b".into(),
+                vec![file("c"), Tree::Dir("d".into(), vec![])],
+            ),
+            Tree::Symlink { name: "e".into(), target: "b".into(), dir: true },
+            file("f"),
+        ],
+    );
+    let dir = Dir::tmp();
+    tree.create_in(dir.path()).unwrap();
+    assert!(tree.create_in(dir.path()).is_err());
+
+    let walked = Tree::from_walk(WalkDir::new(dir.join("root"))).unwrap();
+    assert_eq!(tree, walked);
+    assert!(tree.diff(&walked).is_empty());
+
+    // The root's own name and min_depth don't matter.
+    let wd = WalkDir::new(dir.join("root").join("b")).min_depth(1);
+    let walked = Tree::from_walk(wd).unwrap();
+    assert_eq!(
+        Tree::Dir("b".into(), vec![file("c"), Tree::Dir("d".into(), vec![])]),
+        walked
+    );
+
+    // Markers for the ends of directories aren't part of the tree.
+    let wd = WalkDir::new(dir.join("root")).emit_dir_close(true);
+    assert_eq!(tree, Tree::from_walk(wd).unwrap());
+
+    // Following links turns the link into a copy of its target, which
+    // shows up as a change of kind.
+    let wd = WalkDir::new(dir.join("root")).follow_links(true);
+    let walked = Tree::from_walk(wd).unwrap();
+    assert_eq!(vec![TreeDiff::Changed("e".into())], tree.diff(&walked));
+}
+
+#[cfg(feature = "tree")]
+#[test]
+fn tree_diff() {
+    use crate::{Tree, TreeDiff};
+
+    let file = |name: &str| Tree::File(name.into());
+    let dir = |name: &str, children| Tree::Dir(name.into(), children);
+    let link = |name: &str, target: &str| Tree::Symlink {
+        name: name.into(),
+        target: target.into(),
+        dir: false,
+    };
+
+    let ours = dir(
+        "x",
+        vec![
+            file("a"),
+            dir("b", vec![file("c"), file("d")]),
+            file("e"),
+            link("f", "a"),
+            link("g", "a"),
+        ],
+    );
+    let theirs = dir(
+        "y",
+        vec![
+            dir("a", vec![file("z")]),
+            dir("b", vec![file("bb"), file("c")]),
+            link("f", "a"),
+            link("g", "e"),
+            file("h"),
+        ],
+    );
+    let expected = vec![
+        TreeDiff::Changed("a".into()),
+        TreeDiff::Added(PathBuf::from("b").join("bb")),
+        TreeDiff::Removed(PathBuf::from("b").join("d")),
+        TreeDiff::Removed("e".into()),
+        TreeDiff::Changed("g".into()),
+        TreeDiff::Added("h".into()),
+    ];
+    assert_eq!(expected, ours.diff(&theirs));
+    assert!(ours.diff(&ours).is_empty());
+    assert_eq!(vec![TreeDiff::Changed("".into())], ours.diff(&file("x")));
+}
+
+#[cfg(feature = "tree")]
+#[test]
+fn tree_diff_unsorted() {
+    use crate::{Tree, TreeDiff};
+
+    let file = |name: &str| Tree::File(name.into());
+    let dir = |name: &str, children| Tree::Dir(name.into(), children);
+
+    // Built by hand, with children out of order.
+    let tree =
+        dir("root", vec![dir("b", vec![file("d"), file("c")]), file("a")]);
+    let tmp = Dir::tmp();
+    tree.create_in(tmp.path()).unwrap();
+    let walked = Tree::from_walk(WalkDir::new(tmp.join("root"))).unwrap();
+    assert_ne!(tree, walked);
+    assert!(tree.diff(&walked).is_empty());
+    assert!(walked.diff(&tree).is_empty());
+
+    let other = dir("root", vec![file("e"), dir("b", vec![file("c")])]);
+    let expected = vec![
+        TreeDiff::Removed("a".into()),
+        TreeDiff::Removed(PathBuf::from("b").join("d")),
+        TreeDiff::Added("e".into()),
+    ];
+    assert_eq!(expected, tree.diff(&other));
+}
+
+#[test]
+fn retry_transient() {
+    use crate::util::TRANSIENT_ERRORS;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.touch("a/b");
+    let expected =
+        vec![dir.path().to_path_buf(), dir.join("a"), dir.join("a/b")];
+
+    // Opening the root fails once, and then succeeds on the second attempt.
+    TRANSIENT_ERRORS.with(|errs| errs.set(1));
+    let wd = WalkDir::new(dir.path()).retry_transient(1);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert_eq!(expected, r.sorted_paths());
+    assert_eq!(0, TRANSIENT_ERRORS.with(|errs| errs.get()));
+
+    // Without enough retries, the error is yielded.
+    TRANSIENT_ERRORS.with(|errs| errs.set(2));
+    let wd = WalkDir::new(dir.path()).retry_transient(1);
+    let r = dir.run_recursive(wd);
+    assert_eq!(1, r.errs().len());
+    let err = r.errs()[0].io_error().unwrap();
+    assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+    assert_eq!(vec![dir.path().to_path_buf()], r.sorted_paths());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn transient_os_errors() {
+    use crate::util::is_transient_os_error;
+
+    assert!(is_transient_os_error(libc::EIO));
+    assert!(is_transient_os_error(libc::ESTALE));
+    assert!(!is_transient_os_error(libc::ENOENT));
+    assert!(!is_transient_os_error(libc::EACCES));
+}
+
+#[test]
+fn stop_dir_on_error() {
+    use crate::util::READ_ERROR_AFTER;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.touch_all(&["a/1", "a/2", "a/3", "a/4", "a/5"]);
+
+    // Reading `a` from the root, and then two of its entries, succeeds.
+    // Reading the third entry of `a` fails.
+    let walk = |stop| {
+        READ_ERROR_AFTER.with(|after| after.set(Some(3)));
+        let r = dir
+            .run_recursive(WalkDir::new(dir.path()).stop_dir_on_error(stop));
+        assert_eq!(None, READ_ERROR_AFTER.with(|after| after.get()));
+        r
+    };
+
+    let r = walk(false);
+    assert_eq!(1, r.errs().len());
+    assert!(r.errs()[0].is_enumeration_error());
+
+    let r = walk(true);
+    r.assert_no_errors();
+    let paths = r.sorted_paths();
+    assert_eq!(4, paths.len());
+    assert_eq!(dir.path(), paths[0]);
+    assert_eq!(dir.join("a"), paths[1]);
+    assert!(paths[2..].iter().all(|p| p.parent() == Some(&dir.join("a"))));
+}
+
+#[test]
+fn errors_last() {
+    use crate::util::READ_ERROR_AFTER;
+
+    let dir = Dir::tmp();
+    dir.touch_all(&["x", "y", "z"]);
+
+    // Reading the second entry of the root fails.
+    let walk = |errors_last| {
+        READ_ERROR_AFTER.with(|after| after.set(Some(1)));
+        WalkDir::new(dir.path())
+            .min_depth(1)
+            .sort_by_file_name()
+            .errors_last(errors_last)
+            .into_iter()
+            .map(|r| match r {
+                Ok(ent) => Ok(ent.file_name().to_os_string()),
+                Err(err) => Err(err.depth()),
+            })
+            .collect::<Vec<_>>()
+    };
+    let expected =
+        vec![Err(1), Ok("x".into()), Ok("y".into()), Ok("z".into())];
+    assert_eq!(expected, walk(false));
+    let expected =
+        vec![Ok("x".into()), Ok("y".into()), Ok("z".into()), Err(1)];
+    assert_eq!(expected, walk(true));
+}
+
+#[cfg(unix)]
+#[test]
+fn errors_last_unreadable_dir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("a");
+    dir.mkdirp("b/child");
+    dir.mkdirp("c");
+    dir.touch_all(&["a/1", "c/1"]);
+    let noread = dir.join("b");
+    let set_mode = |mode| {
+        fs::set_permissions(&noread, fs::Permissions::from_mode(mode)).unwrap()
+    };
+    set_mode(0o000);
+    // Privileged users can list the directory anyway.
+    if fs::read_dir(&noread).is_ok() {
+        set_mode(0o755);
+        return;
+    }
+
+    let walk_with = |wd: WalkDir, errors_last| {
+        wd.min_depth(1)
+            .errors_last(errors_last)
+            .into_iter()
+            .map(|r| match r {
+                Ok(ent) => Ok(ent.into_path()),
+                Err(err) => {
+                    assert_eq!(1, err.depth());
+                    Err(err.path().unwrap().to_path_buf())
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let walk = |errors_last| {
+        walk_with(WalkDir::new(dir.path()).sort_by_file_name(), errors_last)
+    };
+    let (first, last) = (walk(false), walk(true));
+    // Without sorting, the error is still held back until the root is left.
+    let unsorted = walk_with(WalkDir::new(dir.path()), true);
+    set_mode(0o755);
+
+    let expected = vec![
+        Ok(dir.join("a")),
+        Ok(dir.join("a/1")),
+        Ok(dir.join("b")),
+        Err(dir.join("b")),
+        Ok(dir.join("c")),
+        Ok(dir.join("c/1")),
+    ];
+    assert_eq!(expected, first);
+    let expected = vec![
+        Ok(dir.join("a")),
+        Ok(dir.join("a/1")),
+        Ok(dir.join("b")),
+        Ok(dir.join("c")),
+        Ok(dir.join("c/1")),
+        Err(dir.join("b")),
+    ];
+    assert_eq!(expected, last);
+    assert_eq!(6, unsorted.len());
+    assert_eq!(Some(&Err(dir.join("b"))), unsorted.last());
+}
+
+#[test]
+fn sorter_panic() {
+    use std::collections::HashSet;
+    use std::panic::{self, AssertUnwindSafe};
+
+    let dir = Dir::tmp();
+    for sub in &["a", "b", "c"] {
+        dir.mkdirp(sub);
+        dir.touch_all(&[format!("{}/1", sub), format!("{}/2", sub)]);
+    }
+
+    let wd = WalkDir::new(dir.path()).sort_by_with_context(|ctx, a, b| {
+        if ctx.parent().ends_with("b") {
+            panic!("sorter panicked");
+        }
+        a.file_name().cmp(b.file_name())
+    });
+    let mut it = wd.into_iter();
+    let mut yielded = vec![];
+    loop {
+        match panic::catch_unwind(AssertUnwindSafe(|| it.next())) {
+            Ok(Some(result)) => yielded.push(result.unwrap().into_path()),
+            Ok(None) => panic!("walk ended without a panic"),
+            Err(_) => break,
+        }
+    }
+    let expected = [dir.path().to_path_buf(), dir.join("a"), dir.join("a/1")];
+    assert_eq!(expected[..], yielded[..3]);
+    let unique: HashSet<_> = yielded.iter().collect();
+    assert_eq!(unique.len(), yielded.len());
+    assert!(!yielded.contains(&dir.join("b/1")));
+
+    // The walk is over for good.
+    for _ in 0..3 {
+        assert!(it.next().is_none());
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn metadata_from_find_data() {
+    use std::io::Write;
+
+    let dir = Dir::tmp();
+    let mut file = fs::File::create(dir.join("a")).unwrap();
+    file.write_all(b"hello").unwrap();
+    drop(file);
+    let want = fs::metadata(dir.join("a")).unwrap();
+
+    let ent = WalkDir::new(dir.path())
+        .min_depth(1)
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap();
+    // The metadata was read with the directory, so it doesn't matter that
+    // the file is gone by now.
+    fs::remove_file(dir.join("a")).unwrap();
+    let md = ent.metadata().unwrap();
+    assert_eq!(5, md.len());
+    assert!(md.is_file());
+    assert_eq!(want.modified().unwrap(), md.modified().unwrap());
+    assert_eq!(want.created().unwrap(), md.created().unwrap());
+    assert_eq!(None, ent.nlink());
+}
+
+#[cfg(any(unix, windows))]
+#[test]
+fn require_unicode() {
+    use std::ffi::OsString;
+    use std::path::Path;
+
+    #[cfg(unix)]
+    let bad = {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(vec![b'a', 0xFF])
+    };
+    #[cfg(windows)]
+    let bad = {
+        use std::os::windows::ffi::OsStringExt;
+        OsString::from_wide(&[b'a' as u16, 0xD800])
+    };
+    let dir = Dir::tmp();
+    dir.mkdirp(Path::new(&bad).join("sub"));
+    dir.touch(Path::new(&bad).join("sub").join("c"));
+    dir.touch("b");
+
+    // Without the option, every entry is yielded.
+    let r = dir.run_recursive(WalkDir::new(dir.path()));
+    r.assert_no_errors();
+    assert_eq!(5, r.ents().len());
+    let ent = r.ents().iter().find(|ent| ent.file_name() == bad).unwrap();
+    assert_eq!(None, ent.file_name_str());
+
+    // The directory is reported as an error, but still descended into.
+    let wd = WalkDir::new(dir.path()).require_unicode(true);
+    let r = dir.run_recursive(wd);
+    assert_eq!(1, r.errs().len());
+    let err = &r.errs()[0];
+    assert_eq!(Some(bad.as_os_str()), err.non_unicode_name());
+    assert_eq!(Some(dir.join(&bad).as_path()), err.path());
+    assert_eq!(1, err.depth());
+    let bad_sub = dir.join(&bad).join("sub");
+    let expected = vec![
+        dir.path().to_path_buf(),
+        bad_sub.clone(),
+        bad_sub.join("c"),
+        dir.join("b"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+    let ent = r.ents().iter().find(|ent| ent.path() == bad_sub).unwrap();
+    assert_eq!(Some("sub"), ent.file_name_str());
+    let wd = WalkDir::new(dir.path()).require_unicode(true);
+    let err: std::io::Error =
+        wd.into_iter().find_map(|result| result.err()).unwrap().into();
+    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+
+    // With pruning, its contents aren't read either.
+    let wd = WalkDir::new(dir.path())
+        .require_unicode(true)
+        .prune_non_unicode(true)
+        .contents_first(true);
+    let r = dir.run_recursive(wd);
+    assert_eq!(1, r.errs().len());
+    assert_eq!(Some(bad.as_os_str()), r.errs()[0].non_unicode_name());
+    assert_eq!(
+        vec![dir.path().to_path_buf(), dir.join("b")],
+        r.sorted_paths()
+    );
+
+    // A pruned directory that is yielded isn't descended into, so filtering
+    // it out doesn't skip its siblings.
+    let wd = WalkDir::new(dir.path())
+        .prune_non_unicode(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|ent| ent.depth() == 0 || ent.file_name_str().is_some());
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    assert_eq!(vec![dir.path().to_path_buf(), dir.join("b")], r.paths());
+}
+
+#[test]
+fn set_max_depth() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b/c");
+    dir.mkdirp("d/e/f");
+
+    let mut it =
+        WalkDir::new(dir.path()).max_depth(1).sort_by_file_name().into_iter();
+    let mut got = vec![];
+    while let Some(result) = it.next() {
+        let ent = result.unwrap();
+        if ent.path() == dir.join("a") {
+            it.set_max_depth(2);
+        }
+        got.push(ent.into_path());
+    }
+    // `a` was yielded at the old maximum depth, so it wasn't opened.
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("d"),
+        dir.join("d/e"),
+    ];
+    assert_eq!(expected, got);
+
+    // Lowering it stops descending and skips entries already being read.
+    let mut it = WalkDir::new(dir.path()).sort_by_file_name().into_iter();
+    let mut got = vec![];
+    while let Some(result) = it.next() {
+        let ent = result.unwrap();
+        if ent.path() == dir.join("a/b") {
+            it.set_max_depth(1);
+        }
+        got.push(ent.into_path());
+    }
+    let expected = vec![
+        dir.path().to_path_buf(),
+        dir.join("a"),
+        dir.join("a/b"),
+        dir.join("d"),
+    ];
+    assert_eq!(expected, got);
+}
+
+#[test]
+fn ancestors_and_last_in_dir() {
+    let dir = Dir::tmp();
+    dir.mkdirp("a/b");
+    dir.touch_all(&["a/b/c", "a/d", "e"]);
+
+    let mut it = WalkDir::new(dir.path()).sort_by_file_name().into_iter();
+    assert_eq!(0, it.ancestors().count());
+    assert_eq!(None, it.is_last_in_dir());
+    let mut got = vec![];
+    while let Some(result) = it.next() {
+        let ent = result.unwrap();
+        let ancestors: Vec<PathBuf> =
+            it.ancestors().map(|path| path.to_path_buf()).collect();
+        assert_eq!(ent.depth(), ancestors.len());
+        got.push((ent.into_path(), ancestors, it.is_last_in_dir()));
+    }
+    let (root, a, b) =
+        (dir.path().to_path_buf(), dir.join("a"), dir.join("a/b"));
+    let expected = vec![
+        (root.clone(), vec![], None),
+        (a.clone(), vec![root.clone()], Some(false)),
+        (b.clone(), vec![root.clone(), a.clone()], Some(false)),
+        (b.join("c"), vec![root.clone(), a.clone(), b], Some(true)),
+        (a.join("d"), vec![root.clone(), a], Some(true)),
+        (dir.join("e"), vec![root], Some(true)),
+    ];
+    assert_eq!(expected, got);
+
+    // Without a sorter, directories are read as they go.
+    let mut it = WalkDir::new(dir.path()).min_depth(1).into_iter();
+    while let Some(result) = it.next() {
+        result.unwrap();
+        assert_eq!(None, it.is_last_in_dir());
+    }
+}
+
+#[cfg(windows)]
+#[test]
+fn follow_reparse_tags() {
+    use std::process::Command;
+
+    const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA0000003;
+    const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000000C;
+
+    let dir = Dir::tmp();
+    dir.mkdirp("target");
+    dir.touch("target/a");
+    dir.mkdirp("root");
+    let status = Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(dir.join("root/junction"))
+        .arg(dir.join("target"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    dir.symlink_dir("target", "root/symlink");
+
+    // By default, the junction is a leaf.
+    let wd = WalkDir::new(dir.join("root")).follow_links(true);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let expected = vec![
+        dir.join("root"),
+        dir.join("root/junction"),
+        dir.join("root/symlink"),
+        dir.join("root/symlink/a"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+    let junction = r
+        .ents()
+        .iter()
+        .find(|ent| ent.path() == dir.join("root/junction"))
+        .unwrap();
+    assert!(junction.file_type().is_symlink());
+
+    let wd =
+        WalkDir::new(dir.join("root")).follow_links(true).follow_reparse_tags(
+            &[IO_REPARSE_TAG_SYMLINK, IO_REPARSE_TAG_MOUNT_POINT],
+        );
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let expected = vec![
+        dir.join("root"),
+        dir.join("root/junction"),
+        dir.join("root/junction/a"),
+        dir.join("root/symlink"),
+        dir.join("root/symlink/a"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+
+    let wd = WalkDir::new(dir.join("root"))
+        .follow_links(true)
+        .follow_reparse_tags(&[IO_REPARSE_TAG_MOUNT_POINT]);
+    let r = dir.run_recursive(wd);
+    r.assert_no_errors();
+    let expected = vec![
+        dir.join("root"),
+        dir.join("root/junction"),
+        dir.join("root/junction/a"),
+        dir.join("root/symlink"),
+    ];
+    assert_eq!(expected, r.sorted_paths());
+}
@@ -1,3 +1,6 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::cmp;
 use std::env;
 use std::error;
 use std::fs::{self, File};
@@ -250,3 +253,59 @@ impl TempDir {
         &self.0
     }
 }
+
+/// An allocator that counts the bytes allocated by each thread, so that
+/// tests can check how much memory something needs.
+struct CountingAlloc;
+
+thread_local! {
+    /// The bytes allocated and the most allocated at once since counting
+    /// started on this thread, if it did.
+    static ALLOCATED: Cell<Option<(isize, isize)>> = const { Cell::new(None) };
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+impl CountingAlloc {
+    fn add(&self, bytes: isize) {
+        // This fails while the thread is being torn down.
+        let _ = ALLOCATED.try_with(|allocated| {
+            if let Some((now, peak)) = allocated.get() {
+                let now = now + bytes;
+                allocated.set(Some((now, cmp::max(now, peak))));
+            }
+        });
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.add(layout.size() as isize);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.add(-(layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        self.add(new_size as isize - layout.size() as isize);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Runs `f` and returns the most bytes it had allocated at once on this
+/// thread.
+pub fn peak_allocated<F: FnOnce()>(f: F) -> usize {
+    ALLOCATED.with(|allocated| allocated.set(Some((0, 0))));
+    f();
+    let (_, peak) = ALLOCATED.with(|allocated| allocated.take()).unwrap();
+    peak as usize
+}
@@ -0,0 +1,250 @@
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{util, DirEntry, Error, Result, WalkDir};
+
+/// An in-memory tree of files, as found by a walk.
+///
+/// A tree can be built from a walk with [`from_walk`], written to disk with
+/// [`create_in`], and compared with another tree with [`diff`]. This is
+/// useful for, e.g., snapshotting a directory and later finding out what
+/// changed, or for creating test fixtures.
+///
+/// The children of a directory built by [`from_walk`] are always sorted by
+/// file name, so two walked trees with the same contents compare equal
+/// regardless of the order that the file system returned them in. Trees
+/// built by hand may list children in any order, which [`diff`] ignores.
+///
+/// # Example
+///
+/// ```no_run
+/// use walkdir::{Tree, WalkDir};
+///
+/// # fn try_main() -> Result<(), walkdir::Error> {
+/// let before = Tree::from_walk(WalkDir::new("foo"))?;
+/// // ... do something with foo ...
+/// let after = Tree::from_walk(WalkDir::new("foo"))?;
+/// for change in before.diff(&after) {
+///     println!("{:?}", change);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`from_walk`]: enum.Tree.html#method.from_walk
+/// [`create_in`]: enum.Tree.html#method.create_in
+/// [`diff`]: enum.Tree.html#method.diff
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Tree {
+    /// A directory with the given name and children.
+    Dir(OsString, Vec<Tree>),
+    /// A file (or any other kind of entry that isn't a directory or a
+    /// symbolic link) with the given name.
+    File(OsString),
+    /// A symbolic link with the given name.
+    Symlink {
+        /// The name of the link.
+        name: OsString,
+        /// The path that the link points to, as it was read from the link.
+        target: PathBuf,
+        /// Whether the link points to a directory. This only matters on
+        /// Windows, where links to directories and files are created
+        /// differently.
+        dir: bool,
+    },
+}
+
+/// A difference between two trees, as reported by [`Tree::diff`].
+///
+/// Each difference carries the path of the node that differs, relative to
+/// the roots of the trees. The roots themselves have an empty path.
+///
+/// [`Tree::diff`]: enum.Tree.html#method.diff
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TreeDiff {
+    /// The node only exists in the other tree.
+    Added(PathBuf),
+    /// The node only exists in this tree.
+    Removed(PathBuf),
+    /// The node exists in both trees but its kind differs, or it is a
+    /// symbolic link with a different target.
+    Changed(PathBuf),
+}
+
+impl Tree {
+    /// Builds a tree from the entries yielded by the given walk.
+    ///
+    /// The walk is done with [`min_depth`] set to `0` and [`contents_first`]
+    /// disabled, since the tree needs every directory before its contents,
+    /// and with [`emit_dir_close`] disabled, since the tree already knows
+    /// where each directory ends. All other options apply as they are set. In particular, when
+    /// [`follow_links`] is enabled, symbolic links to directories become
+    /// directories in the tree.
+    ///
+    /// The first error yielded by the walk is returned.
+    ///
+    /// [`min_depth`]: struct.WalkDir.html#method.min_depth
+    /// [`contents_first`]: struct.WalkDir.html#method.contents_first
+    /// [`emit_dir_close`]: struct.WalkDir.html#method.emit_dir_close
+    /// [`follow_links`]: struct.WalkDir.html#method.follow_links
+    pub fn from_walk(walk: WalkDir) -> Result<Tree> {
+        // The directories on the current path, with their children so far.
+        let mut stack: Vec<(OsString, Vec<Tree>)> = vec![];
+        let mut root = None;
+        let walk =
+            walk.min_depth(0).contents_first(false).emit_dir_close(false);
+        for result in walk {
+            let dent = result?;
+            while stack.len() > dent.depth() {
+                finish(&mut stack, &mut root);
+            }
+            // A root symlink is always followed for traversal, so it's a
+            // directory in the tree when it points to one.
+            if dent.file_type().is_dir()
+                || (dent.depth() == 0 && dent.is_dir())
+            {
+                stack.push((dent.file_name().to_os_string(), vec![]));
+                continue;
+            }
+            let node = Tree::from_entry(&dent)?;
+            match stack.last_mut() {
+                Some(&mut (_, ref mut children)) => children.push(node),
+                None => root = Some(node),
+            }
+        }
+        while !stack.is_empty() {
+            finish(&mut stack, &mut root);
+        }
+        Ok(root.expect("BUG: a walk always yields its root or an error"))
+    }
+
+    fn from_entry(dent: &DirEntry) -> Result<Tree> {
+        let name = dent.file_name().to_os_string();
+        if !dent.file_type().is_symlink() {
+            return Ok(Tree::File(name));
+        }
+        let target = fs::read_link(dent.path())
+            .map_err(|err| Error::from_entry(dent, err))?;
+        let dir = dent.path().is_dir();
+        Ok(Tree::Symlink { name, target, dir })
+    }
+
+    /// Returns the name of the root of this tree.
+    pub fn name(&self) -> &OsStr {
+        match *self {
+            Tree::Dir(ref name, _)
+            | Tree::File(ref name)
+            | Tree::Symlink { ref name, .. } => name,
+        }
+    }
+
+    /// Creates this tree inside the given directory.
+    ///
+    /// The root of the tree becomes `dir.join(self.name())`. The files are
+    /// created empty. Symbolic link targets are written as they are, so
+    /// relative targets are relative to the link's directory.
+    ///
+    /// This fails if any part of the tree already exists.
+    pub fn create_in<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        let path = dir.as_ref().join(self.name());
+        match *self {
+            Tree::Dir(_, ref children) => {
+                fs::create_dir(&path)?;
+                for child in children {
+                    child.create_in(&path)?;
+                }
+                Ok(())
+            }
+            Tree::File(_) => {
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)?;
+                Ok(())
+            }
+            Tree::Symlink { ref target, dir, .. } => {
+                util::symlink(target, &path, dir)
+            }
+        }
+    }
+
+    /// Returns the differences between this tree and `other`.
+    ///
+    /// The names of the two roots aren't compared, so a tree can be compared
+    /// with a copy of itself somewhere else. Nodes are matched by name.
+    /// When the kinds of two nodes differ, the node is reported as changed
+    /// and their children aren't compared. The differences are ordered by
+    /// path.
+    pub fn diff(&self, other: &Tree) -> Vec<TreeDiff> {
+        let mut diffs = vec![];
+        diff(self, other, PathBuf::new(), &mut diffs);
+        diffs
+    }
+}
+
+/// Pops the last directory off the stack and adds it to its parent, or
+/// makes it the root if there's no parent.
+fn finish(stack: &mut Vec<(OsString, Vec<Tree>)>, root: &mut Option<Tree>) {
+    let (name, mut children) = stack.pop().unwrap();
+    children.sort_by(|a, b| util::cmp_file_names(a.name(), b.name()));
+    let node = Tree::Dir(name, children);
+    match stack.last_mut() {
+        Some(&mut (_, ref mut children)) => children.push(node),
+        None => *root = Some(node),
+    }
+}
+
+fn diff(ours: &Tree, theirs: &Tree, path: PathBuf, diffs: &mut Vec<TreeDiff>) {
+    let (ours, theirs) = match (ours, theirs) {
+        (Tree::Dir(_, ours), Tree::Dir(_, theirs)) => (ours, theirs),
+        (Tree::File(_), Tree::File(_)) => return,
+        (
+            Tree::Symlink { target: ours, .. },
+            Tree::Symlink { target: theirs, .. },
+        ) if ours == theirs => return,
+        _ => {
+            diffs.push(TreeDiff::Changed(path));
+            return;
+        }
+    };
+    // Once both lists of children are sorted by name, they can be merged.
+    let (mut ours, mut theirs) = (
+        sorted(ours).into_iter().peekable(),
+        sorted(theirs).into_iter().peekable(),
+    );
+    loop {
+        let order = match (ours.peek(), theirs.peek()) {
+            (None, None) => return,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => util::cmp_file_names(a.name(), b.name()),
+        };
+        match order {
+            Ordering::Less => {
+                let node = ours.next().unwrap();
+                diffs.push(TreeDiff::Removed(path.join(node.name())));
+            }
+            Ordering::Greater => {
+                let node = theirs.next().unwrap();
+                diffs.push(TreeDiff::Added(path.join(node.name())));
+            }
+            Ordering::Equal => {
+                let (a, b) = (ours.next().unwrap(), theirs.next().unwrap());
+                diff(a, b, path.join(a.name()), diffs);
+            }
+        }
+    }
+}
+
+/// Returns the given children sorted by name.
+///
+/// Only trees built by `from_walk` are guaranteed to have their children
+/// sorted already. Trees built by hand may list them in any order.
+fn sorted(children: &[Tree]) -> Vec<&Tree> {
+    let mut children: Vec<&Tree> = children.iter().collect();
+    children.sort_by(|a, b| util::cmp_file_names(a.name(), b.name()));
+    children
+}
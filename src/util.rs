@@ -1,5 +1,10 @@
+#[cfg(not(unix))]
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[cfg(unix)]
 pub fn device_num<P: AsRef<Path>>(path: P) -> io::Result<u64> {
@@ -23,3 +28,482 @@ pub fn device_num<P: AsRef<Path>>(_: P) -> io::Result<u64> {
         "walkdir: same_file_system option not supported on this platform",
     ))
 }
+
+/// Returns a unique identity for the file at the given path, if it has more
+/// than one hard link.
+#[cfg(unix)]
+pub fn hard_link_id<P: AsRef<Path>>(
+    path: P,
+) -> io::Result<Option<(u64, u64)>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let md = path.as_ref().metadata()?;
+    Ok(if md.nlink() > 1 { Some((md.dev(), md.ino())) } else { None })
+}
+
+/// Returns a unique identity for the file at the given path, if it has more
+/// than one hard link.
+#[cfg(windows)]
+pub fn hard_link_id<P: AsRef<Path>>(
+    path: P,
+) -> io::Result<Option<(u64, u64)>> {
+    use winapi_util::{file, Handle};
+
+    let h = Handle::from_path_any(path)?;
+    let info = file::information(h)?;
+    Ok(if info.number_of_links() > 1 {
+        Some((info.volume_serial_number(), info.file_index()))
+    } else {
+        None
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn hard_link_id<P: AsRef<Path>>(_: P) -> io::Result<Option<(u64, u64)>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "walkdir: hard link detection not supported on this platform",
+    ))
+}
+
+/// Returns the identity of the file at the given path, following it if it's
+/// a symbolic link and `follow` is true.
+#[cfg(unix)]
+pub fn file_id<P: AsRef<Path>>(
+    path: P,
+    follow: bool,
+) -> io::Result<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = path.as_ref();
+    let md = if follow { path.metadata()? } else { path.symlink_metadata()? };
+    Ok((md.dev(), md.ino()))
+}
+
+/// Returns the identity of the file at the given path. Symbolic links are
+/// always followed.
+#[cfg(windows)]
+pub fn file_id<P: AsRef<Path>>(path: P, _: bool) -> io::Result<(u64, u64)> {
+    use winapi_util::{file, Handle};
+
+    let h = Handle::from_path_any(path)?;
+    let info = file::information(h)?;
+    Ok((info.volume_serial_number(), info.file_index()))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn file_id<P: AsRef<Path>>(_: P, _: bool) -> io::Result<(u64, u64)> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "walkdir: file identities not supported on this platform",
+    ))
+}
+
+#[cfg(windows)]
+pub fn nlink<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    use winapi_util::{file, Handle};
+
+    let h = Handle::from_path_any(path)?;
+    file::information(h).map(|info| info.number_of_links())
+}
+
+/// The reparse tag of a symbolic link.
+#[cfg(windows)]
+pub const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000000C;
+
+/// Returns the reparse tag of the file at the given path, without following
+/// it, or `0` if it isn't a reparse point.
+#[cfg(windows)]
+pub fn reparse_tag<P: AsRef<Path>>(path: P) -> io::Result<u32> {
+    use std::mem;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+
+    use windows_sys::Win32::Storage::FileSystem::{
+        FileAttributeTagInfo, GetFileInformationByHandleEx,
+        FILE_ATTRIBUTE_REPARSE_POINT, FILE_ATTRIBUTE_TAG_INFO,
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT,
+    };
+
+    // Only the attributes are read, so no access rights are needed.
+    let file = fs::OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(
+            FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT,
+        )
+        .open(path)?;
+    // SAFETY: The struct is plain data, and it's only read after the call
+    // succeeded and filled it in.
+    let info = unsafe {
+        let mut info: FILE_ATTRIBUTE_TAG_INFO = mem::zeroed();
+        let ok = GetFileInformationByHandleEx(
+            file.as_raw_handle() as _,
+            FileAttributeTagInfo,
+            &mut info as *mut FILE_ATTRIBUTE_TAG_INFO as *mut _,
+            mem::size_of::<FILE_ATTRIBUTE_TAG_INFO>() as u32,
+        );
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        info
+    };
+    if info.FileAttributes & FILE_ATTRIBUTE_REPARSE_POINT == 0 {
+        return Ok(0);
+    }
+    Ok(info.ReparseTag)
+}
+
+/// Collapses runs of path separators in the given path into a single
+/// separator. Nothing else about the path is changed.
+#[cfg(unix)]
+pub fn collapse_separators(path: &Path) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+
+    let bytes = path.as_os_str().as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'/' && i > 0 && bytes[i - 1] == b'/' {
+            continue;
+        }
+        out.push(b);
+    }
+    PathBuf::from(OsStr::from_bytes(&out))
+}
+
+/// Collapses runs of path separators in the given path into a single
+/// separator. Nothing else about the path is changed.
+///
+/// The leading `\\` of a UNC path is kept, and verbatim (`\\?\`) paths
+/// are returned unchanged since Windows doesn't treat their separators
+/// specially.
+#[cfg(windows)]
+pub fn collapse_separators(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+    let is_sep = |c: u16| c == b'/' as u16 || c == b'\\' as u16;
+    let wide: Vec<u16> = path.as_os_str().encode_wide().collect();
+    let is_verbatim = wide.len() >= 4
+        && is_sep(wide[0])
+        && is_sep(wide[1])
+        && wide[2] == b'?' as u16
+        && is_sep(wide[3]);
+    if is_verbatim {
+        return path.to_path_buf();
+    }
+    let mut out = Vec::with_capacity(wide.len());
+    for (i, &c) in wide.iter().enumerate() {
+        if i > 1 && is_sep(c) && is_sep(wide[i - 1]) {
+            continue;
+        }
+        out.push(c);
+    }
+    PathBuf::from(OsString::from_wide(&out))
+}
+
+/// Collapses runs of path separators in the given path into a single
+/// separator. Nothing else about the path is changed.
+///
+/// Paths that aren't valid UTF-8 are returned unchanged.
+#[cfg(not(any(unix, windows)))]
+pub fn collapse_separators(path: &Path) -> PathBuf {
+    let s = match path.to_str() {
+        None => return path.to_path_buf(),
+        Some(s) => s,
+    };
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '/' && out.ends_with('/') {
+            continue;
+        }
+        out.push(c);
+    }
+    PathBuf::from(out)
+}
+
+/// Returns true if the given path ends with a separator that could be
+/// removed without changing which file it refers to.
+///
+/// Such a path asserts that it refers to a directory.
+pub fn has_trailing_separator(path: &Path) -> bool {
+    let trimmed = trim_trailing_separators(path);
+    trimmed.as_os_str().len() != path.as_os_str().len()
+}
+
+/// Removes trailing path separators from the given path, unless that would
+/// change which file it refers to (e.g., for `/`).
+#[cfg(unix)]
+pub fn trim_trailing_separators(path: &Path) -> &Path {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut bytes = path.as_os_str().as_bytes();
+    while bytes.len() > 1 && bytes[bytes.len() - 1] == b'/' {
+        bytes = &bytes[..bytes.len() - 1];
+    }
+    Path::new(OsStr::from_bytes(bytes))
+}
+
+/// Removes trailing path separators from the given path, unless that would
+/// change which file it refers to (e.g., for `C:\`).
+///
+/// Verbatim (`\\?\`) paths and paths that aren't valid UTF-8 are returned
+/// unchanged.
+#[cfg(not(unix))]
+pub fn trim_trailing_separators(path: &Path) -> Cow<'_, Path> {
+    let s = match path.to_str() {
+        Some(s) if !s.starts_with(r"\\?\") => s,
+        _ => return Cow::Borrowed(path),
+    };
+    let trimmed = s.trim_end_matches(std::path::is_separator);
+    if trimmed.len() == s.len() {
+        return Cow::Borrowed(path);
+    }
+    if Path::new(trimmed) == path {
+        return Cow::Owned(PathBuf::from(trimmed));
+    }
+    // Keep one separator so that a root like `C:\` stays a root.
+    // Separators are always ASCII.
+    Cow::Owned(PathBuf::from(&s[..trimmed.len() + 1]))
+}
+
+/// Compares two file names by their raw bytes.
+#[cfg(unix)]
+pub fn cmp_file_names(a: &OsStr, b: &OsStr) -> Ordering {
+    use std::os::unix::ffi::OsStrExt;
+
+    a.as_bytes().cmp(b.as_bytes())
+}
+
+/// Compares two file names by their UTF-16 code units.
+#[cfg(windows)]
+pub fn cmp_file_names(a: &OsStr, b: &OsStr) -> Ordering {
+    use std::os::windows::ffi::OsStrExt;
+
+    a.encode_wide().cmp(b.encode_wide())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn cmp_file_names(a: &OsStr, b: &OsStr) -> Ordering {
+    a.cmp(b)
+}
+
+/// Compares two file names by their raw bytes, ignoring ASCII case.
+#[cfg(unix)]
+pub fn cmp_file_names_ascii_ci(a: &OsStr, b: &OsStr) -> Ordering {
+    use std::os::unix::ffi::OsStrExt;
+
+    let lower = |b: &u8| b.to_ascii_lowercase();
+    a.as_bytes().iter().map(lower).cmp(b.as_bytes().iter().map(lower))
+}
+
+/// Compares two file names by their UTF-16 code units, ignoring ASCII case.
+#[cfg(windows)]
+pub fn cmp_file_names_ascii_ci(a: &OsStr, b: &OsStr) -> Ordering {
+    use std::os::windows::ffi::OsStrExt;
+
+    let lower = |c: u16| {
+        if (b'A' as u16..=b'Z' as u16).contains(&c) {
+            c + (b'a' - b'A') as u16
+        } else {
+            c
+        }
+    };
+    a.encode_wide().map(lower).cmp(b.encode_wide().map(lower))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn cmp_file_names_ascii_ci(a: &OsStr, b: &OsStr) -> Ordering {
+    let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+    let lower = |b: &u8| b.to_ascii_lowercase();
+    a.as_bytes().iter().map(lower).cmp(b.as_bytes().iter().map(lower))
+}
+
+/// Creates a symbolic link to `src` named `link_name`.
+///
+/// On Windows, `dir` says whether to create a link to a directory or to a
+/// file.
+#[cfg(unix)]
+pub fn symlink(src: &Path, link_name: &Path, _dir: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(src, link_name)
+}
+
+/// Creates a symbolic link to `src` named `link_name`.
+///
+/// On Windows, `dir` says whether to create a link to a directory or to a
+/// file.
+#[cfg(windows)]
+pub fn symlink(src: &Path, link_name: &Path, dir: bool) -> io::Result<()> {
+    if dir {
+        std::os::windows::fs::symlink_dir(src, link_name)
+    } else {
+        std::os::windows::fs::symlink_file(src, link_name)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn symlink(_: &Path, _: &Path, _: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "walkdir: symbolic links are not supported on this platform",
+    ))
+}
+
+/// Opens a directory for reading, trying up to `retries` more times if it
+/// fails with a transient error.
+pub fn read_dir<P: AsRef<Path>>(
+    path: P,
+    mut retries: usize,
+) -> io::Result<fs::ReadDir> {
+    loop {
+        match open_dir(path.as_ref()) {
+            Err(ref err) if retries > 0 && is_transient(err) => retries -= 1,
+            result => return result,
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn open_dir(path: &Path) -> io::Result<fs::ReadDir> {
+    fs::read_dir(path)
+}
+
+#[cfg(test)]
+fn open_dir(path: &Path) -> io::Result<fs::ReadDir> {
+    let fail = TRANSIENT_ERRORS.with(|errs| {
+        let n = errs.get();
+        errs.set(n.saturating_sub(1));
+        n > 0
+    });
+    if fail {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "injected"));
+    }
+    fs::read_dir(path)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of transient errors to fail opening directories with on
+    /// this thread before actually opening them.
+    pub static TRANSIENT_ERRORS: std::cell::Cell<usize> =
+        const { std::cell::Cell::new(0) };
+}
+
+/// Reads the next entry of an open directory.
+#[cfg(not(test))]
+#[inline(always)]
+pub fn read_entry(rd: &mut fs::ReadDir) -> Option<io::Result<fs::DirEntry>> {
+    rd.next()
+}
+
+#[cfg(test)]
+pub fn read_entry(rd: &mut fs::ReadDir) -> Option<io::Result<fs::DirEntry>> {
+    let fail = READ_ERROR_AFTER.with(|after| match after.get() {
+        None => false,
+        Some(0) => {
+            after.set(None);
+            true
+        }
+        Some(n) => {
+            after.set(Some(n - 1));
+            false
+        }
+    });
+    if fail {
+        return Some(Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "injected",
+        )));
+    }
+    rd.next()
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of reads from open directories on this thread after which
+    /// the next read fails, once.
+    pub static READ_ERROR_AFTER: std::cell::Cell<Option<usize>> =
+        const { std::cell::Cell::new(None) };
+}
+
+/// Returns true if the given error may go away when trying again.
+fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::TimedOut
+        | io::ErrorKind::WouldBlock
+        | io::ErrorKind::Interrupted => return true,
+        _ => {}
+    }
+    matches!(err.raw_os_error(), Some(code) if is_transient_os_error(code))
+}
+
+#[cfg(unix)]
+pub(crate) fn is_transient_os_error(code: i32) -> bool {
+    const EIO: i32 = 5;
+    // Linux uses the errno values of the architectures it was ported to.
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        any(
+            target_arch = "mips",
+            target_arch = "mips64",
+            target_arch = "mips32r6",
+            target_arch = "mips64r6"
+        )
+    ))]
+    const ESTALE: Option<i32> = Some(151);
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        any(target_arch = "sparc", target_arch = "sparc64")
+    ))]
+    const ESTALE: Option<i32> = Some(70);
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        not(any(
+            target_arch = "mips",
+            target_arch = "mips64",
+            target_arch = "mips32r6",
+            target_arch = "mips64r6",
+            target_arch = "sparc",
+            target_arch = "sparc64"
+        ))
+    ))]
+    const ESTALE: Option<i32> = Some(116);
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    const ESTALE: Option<i32> = Some(70);
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    )))]
+    const ESTALE: Option<i32> = None;
+
+    code == EIO || Some(code) == ESTALE
+}
+
+#[cfg(windows)]
+pub(crate) fn is_transient_os_error(code: i32) -> bool {
+    const ERROR_UNEXP_NET_ERR: i32 = 59;
+    const ERROR_NETNAME_DELETED: i32 = 64;
+    const ERROR_SEM_TIMEOUT: i32 = 121;
+
+    matches!(
+        code,
+        ERROR_UNEXP_NET_ERR | ERROR_NETNAME_DELETED | ERROR_SEM_TIMEOUT
+    )
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn is_transient_os_error(_: i32) -> bool {
+    false
+}
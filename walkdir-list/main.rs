@@ -9,17 +9,21 @@
 // it should be added to this program.
 //
 // Finally, this can be useful for ad hoc benchmarking. e.g., See the --timeit
-// and --count flags.
+// and --count flags. It can also be used to check walkdir against a simple
+// reference implementation on unusual file systems. See --verify-against-std.
 
+use std::cmp::Ordering;
 use std::error::Error;
 use std::ffi::OsStr;
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process;
 use std::result;
 use std::time::Instant;
+use std::vec;
 
-use bstr::BString;
+use bstr::{BStr, BString};
 use walkdir::WalkDir;
 
 type Result<T> = result::Result<T, Box<dyn Error>>;
@@ -40,7 +44,12 @@ fn try_main() -> Result<()> {
     let mut stderr = io::stderr();
 
     let start = Instant::now();
-    if args.count {
+    if args.verify_against_std {
+        let diffs = verify(&args, io::stdout(), &mut stderr)?;
+        if diffs > 0 {
+            process::exit(3);
+        }
+    } else if args.count {
         print_count(&args, io::stdout(), &mut stderr)?;
     } else if atty::is(atty::Stream::Stdout) {
         print_paths(&args, io::stdout(), &mut stderr)?;
@@ -91,9 +100,9 @@ where
 {
     for dir in &args.dirs {
         if args.tree {
-            print_paths_tree(&args, &mut stdout, &mut stderr, dir)?;
+            print_paths_tree(args, &mut stdout, &mut stderr, dir)?;
         } else {
-            print_paths_flat(&args, &mut stdout, &mut stderr, dir)?;
+            print_paths_flat(args, &mut stdout, &mut stderr, dir)?;
         }
     }
     Ok(())
@@ -135,7 +144,17 @@ where
     W1: io::Write,
     W2: io::Write,
 {
-    for result in args.walkdir(dir) {
+    // Whether each directory is read into memory has to be known to draw
+    // the tree, which is the case when it's sorted.
+    let mut walkdir = args.walkdir(dir).contents_first(false);
+    if !args.sort {
+        walkdir = walkdir.sort_by_file_name();
+    }
+    let mut it = walkdir.into_iter();
+    // Whether the entry last yielded at each depth, starting at 1, was the
+    // last one in its directory.
+    let mut last: Vec<bool> = vec![];
+    while let Some(result) = it.next() {
         let dent = match result {
             Ok(dent) => dent,
             Err(err) => {
@@ -145,14 +164,269 @@ where
                 continue;
             }
         };
-        stdout.write_all("  ".repeat(dent.depth()).as_bytes())?;
+        if dent.depth() == 0 {
+            write_path(&mut stdout, dent.path())?;
+            stdout.write_all(b"\n")?;
+            continue;
+        }
+        last.resize(dent.depth() - 1, false);
+        for &done in &last {
+            let indent = if done { "    " } else { "\u{2502}   " };
+            stdout.write_all(indent.as_bytes())?;
+        }
+        let is_last = it.is_last_in_dir() == Some(true);
+        let branch = if is_last { "\u{2514}" } else { "\u{251c}" };
+        write!(stdout, "{}\u{2500}\u{2500} ", branch)?;
         write_os_str(&mut stdout, dent.file_name())?;
         stdout.write_all(b"\n")?;
+        last.push(is_last);
     }
     Ok(())
 }
 
-#[derive(Debug)]
+/// Walks every directory with both walkdir and `StdWalk`, and prints each
+/// entry (path and file type) that only one of them yields.
+///
+/// Only `--follow-links`, `--min-depth`, `--max-depth`, `--max-open` and
+/// `--sort` are used, since the reference implementation doesn't support the
+/// other options. Errors are printed but not compared.
+///
+/// Returns the number of differences found.
+fn verify<W1, W2>(args: &Args, mut stdout: W1, mut stderr: W2) -> Result<u64>
+where
+    W1: io::Write,
+    W2: io::Write,
+{
+    let mut diffs = 0;
+    for dir in &args.dirs {
+        let mut walkdir = WalkDir::new(dir).follow_links(args.follow_links);
+        if let Some(x) = args.min_depth {
+            walkdir = walkdir.min_depth(x);
+        }
+        if let Some(x) = args.max_depth {
+            walkdir = walkdir.max_depth(x);
+        }
+        if let Some(x) = args.max_open {
+            walkdir = walkdir.max_open(x);
+        }
+        if args.sort {
+            walkdir = walkdir.sort_by(|a, b| a.file_name().cmp(b.file_name()));
+        }
+        let (mut our_errors, mut their_errors) = (vec![], vec![]);
+        let ours = walkdir.into_iter().filter_map(|result| match result {
+            Ok(dent) => {
+                Some((dent.path().to_path_buf(), kind(dent.file_type())))
+            }
+            Err(err) => {
+                our_errors.push(err.to_string());
+                None
+            }
+        });
+        let theirs =
+            StdWalk::new(args, dir).filter_map(|result| match result {
+                Ok(ent) => Some(ent),
+                Err(err) => {
+                    their_errors.push(err);
+                    None
+                }
+            });
+        // Both walks yield each directory's entries sorted by file name
+        // when --sort is given, which puts all paths in order. So they can
+        // be compared as they go, without keeping every path in memory.
+        diffs += if args.sort {
+            compare(&mut stdout, ours, theirs)?
+        } else {
+            let (mut ours, mut theirs): (Vec<_>, Vec<_>) =
+                (ours.collect(), theirs.collect());
+            ours.sort();
+            theirs.sort();
+            compare(&mut stdout, ours.into_iter(), theirs.into_iter())?
+        };
+        if !args.ignore_errors {
+            for err in our_errors.into_iter().chain(their_errors) {
+                writeln!(stderr, "ERROR: {}", err)?;
+            }
+        }
+    }
+    Ok(diffs)
+}
+
+/// Compares two sorted lists of entries, printing each entry that is only
+/// in one of them, and returns how many there were.
+fn compare<W, I1, I2>(mut stdout: W, ours: I1, theirs: I2) -> Result<u64>
+where
+    W: io::Write,
+    I1: Iterator<Item = (PathBuf, char)>,
+    I2: Iterator<Item = (PathBuf, char)>,
+{
+    let (mut ours, mut theirs) = (ours.peekable(), theirs.peekable());
+    let mut diffs = 0;
+    loop {
+        let ord = match (ours.peek(), theirs.peek()) {
+            (None, None) => return Ok(diffs),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => a.cmp(b),
+        };
+        let (side, (path, kind)) = match ord {
+            Ordering::Equal => {
+                ours.next();
+                theirs.next();
+                continue;
+            }
+            Ordering::Less => ("walkdir", ours.next().unwrap()),
+            Ordering::Greater => ("std", theirs.next().unwrap()),
+        };
+        diffs += 1;
+        write!(stdout, "only in {}: {} ", side, kind)?;
+        write_path(&mut stdout, &path)?;
+        stdout.write_all(b"\n")?;
+    }
+}
+
+/// Returns a single character describing a file type: `d` for directories,
+/// `f` for files, `l` for symbolic links and `o` for anything else.
+fn kind(ty: fs::FileType) -> char {
+    if ty.is_dir() {
+        'd'
+    } else if ty.is_file() {
+        'f'
+    } else if ty.is_symlink() {
+        'l'
+    } else {
+        'o'
+    }
+}
+
+/// A straight-forward recursive directory walk built on `std::fs::read_dir`,
+/// used as a reference for --verify-against-std.
+///
+/// It yields the same entries as walkdir, including the root, in pre-order.
+/// Each directory is read in full before any of its entries are yielded.
+struct StdWalk {
+    follow_links: bool,
+    min_depth: usize,
+    max_depth: usize,
+    sort: bool,
+    start: Option<PathBuf>,
+    /// The unread children of each open directory, along with the canonical
+    /// path of that directory, which is used to detect loops.
+    stack: Vec<(vec::IntoIter<PathBuf>, PathBuf)>,
+    /// An error to yield before reading any further.
+    pending: Option<String>,
+}
+
+impl StdWalk {
+    fn new(args: &Args, root: &Path) -> StdWalk {
+        StdWalk {
+            follow_links: args.follow_links,
+            min_depth: args.min_depth.unwrap_or(0),
+            max_depth: args.max_depth.unwrap_or(usize::MAX),
+            sort: args.sort,
+            start: Some(root.to_path_buf()),
+            stack: vec![],
+            pending: None,
+        }
+    }
+
+    /// Reads the given directory and pushes its children on the stack.
+    fn push(&mut self, dir: &Path, canonical: PathBuf) {
+        let err = |err: io::Error| format!("{}: {}", dir.display(), err);
+        let mut children = vec![];
+        let rd = match fs::read_dir(dir) {
+            Ok(rd) => rd,
+            Err(e) => {
+                self.pending = Some(err(e));
+                return;
+            }
+        };
+        for result in rd {
+            match result {
+                Ok(ent) => children.push(ent.path()),
+                Err(e) => self.pending = Some(err(e)),
+            }
+        }
+        if self.sort {
+            children.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+        }
+        self.stack.push((children.into_iter(), canonical));
+    }
+
+    /// Returns the next entry at any depth, along with its depth.
+    fn next_any(
+        &mut self,
+    ) -> Option<result::Result<(PathBuf, char, usize), String>> {
+        if let Some(err) = self.pending.take() {
+            return Some(Err(err));
+        }
+        let (path, depth) = match self.start.take() {
+            Some(root) => (root, 0),
+            None => loop {
+                let (children, _) = self.stack.last_mut()?;
+                match children.next() {
+                    Some(path) => break (path, self.stack.len()),
+                    None => {
+                        self.stack.pop();
+                    }
+                }
+            },
+        };
+        let err = |err: io::Error| format!("{}: {}", path.display(), err);
+        let md = match fs::symlink_metadata(&path) {
+            Ok(md) => md,
+            Err(e) => return Some(Err(err(e))),
+        };
+        let is_link = md.file_type().is_symlink();
+        // Like walkdir, the root is always followed, but only reported as
+        // the target's type when following links.
+        let target = if is_link && (self.follow_links || depth == 0) {
+            match fs::metadata(&path) {
+                Ok(target) => Some(target),
+                Err(_) if !self.follow_links => None,
+                Err(e) => return Some(Err(err(e))),
+            }
+        } else {
+            None
+        };
+        let ty = match target {
+            Some(ref target) if self.follow_links => target.file_type(),
+            _ => md.file_type(),
+        };
+        let is_dir = target.as_ref().unwrap_or(&md).is_dir();
+        if is_dir {
+            let canonical = match fs::canonicalize(&path) {
+                Ok(canonical) => canonical,
+                Err(e) => return Some(Err(err(e))),
+            };
+            if is_link && self.stack.iter().any(|(_, a)| *a == canonical) {
+                return Some(Err(format!(
+                    "{}: file system loop found",
+                    path.display()
+                )));
+            }
+            if depth < self.max_depth {
+                self.push(&path, canonical);
+            }
+        }
+        Some(Ok((path, kind(ty), depth)))
+    }
+}
+
+impl Iterator for StdWalk {
+    type Item = result::Result<(PathBuf, char), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_any()? {
+                Err(err) => return Some(Err(err)),
+                Ok((_, _, depth)) if depth < self.min_depth => continue,
+                Ok((path, kind, _)) => return Some(Ok((path, kind))),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 struct Args {
     dirs: Vec<PathBuf>,
     follow_links: bool,
@@ -164,8 +438,33 @@ struct Args {
     sort: bool,
     depth_first: bool,
     same_file_system: bool,
+    dedup_hard_links: bool,
+    skip_unreadable: bool,
     timeit: bool,
     count: bool,
+    verify_against_std: bool,
+}
+
+/// Parses a list of root paths, as given to --files-from.
+///
+/// The paths are separated by NUL bytes if `null` is true or if there are
+/// any NUL bytes at all, and by newlines otherwise. Empty paths are ignored.
+/// On Unix, the paths are used as is, without requiring them to be valid
+/// UTF-8. Elsewhere, invalid UTF-8 is replaced.
+fn parse_roots(list: &[u8], null: bool) -> Vec<PathBuf> {
+    let null = null || list.contains(&b'\0');
+    let sep = if null { b'\0' } else { b'\n' };
+    list.split(|&b| b == sep)
+        .map(|path| {
+            if !null && path.last() == Some(&b'\r') {
+                &path[..path.len() - 1]
+            } else {
+                path
+            }
+        })
+        .filter(|path| !path.is_empty())
+        .map(|path| BStr::new(path).to_path_lossy().into_owned())
+        .collect()
 }
 
 impl Args {
@@ -177,6 +476,26 @@ impl Args {
             .version(crate_version!())
             .max_term_width(100)
             .arg(Arg::with_name("dirs").multiple(true))
+            .arg(
+                Arg::with_name("files-from")
+                    .long("files-from")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .help(
+                        "Also walk the directories listed in this file, or \
+                         in stdin if it's -. They are separated by NUL \
+                         bytes if there are any, and by newlines otherwise.",
+                    ),
+            )
+            .arg(
+                Arg::with_name("null-input")
+                    .long("null-input")
+                    .short("0")
+                    .help(
+                        "Only separate the directories given to --files-from \
+                         by NUL bytes.",
+                    ),
+            )
             .arg(
                 Arg::with_name("follow-links")
                     .long("follow-links")
@@ -205,7 +524,7 @@ impl Args {
             .arg(
                 Arg::with_name("tree")
                     .long("tree")
-                    .help("Show file paths in a tree."),
+                    .help("Show file paths in a tree. Implies --sort."),
             )
             .arg(
                 Arg::with_name("ignore-errors")
@@ -231,6 +550,16 @@ impl Args {
                         "Only show paths on the same file system as the root.",
                     ),
             )
+            .arg(
+                Arg::with_name("dedup-hard-links")
+                    .long("dedup-hard-links")
+                    .help("Show each hard linked file only once."),
+            )
+            .arg(
+                Arg::with_name("skip-unreadable")
+                    .long("skip-unreadable")
+                    .help("Skip entries whose type can't be read."),
+            )
             .arg(
                 Arg::with_name("timeit")
                     .long("timeit")
@@ -243,14 +572,44 @@ impl Args {
                     .short("c")
                     .help("Print only a total count of all file paths."),
             )
+            .arg(
+                Arg::with_name("verify-against-std")
+                    .long("verify-against-std")
+                    .help(
+                        "Compare the listing with a walk built on \
+                         std::fs::read_dir and print the differences. \
+                         Exits with code 3 if there are any.",
+                    ),
+            )
             .get_matches();
 
-        let dirs = match parsed.values_of_os("dirs") {
-            None => vec![PathBuf::from("./")],
+        let mut dirs: Vec<PathBuf> = match parsed.values_of_os("dirs") {
+            None => vec![],
             Some(dirs) => dirs.map(PathBuf::from).collect(),
         };
+        match parsed.value_of_os("files-from") {
+            None if dirs.is_empty() => dirs.push(PathBuf::from("./")),
+            None => {}
+            Some(path) => {
+                let mut list = vec![];
+                let read = if path == "-" {
+                    io::stdin().read_to_end(&mut list)
+                } else {
+                    fs::File::open(path)
+                        .and_then(|mut f| f.read_to_end(&mut list))
+                };
+                if let Err(e) = read {
+                    let path = Path::new(path);
+                    return err!("failed to read {}: {}", path.display(), e);
+                }
+                dirs.extend(parse_roots(
+                    &list,
+                    parsed.is_present("null-input"),
+                ));
+            }
+        }
         Ok(Args {
-            dirs: dirs,
+            dirs,
             follow_links: parsed.is_present("follow-links"),
             min_depth: parse_usize(&parsed, "min-depth")?,
             max_depth: parse_usize(&parsed, "max-depth")?,
@@ -260,8 +619,11 @@ impl Args {
             sort: parsed.is_present("sort"),
             depth_first: parsed.is_present("depth-first"),
             same_file_system: parsed.is_present("same-file-system"),
+            dedup_hard_links: parsed.is_present("dedup-hard-links"),
+            skip_unreadable: parsed.is_present("skip-unreadable"),
             timeit: parsed.is_present("timeit"),
             count: parsed.is_present("count"),
+            verify_against_std: parsed.is_present("verify-against-std"),
         })
     }
 
@@ -269,7 +631,9 @@ impl Args {
         let mut walkdir = WalkDir::new(path)
             .follow_links(self.follow_links)
             .contents_first(self.depth_first)
-            .same_file_system(self.same_file_system);
+            .same_file_system(self.same_file_system)
+            .dedup_hard_links(self.dedup_hard_links)
+            .skip_unreadable(self.skip_unreadable);
         if let Some(x) = self.min_depth {
             walkdir = walkdir.min_depth(x);
         }
@@ -292,9 +656,10 @@ fn parse_usize(
 ) -> Result<Option<usize>> {
     match parsed.value_of_lossy(flag) {
         None => Ok(None),
-        Some(x) => x.parse().map(Some).or_else(|e| {
-            err!("failed to parse --{} as a number: {}", flag, e)
-        }),
+        Some(x) => match x.parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(e) => err!("failed to parse --{} as a number: {}", flag, e),
+        },
     }
 }
 
@@ -310,3 +675,208 @@ fn write_os_str<W: io::Write>(mut wtr: W, os: &OsStr) -> io::Result<()> {
     // invalid UTF-16 to a console anyway.
     wtr.write_all(BString::from_os_str_lossy(os).as_bytes())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use bstr::BString;
+
+    use super::{compare, parse_roots, print_paths, verify, Args};
+
+    /// A temporary fixture tree that is removed when dropped.
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str) -> Fixture {
+            let dir = env::temp_dir().join(format!(
+                "walkdir-list-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("a/b")).unwrap();
+            fs::create_dir_all(dir.join("c")).unwrap();
+            for file in &["a/f", "a/b/g", "c/h", "i"] {
+                fs::File::create(dir.join(file)).unwrap();
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::symlink;
+
+                symlink("../c", dir.join("a/lc")).unwrap();
+                symlink("../..", dir.join("a/b/loop")).unwrap();
+                symlink("missing", dir.join("broken")).unwrap();
+            }
+            Fixture(dir)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn verify_fixture() {
+        let fixture = Fixture::new("verify");
+        for &sort in &[false, true] {
+            for &follow_links in &[false, true] {
+                for &max_depth in &[None, Some(0), Some(1), Some(2)] {
+                    let args = Args {
+                        dirs: vec![fixture.path().to_path_buf()],
+                        follow_links,
+                        max_depth,
+                        max_open: Some(1),
+                        sort,
+                        ..Args::default()
+                    };
+                    let (mut out, mut err) = (vec![], vec![]);
+                    let diffs = verify(&args, &mut out, &mut err).unwrap();
+                    assert_eq!(
+                        0,
+                        diffs,
+                        "{:?}: {}",
+                        args,
+                        String::from_utf8_lossy(&out)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_roots_separators() {
+        let roots = parse_roots(b"a\nb c\r\n\nd", false);
+        let expected: Vec<PathBuf> =
+            vec!["a".into(), "b c".into(), "d".into()];
+        assert_eq!(expected, roots);
+
+        let roots = parse_roots(b"a\nb\0c\0", false);
+        assert_eq!(vec![PathBuf::from("a\nb"), PathBuf::from("c")], roots);
+        let roots = parse_roots(b"a\nb", true);
+        assert_eq!(vec![PathBuf::from("a\nb")], roots);
+    }
+
+    #[test]
+    fn files_from() {
+        let fixture = Fixture::new("files-from");
+        let mut list = vec![];
+        list.extend_from_slice(b"missing\0");
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            // A file name that isn't valid UTF-8.
+            let name = std::ffi::OsStr::from_bytes(b"\xFFdir");
+            fs::create_dir(fixture.path().join(name)).unwrap();
+            fs::File::create(fixture.path().join(name).join("x")).unwrap();
+            list.extend_from_slice(fixture.path().as_os_str().as_bytes());
+            list.extend_from_slice(b"/\xFFdir\0");
+        }
+        list.extend_from_slice(b"c\0");
+
+        let dirs = parse_roots(&list, false);
+        let args = Args {
+            dirs: dirs.iter().map(|d| fixture.path().join(d)).collect(),
+            sort: true,
+            ..Args::default()
+        };
+        let (mut out, mut err) = (vec![], vec![]);
+        print_paths(&args, &mut out, &mut err).unwrap();
+        let err = String::from_utf8_lossy(&err);
+        assert_eq!(1, err.lines().count(), "{}", err);
+        assert!(err.contains("missing"), "{}", err);
+
+        let c = fixture.path().join("c");
+        let mut expected = vec![];
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let dir =
+                fixture.path().join(std::ffi::OsStr::from_bytes(b"\xFFdir"));
+            for path in &[dir.clone(), dir.join("x")] {
+                expected.extend_from_slice(path.as_os_str().as_bytes());
+                expected.push(b'\n');
+            }
+        }
+        for path in &[c.clone(), c.join("h")] {
+            expected.extend_from_slice(path.to_str().unwrap().as_bytes());
+            expected.push(b'\n');
+        }
+        assert_eq!(BString::from(expected), BString::from(out));
+    }
+
+    #[test]
+    fn tree() {
+        let fixture = Fixture::new("tree");
+        let args = Args {
+            dirs: vec![fixture.path().to_path_buf()],
+            tree: true,
+            ..Args::default()
+        };
+        let (mut out, mut err) = (vec![], vec![]);
+        print_paths(&args, &mut out, &mut err).unwrap();
+        assert!(err.is_empty(), "{}", String::from_utf8_lossy(&err));
+
+        let expected = if cfg!(unix) {
+            "\
+\u{251c}\u{2500}\u{2500} a
+\u{2502}   \u{251c}\u{2500}\u{2500} b
+\u{2502}   \u{2502}   \u{251c}\u{2500}\u{2500} g
+\u{2502}   \u{2502}   \u{2514}\u{2500}\u{2500} loop
+\u{2502}   \u{251c}\u{2500}\u{2500} f
+\u{2502}   \u{2514}\u{2500}\u{2500} lc
+\u{251c}\u{2500}\u{2500} broken
+\u{251c}\u{2500}\u{2500} c
+\u{2502}   \u{2514}\u{2500}\u{2500} h
+\u{2514}\u{2500}\u{2500} i
+"
+        } else {
+            "\
+\u{251c}\u{2500}\u{2500} a
+\u{2502}   \u{251c}\u{2500}\u{2500} b
+\u{2502}   \u{2502}   \u{2514}\u{2500}\u{2500} g
+\u{2502}   \u{2514}\u{2500}\u{2500} f
+\u{251c}\u{2500}\u{2500} c
+\u{2502}   \u{2514}\u{2500}\u{2500} h
+\u{2514}\u{2500}\u{2500} i
+"
+        };
+        let expected = format!("{}\n{}", fixture.path().display(), expected);
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn compare_reports_differences() {
+        let ours = vec![
+            (PathBuf::from("a"), 'd'),
+            (PathBuf::from("a/b"), 'f'),
+            (PathBuf::from("c"), 'f'),
+        ];
+        let theirs = vec![
+            (PathBuf::from("a"), 'd'),
+            (PathBuf::from("a/b"), 'l'),
+            (PathBuf::from("d"), 'f'),
+        ];
+        let mut out = vec![];
+        let diffs =
+            compare(&mut out, ours.into_iter(), theirs.into_iter()).unwrap();
+        assert_eq!(4, diffs);
+        let expected = "\
+only in walkdir: f a/b
+only in std: l a/b
+only in walkdir: f c
+only in std: f d
+";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+}